@@ -0,0 +1,178 @@
+//! `--alert`: conditions on the level and status mix of the recent records, checked as they arrive.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::filter::Line;
+use crate::protocols::{Level, Record};
+use crate::time;
+
+/// Ratios need this many records in the window before they can trip an alert.
+const MIN_RECORDS: usize = 10;
+
+/// A parsed `--alert` condition such as `error_rate>5%/1m`.
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    text: String,
+    metric: Metric,
+    /// Whether the threshold itself trips the alert (`>=`) or only values above it (`>`).
+    inclusive: bool,
+    /// A fraction for the ratios, a count otherwise.
+    threshold: f64,
+    window: Duration,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Metric {
+    /// Share of records at ERROR or above.
+    ErrorRate,
+    /// Share of request logs with a 5xx status.
+    ServerErrorRate,
+    /// Records at ERROR or above.
+    Errors,
+    /// Records of any kind.
+    Lines,
+}
+
+impl AlertRule {
+    /// Parse `<metric><op><threshold>/<window>`: `error_rate`, `5xx_rate`, `errors`, or `lines`;
+    /// `>` or `>=`; a percentage or fraction for the rates, a count otherwise; and a duration.
+    /// Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("`{s}`: expected e.g. error_rate>5%/1m or errors>=100/5m");
+        let op_at = s.find('>').ok_or_else(invalid)?;
+        let name = s[..op_at].trim();
+        let rest = &s[op_at + 1..];
+        let (inclusive, rest) = match rest.strip_prefix('=') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (threshold, window) = rest.split_once('/').ok_or_else(invalid)?;
+        let metric = match name {
+            "error_rate" => Metric::ErrorRate,
+            "5xx_rate" => Metric::ServerErrorRate,
+            "errors" => Metric::Errors,
+            "lines" => Metric::Lines,
+            _ => {
+                return Err(format!(
+                    "`{name}`: expected error_rate, 5xx_rate, errors, or lines"
+                ));
+            }
+        };
+        let threshold = threshold.trim();
+        let threshold = match threshold.strip_suffix('%') {
+            Some(pct) => pct.trim().parse::<f64>().map(|p| p / 100.0),
+            None => threshold.parse::<f64>(),
+        }
+        .map_err(|_| invalid())?;
+        Ok(Self {
+            text: s.to_string(),
+            metric,
+            inclusive,
+            threshold,
+            window: time::parse_duration(window)?,
+        })
+    }
+
+    fn trips(&self, value: f64) -> bool {
+        if self.inclusive {
+            value >= self.threshold
+        } else {
+            value > self.threshold
+        }
+    }
+}
+
+/// One rule and the records within its window.
+pub struct Alert {
+    rule: AlertRule,
+    /// Arrival, whether the record was an error, and whether it was a 5xx (`None` without a status).
+    recent: VecDeque<(Instant, bool, Option<bool>)>,
+    /// Running totals over `recent`.
+    errors: usize,
+    statuses: usize,
+    server_errors: usize,
+    /// Whether the condition held at the last record; alerts fire again only after it cleared.
+    firing: bool,
+}
+
+impl Alert {
+    pub fn new(rule: AlertRule) -> Self {
+        Self {
+            rule,
+            recent: VecDeque::new(),
+            errors: 0,
+            statuses: 0,
+            server_errors: 0,
+            firing: false,
+        }
+    }
+
+    /// Account for a record that passed the filters; returns the banner text when the
+    /// condition starts to hold.
+    pub fn observe(&mut self, line: &Line<'_>) -> Option<String> {
+        let now = Instant::now();
+        let rec = line.record();
+        let error = rec.and_then(|r| r.level).is_some_and(|l| l >= Level::Error);
+        let server_error = rec
+            .and_then(Record::status)
+            .map(|s| (500..600).contains(&s));
+        self.count((error, server_error), 1);
+        self.recent.push_back((now, error, server_error));
+        while let Some(&(t, error, server_error)) = self.recent.front()
+            && now - t > self.rule.window
+        {
+            self.count((error, server_error), -1);
+            self.recent.pop_front();
+        }
+
+        let ratio = |n: usize, of: usize| {
+            let shown = format!("{:.1}%", n as f64 * 100.0 / of.max(1) as f64);
+            ((of >= MIN_RECORDS).then(|| n as f64 / of as f64), shown)
+        };
+        let (value, shown) = match self.rule.metric {
+            Metric::ErrorRate => ratio(self.errors, self.recent.len()),
+            Metric::ServerErrorRate => ratio(self.server_errors, self.statuses),
+            Metric::Errors => (Some(self.errors as f64), self.errors.to_string()),
+            Metric::Lines => (
+                Some(self.recent.len() as f64),
+                self.recent.len().to_string(),
+            ),
+        };
+        let holds = value.is_some_and(|v| self.rule.trips(v));
+        let fired = holds && !self.firing;
+        self.firing = holds;
+        fired.then(|| format!("{} (now {shown})", self.rule.text))
+    }
+
+    /// Add (`delta` 1) or remove (-1) a record from the running totals.
+    fn count(&mut self, (error, server_error): (bool, Option<bool>), delta: isize) {
+        let apply = |n: &mut usize, on: bool| {
+            if on {
+                *n = n.strict_add_signed(delta);
+            }
+        };
+        apply(&mut self.errors, error);
+        apply(&mut self.statuses, server_error.is_some());
+        apply(&mut self.server_errors, server_error == Some(true));
+    }
+}
+
+/// Error ending the run when an alert trips under `--fail-on-alert`.
+#[derive(Debug)]
+pub struct Tripped(pub String);
+
+impl fmt::Display for Tripped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alert: {}", self.0)
+    }
+}
+
+impl std::error::Error for Tripped {}
+
+/// Whether `e` is a [`Tripped`] alert rather than a failure.
+pub fn is_tripped(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Tripped>())
+}
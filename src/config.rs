@@ -0,0 +1,178 @@
+//! Optional TOML configuration file.
+//!
+//! Looked up at `--config`/`$JLO_CONFIG`, else `$XDG_CONFIG_HOME/jlo/config.toml`
+//! (or `~/.config/jlo/config.toml`). A missing default file is not an error.
+//!
+//! ```toml
+//! [colors]
+//! info = "green"
+//! warn = "bold 214"
+//! error = "#ff5555"
+//! timestamp = "dim"
+//! key = "cyan"
+//!
+//! [protocols.nginx]
+//! compact = true
+//! colors = { message = "dim", faint = "dim" }
+//! ```
+
+use serde::{Deserialize, Deserializer, de};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub colors: ColorConfig,
+    /// Style overrides keyed by protocol name (`nginx`, `tracing`, `gcp`, `azure`, `generic`).
+    pub protocols: BTreeMap<String, ProtocolStyle>,
+}
+
+/// `[protocols.<name>]`: colors and layout for one protocol, on top of the global settings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtocolStyle {
+    pub colors: ColorConfig,
+    pub compact: Option<bool>,
+    pub timestamp: Option<bool>,
+}
+
+/// Color overrides for the semantic palette slots; unset slots keep the theme's colors.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    pub info: Option<Ansi>,
+    pub warn: Option<Ansi>,
+    pub error: Option<Ansi>,
+    pub status3xx: Option<Ansi>,
+    pub faint: Option<Ansi>,
+    pub error_bg: Option<Ansi>,
+    pub timestamp: Option<Ansi>,
+    pub message: Option<Ansi>,
+    pub key: Option<Ansi>,
+    pub value: Option<Ansi>,
+}
+
+/// An escape sequence parsed from a spec such as `bold red`, `214`, or `#ff8800`.
+///
+/// Specs are whitespace-separated attributes (`bold`, `dim`, `italic`, `underline`),
+/// color names (`red`, `bright-blue`, …), 256-color indices, or `#rrggbb` truecolor values;
+/// `on <color>` sets the background. An empty spec means "no styling".
+#[derive(Debug, Clone, Copy)]
+pub struct Ansi(pub &'static str);
+
+impl<'de> Deserialize<'de> for Ansi {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spec = String::deserialize(deserializer)?;
+        Self::try_from(spec).map_err(de::Error::custom)
+    }
+}
+
+impl TryFrom<String> for Ansi {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        let mut codes: Vec<String> = Vec::new();
+        let mut background = false;
+        for token in spec.split_whitespace() {
+            let token = token.to_ascii_lowercase();
+            if token == "on" {
+                background = true;
+                continue;
+            }
+            let attr = match token.as_str() {
+                "bold" => Some("1"),
+                "dim" | "faint" => Some("2"),
+                "italic" => Some("3"),
+                "underline" => Some("4"),
+                "reverse" => Some("7"),
+                _ => None,
+            };
+            if let Some(attr) = attr {
+                codes.push(attr.to_string());
+                continue;
+            }
+            let base = if std::mem::take(&mut background) {
+                40
+            } else {
+                30
+            };
+            codes.push(color_code(&token, base).ok_or_else(|| format!("invalid color `{token}`"))?);
+        }
+        if background {
+            return Err(format!("`on` without a color in `{spec}`"));
+        }
+        if codes.is_empty() {
+            return Ok(Self(""));
+        }
+        // Parsed once at startup and kept for the whole run, like the built-in themes
+        Ok(Self(String::leak(format!("\x1b[{}m", codes.join(";")))))
+    }
+}
+
+/// SGR code for a named, indexed, or hex color; `base` is 30 for foreground, 40 for background.
+fn color_code(token: &str, base: u8) -> Option<String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    if let Some(i) = NAMES.iter().position(|n| *n == token) {
+        return Some((base + i as u8).to_string());
+    }
+    if let Some(name) = token.strip_prefix("bright-")
+        && let Some(i) = NAMES.iter().position(|n| *n == name)
+    {
+        return Some((base + 60 + i as u8).to_string());
+    }
+    if let Ok(n) = token.parse::<u8>() {
+        return Some(format!("{};5;{n}", base + 8));
+    }
+    let hex = token.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(format!(
+        "{};2;{};{};{}",
+        base + 8,
+        channel(0)?,
+        channel(2)?,
+        channel(4)?
+    ))
+}
+
+impl Config {
+    /// Load the configuration from `path`, or from the default location if `None`.
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let (path, required) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match default_path() {
+                Some(p) => (p, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display())));
+            }
+        };
+        toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+    Some(dir.join("jlo").join("config.toml"))
+}
@@ -0,0 +1,72 @@
+//! Grep-style context lines around filter matches for `-A`/`-B`/`-C`.
+
+use std::collections::VecDeque;
+
+/// Holds back the lines before a match and counts down the lines after it.
+pub struct Context {
+    before: usize,
+    after: usize,
+    /// Non-matching lines that may precede the next match, with their line numbers.
+    held: VecDeque<(u64, Vec<u8>)>,
+    /// Lines still to show after the last match.
+    after_left: usize,
+    /// Number of the current line.
+    line: u64,
+    /// Number of the last line shown, to separate non-adjacent groups.
+    last_shown: Option<u64>,
+}
+
+/// What to write for a line, in order.
+pub enum Shown {
+    /// `--` between groups that aren't adjacent.
+    Separator,
+    /// A line shown for context only.
+    Context(Vec<u8>),
+    /// The current line, which matched.
+    Match,
+}
+
+impl Context {
+    pub fn new(before: usize, after: usize) -> Self {
+        Self {
+            before,
+            after,
+            held: VecDeque::with_capacity(before),
+            after_left: 0,
+            line: 0,
+            last_shown: None,
+        }
+    }
+
+    /// Feed the next input line and whether it passed the filters.
+    pub fn push(&mut self, buf: &[u8], matched: bool) -> Vec<Shown> {
+        self.line += 1;
+        let mut shown = Vec::new();
+        if matched {
+            for (line, held) in std::mem::take(&mut self.held) {
+                self.show(line, &mut shown);
+                shown.push(Shown::Context(held));
+            }
+            self.show(self.line, &mut shown);
+            shown.push(Shown::Match);
+            self.after_left = self.after;
+        } else if self.after_left > 0 {
+            self.after_left -= 1;
+            self.show(self.line, &mut shown);
+            shown.push(Shown::Context(buf.to_vec()));
+        } else if self.before > 0 {
+            if self.held.len() == self.before {
+                self.held.pop_front();
+            }
+            self.held.push_back((self.line, buf.to_vec()));
+        }
+        shown
+    }
+
+    fn show(&mut self, line: u64, shown: &mut Vec<Shown>) {
+        if self.last_shown.is_some_and(|last| line > last + 1) {
+            shown.push(Shown::Separator);
+        }
+        self.last_shown = Some(line);
+    }
+}
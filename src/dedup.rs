@@ -0,0 +1,114 @@
+//! Collapsing of repeated messages for `--dedup`.
+
+use jiff::Timestamp;
+use std::collections::VecDeque;
+
+use crate::filter::Line;
+use crate::memory;
+
+/// Remembers the last `window` distinct messages and counts repeats of them.
+///
+/// With a window of 1 only consecutive repeats collapse; larger windows also catch
+/// messages that interleave, e.g. two retry loops.
+pub struct Dedup {
+    window: usize,
+    recent: VecDeque<Seen>,
+}
+
+struct Seen {
+    key: String,
+    message: String,
+    repeats: u64,
+    first: Option<Timestamp>,
+    last: Option<Timestamp>,
+}
+
+impl Dedup {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Note a rendered line. Returns whether it repeats a message in the window (and should
+    /// be skipped), along with the summary of a message that left the window, if any.
+    pub fn push(&mut self, line: &Line<'_>) -> (bool, Option<String>) {
+        let rec = line.record();
+        let message = rec
+            .and_then(|r| r.message.clone())
+            .unwrap_or_else(|| String::from_utf8_lossy(line.buf).into_owned());
+        let key = match rec.and_then(|r| r.level) {
+            Some(level) => format!("{level} {message}"),
+            None => message.clone(),
+        };
+        let ts = line.timestamp().and_then(crate::time::parse_timestamp);
+
+        if let Some(seen) = self.recent.iter_mut().find(|s| s.key == key) {
+            seen.repeats += 1;
+            seen.last = ts.or(seen.last);
+            return (true, None);
+        }
+        self.recent.push_back(Seen {
+            key,
+            message,
+            repeats: 0,
+            first: ts,
+            last: ts,
+        });
+        let expired = if self.recent.len() > self.window {
+            self.recent.pop_front().and_then(|s| self.summary(&s))
+        } else {
+            None
+        };
+        (false, expired)
+    }
+
+    /// Roughly how many bytes the window holds, for `--max-memory`.
+    pub fn memory(&self) -> usize {
+        self.recent
+            .iter()
+            .map(|s| s.key.len() + s.message.len() + memory::ENTRY)
+            .sum()
+    }
+
+    /// Halve the window to stay within `--max-memory`; the summaries of the messages that left it.
+    pub fn shrink(&mut self) -> Vec<String> {
+        self.window = (self.recent.len() / 2).max(1);
+        let mut expired = Vec::new();
+        while self.recent.len() > self.window {
+            if let Some(summary) = self.recent.pop_front().and_then(|s| self.summary(&s)) {
+                expired.push(summary);
+            }
+        }
+        expired
+    }
+
+    /// Summaries of the repeated messages still in the window, at the end of the input.
+    pub fn finish(&mut self) -> Vec<String> {
+        let recent = std::mem::take(&mut self.recent);
+        recent.iter().filter_map(|s| self.summary(s)).collect()
+    }
+
+    /// `(repeated 847×, 00:02:13)`, naming the message if it may not be the line just above.
+    fn summary(&self, seen: &Seen) -> Option<String> {
+        if seen.repeats == 0 {
+            return None;
+        }
+        let mut text = format!("(repeated {}×", seen.repeats);
+        if let (Some(first), Some(last)) = (seen.first, seen.last) {
+            let secs = (last.as_second() - first.as_second()).unsigned_abs();
+            text += &format!(
+                ", {:02}:{:02}:{:02}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            );
+        }
+        text.push(')');
+        if self.window > 1 {
+            text += &format!(": {}", seen.message);
+        }
+        Some(text)
+    }
+}
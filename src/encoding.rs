@@ -0,0 +1,217 @@
+//! UTF-16 input, as exported by some Windows tools, transcoded to the UTF-8 the pipeline reads.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+/// Bytes read from the UTF-16 input at once.
+const CHUNK: usize = 8 * 1024;
+
+/// An input stream as UTF-8.
+pub enum Decoded<R> {
+    Utf8(R),
+    Utf16(BufReader<Utf16<R>>),
+}
+
+/// The text encoding of an input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16 { big_endian: bool },
+}
+
+/// The encoding of an input starting with `head`, and the length of its byte order mark: UTF-16
+/// with a byte order mark, or starting with `{"` in either byte order; else UTF-8.
+pub fn sniff(head: &[u8]) -> (Encoding, usize) {
+    match head {
+        [0xFF, 0xFE, ..] => (Encoding::Utf16 { big_endian: false }, 2),
+        [0xFE, 0xFF, ..] => (Encoding::Utf16 { big_endian: true }, 2),
+        [b'{', 0, b'"', 0, ..] => (Encoding::Utf16 { big_endian: false }, 0),
+        [0, b'{', 0, b'"', ..] => (Encoding::Utf16 { big_endian: true }, 0),
+        _ => (Encoding::Utf8, 0),
+    }
+}
+
+/// `reader` as UTF-8, in the encoding [`sniff`]ed from its first bytes.
+pub fn decode<R: BufRead>(mut reader: R) -> io::Result<Decoded<R>> {
+    let (encoding, bom) = sniff(reader.fill_buf()?);
+    reader.consume(bom);
+    Ok(decode_as(reader, encoding))
+}
+
+/// `reader`, past any byte order mark, from `encoding` as UTF-8.
+pub fn decode_as<R: BufRead>(reader: R, encoding: Encoding) -> Decoded<R> {
+    let Encoding::Utf16 { big_endian } = encoding else {
+        return Decoded::Utf8(reader);
+    };
+    Decoded::Utf16(BufReader::new(Utf16 {
+        inner: reader,
+        big_endian,
+        pending: Vec::new(),
+        out: Vec::new(),
+        pos: 0,
+        eof: false,
+    }))
+}
+
+impl<R: BufRead> Read for Decoded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Utf8(r) => r.read(buf),
+            Self::Utf16(r) => r.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for Decoded<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Self::Utf8(r) => r.fill_buf(),
+            Self::Utf16(r) => r.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        match self {
+            Self::Utf8(r) => r.consume(n),
+            Self::Utf16(r) => r.consume(n),
+        }
+    }
+}
+
+/// UTF-16 bytes read as UTF-8; what doesn't decode becomes U+FFFD.
+pub struct Utf16<R> {
+    inner: R,
+    big_endian: bool,
+    /// Bytes read but not decoded yet: an odd byte or a high surrogate at the end of a chunk.
+    pending: Vec<u8>,
+    /// Decoded UTF-8 and how much of it was read.
+    out: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> Utf16<R> {
+    /// Decode the next chunk into `out`.
+    fn refill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; CHUNK];
+        let n = self.inner.read(&mut chunk)?;
+        self.eof = n == 0;
+        self.pending.extend_from_slice(&chunk[..n]);
+
+        let mut units: Vec<u16> = self
+            .pending
+            .chunks_exact(2)
+            .map(|b| {
+                if self.big_endian {
+                    u16::from_be_bytes([b[0], b[1]])
+                } else {
+                    u16::from_le_bytes([b[0], b[1]])
+                }
+            })
+            .collect();
+        // A surrogate pair may straddle two chunks
+        if !self.eof && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+            units.pop();
+        }
+        let used = if self.eof {
+            self.pending.len()
+        } else {
+            units.len() * 2
+        };
+        self.pending.drain(..used);
+
+        self.out.clear();
+        self.pos = 0;
+        let mut utf8 = [0; 4];
+        for c in char::decode_utf16(units) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.out
+                .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+        // A lone byte at the very end
+        if self.eof && used % 2 == 1 {
+            let c = char::REPLACEMENT_CHARACTER;
+            self.out
+                .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Utf16<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.out.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.refill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &[u8]) -> (bool, String) {
+        let mut decoded = decode(input).unwrap();
+        let utf16 = matches!(decoded, Decoded::Utf16(_));
+        let mut out = String::new();
+        decoded.read_to_string(&mut out).unwrap();
+        (utf16, out)
+    }
+
+    fn utf16(s: &str, big_endian: bool) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn utf8_passes_through() {
+        assert_eq!(read(b"{\"a\":1}\n"), (false, "{\"a\":1}\n".into()));
+        assert_eq!(read(b""), (false, String::new()));
+    }
+
+    #[test]
+    fn utf16_by_bom_or_first_bytes() {
+        let text = "{\"msg\":\"grüß 🎉\"}\n";
+        for big_endian in [false, true] {
+            let bom = if big_endian {
+                [0xFE, 0xFF]
+            } else {
+                [0xFF, 0xFE]
+            };
+            let with_bom = [&bom[..], &utf16(text, big_endian)].concat();
+            assert_eq!(read(&with_bom), (true, text.into()), "{big_endian}");
+            assert_eq!(read(&utf16(text, big_endian)), (true, text.into()));
+        }
+    }
+
+    #[test]
+    fn surrogates_across_chunks() {
+        // The emoji's surrogate pair straddles the end of the first chunk
+        let text = format!("{{\"{}\":\"🎉\"}}", "x".repeat(CHUNK / 2 - 6));
+        let bytes = utf16(&text, false);
+        assert_eq!(bytes[CHUNK - 2..CHUNK], utf16("🎉", false)[..2]);
+        assert_eq!(read(&bytes), (true, text));
+    }
+
+    #[test]
+    fn undecodable_units_are_replaced() {
+        let mut bytes = utf16("{\"a\":\"", false);
+        bytes.extend_from_slice(&[0x00, 0xD8]);
+        bytes.extend_from_slice(&utf16("\"}", false));
+        bytes.push(b'x');
+        assert_eq!(read(&bytes), (true, "{\"a\":\"\u{FFFD}\"}\u{FFFD}".into()));
+    }
+}
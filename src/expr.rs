@@ -0,0 +1,260 @@
+//! The `--filter` expression language: field comparisons combined with `and`, `or`, `not`,
+//! and parentheses, e.g. `(level>=WARN and target~"db") or status>=500`.
+//!
+//! - `field op value` compares like `--where`; `~` and `!~` match a regex
+//! - values are bare words or quoted strings (`"..."` or `'...'`, with `\` escapes)
+//! - `&&`, `||`, and `!` may be used for `and`, `or`, and `not`
+
+use crate::filter::{Condition, Line};
+
+/// A parsed `--filter` expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(Condition),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Op(&'static str),
+    Word(String),
+}
+
+impl Expr {
+    /// Parse an expression. Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(t) => Err(format!("unexpected {}", describe(t))),
+        }
+    }
+
+    pub fn eval(&self, line: &Line<'_>) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(line) && b.eval(line),
+            Self::Or(a, b) => a.eval(line) || b.eval(line),
+            Self::Not(e) => !e.eval(line),
+            Self::Cmp(c) => c.holds(line),
+        }
+    }
+}
+
+/// Comparison operators, longest first so `>=` isn't read as `>`.
+const OPS: [&str; 9] = ["==", "!=", ">=", "<=", "!~", "=", ">", "<", "~"];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            _ if rest.starts_with("&&") => (Token::And, 2),
+            _ if rest.starts_with("||") => (Token::Or, 2),
+            '"' | '\'' => {
+                let (word, len) = quoted(rest)?;
+                (Token::Word(word), len)
+            }
+            _ => match OPS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => (Token::Op(op), op.len()),
+                None if c == '!' => (Token::Not, 1),
+                None => {
+                    let len = rest
+                        .find(|c: char| c.is_whitespace() || "()=!<>~&|\"'".contains(c))
+                        .unwrap_or(rest.len());
+                    let word = &rest[..len];
+                    let token = match word.to_ascii_lowercase().as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "not" => Token::Not,
+                        _ => Token::Word(word.to_string()),
+                    };
+                    (token, len)
+                }
+            },
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The contents of the string literal at the start of `s`, and its length including quotes.
+fn quoted(s: &str) -> Result<(String, usize), String> {
+    let quote = s.chars().next().expect("starts with a quote");
+    let mut out = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                // Keep escapes the regex syntax needs, e.g. `\d`
+                Some((_, c)) if c == quote || c == '\\' => out.push(c),
+                Some((_, c)) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => break,
+            },
+            c if c == quote => return Ok((out, i + 1)),
+            c => out.push(c),
+        }
+    }
+    Err(format!("unterminated string {s}"))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "`(`".into(),
+        Token::Close => "`)`".into(),
+        Token::And => "`and`".into(),
+        Token::Or => "`or`".into(),
+        Token::Not => "`not`".into(),
+        Token::Op(op) => format!("`{op}`"),
+        Token::Word(w) => format!("`{w}`"),
+    }
+}
+
+/// Recursive descent over the tokens; `and` binds tighter than `or`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos) == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing `)`".into());
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(key)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(format!("expected an operator after `{key}`")),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    _ => return Err(format!("expected a value after `{key}{op}`")),
+                };
+                Condition::new(&key, op, &value)
+                    .map(Expr::Cmp)
+                    .map_err(|e| format!("`{key}{op}{value}`: {e}"))
+            }
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::Detection;
+    use serde_json::{Value, json};
+
+    fn eval(expr: &str, v: &Value) -> bool {
+        let buf = v.to_string();
+        let line = Line::new(buf.as_bytes(), Some(v), Detection::default());
+        Expr::parse(expr).unwrap().eval(&line)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr = Expr::parse("a=1 or b=2 and c=3").unwrap();
+        let Expr::Or(_, rhs) = expr else {
+            panic!("expected `or` at the top: {expr:?}");
+        };
+        assert!(matches!(*rhs, Expr::And(..)));
+
+        let v = json!({"a": 1, "b": 0, "c": 0});
+        assert!(eval("a=1 or b=2 and c=3", &v));
+        assert!(!eval("(a=1 or b=2) and c=3", &v));
+    }
+
+    #[test]
+    fn symbols_and_words() {
+        let v = json!({"status": 503, "path": "/api/users"});
+        assert!(eval("status>=500 && !path~'^/health'", &v));
+        assert!(eval("status>=500 AND NOT path~'^/health'", &v));
+        assert!(!eval("not (status>=500 || path=/)", &v));
+        assert!(eval("status==503", &v));
+        assert!(eval("path!~\"^/admin\"", &v));
+    }
+
+    #[test]
+    fn quoted_values() {
+        let v = json!({"msg": "it's \"done\"", "id": "a1"});
+        assert!(eval(r#"msg="it's \"done\"""#, &v));
+        assert!(eval(r#"msg='it\'s "done"'"#, &v));
+        // Regex escapes other than the quote survive
+        assert!(eval(r#"id~"^a\d$""#, &v));
+    }
+
+    #[test]
+    fn levels_compare_by_severity() {
+        assert!(eval("level>=warn", &json!({"level": "error"})));
+        assert!(!eval("level>=warn", &json!({"level": "info"})));
+    }
+
+    #[test]
+    fn errors() {
+        for (expr, error) in [
+            ("(a=1", "missing `)`"),
+            ("a=1)", "unexpected `)`"),
+            ("a=1 b=2", "unexpected `b`"),
+            ("a", "expected an operator after `a`"),
+            ("a=", "expected a value after `a=`"),
+            ("a=1 and", "unexpected end of expression"),
+            ("a='1", "unterminated string '1"),
+            ("a>x", "`a>x`: `x` is not a number"),
+            (r#"a~"(""#, "`a~(`: "),
+        ] {
+            let e = Expr::parse(expr).unwrap_err();
+            assert!(e.starts_with(error), "{expr}: {e}");
+        }
+    }
+}
@@ -0,0 +1,816 @@
+//! Record selection applied before rendering, so dropped lines cost little more than parsing.
+
+use jiff::Timestamp;
+use jsonpath_rust::parser::model::JpQuery;
+use jsonpath_rust::parser::parse_json_path;
+use jsonpath_rust::query::js_path_process;
+use regex::Regex;
+use serde_json::Value;
+use std::cell::{Cell, OnceCell};
+use std::ops::RangeInclusive;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::expr::Expr;
+use crate::json::Fields;
+use crate::links;
+use crate::protocols::generic::Generic;
+use crate::protocols::{self, Detection, Head, JsonProtocol, Level, Record};
+use crate::tail::glob_match;
+use crate::time;
+
+/// Criteria a line must meet to be rendered; the default keeps everything.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Drop records below this severity (`--level`); records without a level are kept.
+    pub min_level: Option<Level>,
+    /// Keep lines whose message or raw text matches any of these (`--grep`).
+    pub grep: Vec<Regex>,
+    /// Keep the lines `grep` would drop instead (`-v`).
+    pub invert: bool,
+    /// Drop lines whose message or raw text matches any of these (`--exclude`).
+    pub exclude: Vec<Regex>,
+    /// Fields every record must have (`--has`), by dotted path or display name.
+    pub has: Vec<String>,
+    /// Fields no record may have (`--missing`). With either set, lines that aren't JSON are dropped.
+    pub missing: Vec<String>,
+    /// Field conditions that must all hold (`--where`).
+    pub conditions: Vec<Condition>,
+    /// Records timestamped before this are dropped (`--since`).
+    pub since: Option<Timestamp>,
+    /// Records timestamped after this are dropped (`--until`).
+    pub until: Option<Timestamp>,
+    /// Trace or request ID that one of the record's correlation fields must hold (`--trace`).
+    pub trace: Option<String>,
+    /// RUST_LOG-style per-target levels for tracing records (`--target`).
+    pub targets: Vec<Directive>,
+    /// A boolean `--filter` expression.
+    pub expr: Option<Expr>,
+    /// HTTP status codes to show (`--status`); records without a status are dropped.
+    pub status: Vec<RangeInclusive<u64>>,
+    /// Request path globs (`--path`); `!`-prefixed ones exclude. With any includes, records
+    /// without a matching path are dropped.
+    pub paths: Vec<String>,
+    /// Drop access-log records whose user agent matches any of these (`--ignore-ua`).
+    pub ignore_ua: Vec<Regex>,
+    /// Drop records whose longest duration field is shorter than this (`--min-duration`),
+    /// and those without one.
+    pub min_duration: Option<Duration>,
+    /// HTTP methods (`--method`), matched case-insensitively; `!`-prefixed ones exclude.
+    pub methods: Vec<String>,
+    /// Keeps a random fraction of the lines that pass everything else (`--sample`).
+    pub sample: Option<Sampler>,
+    /// JSONPath queries that must each match something (`--select`).
+    pub selectors: Vec<Selector>,
+}
+
+/// User agents of health checkers, uptime monitors, and crawlers hidden by `--ignore-health-checks`.
+pub const HEALTH_CHECK_AGENTS: &[&str] = &[
+    "kube-probe",
+    "ELB-HealthChecker",
+    "GoogleHC",
+    "Googlebot",
+    "UptimeRobot",
+];
+
+/// A `--select` JSONPath query, shown in the tail under `name`.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    name: String,
+    path: JpQuery,
+}
+
+/// One `target[span]=level` part of a `--target` filter, as in `RUST_LOG`.
+#[derive(Debug, Clone)]
+pub struct Directive {
+    /// Target prefix, e.g. `my_app::db`; `None` applies to every target.
+    target: Option<String>,
+    /// Only records inside a span of this name.
+    span: Option<String>,
+    /// Least severe level shown; `None` is `off`.
+    level: Option<Level>,
+}
+
+impl Directive {
+    /// Parse `level`, `target`, `target=level`, or `target[span]=level`, with `off` as a level.
+    /// Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parse_level = |l: &str| match l.trim() {
+            "off" => Ok(None),
+            l => Level::parse(l)
+                .map(Some)
+                .ok_or_else(|| format!("`{l}`: unknown level")),
+        };
+        let s = s.trim();
+        let (spec, level) = match s.split_once('=') {
+            Some((spec, level)) => (spec, parse_level(level)?),
+            // A bare level sets the default; a bare target enables all of it
+            None => match parse_level(s) {
+                Ok(level) => ("", level),
+                Err(_) => (s, Some(Level::Trace)),
+            },
+        };
+        let (target, span) = match spec.split_once('[') {
+            Some((target, span)) => {
+                let span = span
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("`{s}`: missing `]`"))?;
+                (target, Some(span.to_string()))
+            }
+            None => (spec, None),
+        };
+        Ok(Self {
+            target: Some(target.trim().to_string()).filter(|t| !t.is_empty()),
+            span,
+            level,
+        })
+    }
+}
+
+/// A `--where` (or `--filter`) comparison of a field against a value.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    key: String,
+    op: Op,
+    value: String,
+    /// The compiled `value` for `~` and `!~`.
+    regex: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Match,
+    NotMatch,
+}
+
+/// One input record with what is known of it, the rest worked out on first use: the top-level
+/// fields of an object, enough to pick its protocol and read its level and timestamp; then its
+/// full JSON value and normalized record. Made once per record and handed to everything that
+/// looks at it.
+pub struct Line<'a> {
+    pub buf: &'a [u8],
+    /// How the protocol is picked, as of when the line was read.
+    pub detection: Detection,
+    /// `None` for JSON other than an object, and for lines that aren't JSON.
+    fields: Option<Fields<'a>>,
+    /// The value parsed up front, where it was.
+    given: Option<&'a Value>,
+    /// The value of an object split into its `fields` only.
+    parsed: OnceCell<Value>,
+    protocol: OnceCell<&'static dyn JsonProtocol>,
+    record: OnceCell<Record>,
+    head: OnceCell<Head<'a>>,
+}
+
+impl<'a> Line<'a> {
+    /// A line with its JSON value parsed already; `None` if it isn't JSON.
+    pub fn new(buf: &'a [u8], value: Option<&'a Value>, detection: Detection) -> Self {
+        Self::with(buf, value.and_then(Fields::of), value, detection)
+    }
+
+    /// A JSON object split into its top-level fields, parsed in full only when needed.
+    pub fn split(buf: &'a [u8], fields: Fields<'a>, detection: Detection) -> Self {
+        Self::with(buf, Some(fields), None, detection)
+    }
+
+    fn with(
+        buf: &'a [u8],
+        fields: Option<Fields<'a>>,
+        given: Option<&'a Value>,
+        detection: Detection,
+    ) -> Self {
+        Self {
+            buf,
+            detection,
+            fields,
+            given,
+            parsed: OnceCell::new(),
+            protocol: OnceCell::new(),
+            record: OnceCell::new(),
+            head: OnceCell::new(),
+        }
+    }
+
+    /// Another line read the same way, e.g. an output of `--jq` for this one.
+    pub fn other<'b>(&self, buf: &'b [u8], value: Option<&'b Value>) -> Line<'b> {
+        Line::new(buf, value, self.detection)
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.given.is_some() || self.fields.is_some()
+    }
+
+    /// The JSON value; `None` for lines that aren't JSON.
+    pub fn value(&self) -> Option<&Value> {
+        if let Some(v) = self.given {
+            return Some(v);
+        }
+        let fields = self.fields.as_ref()?;
+        Some(
+            self.parsed
+                .get_or_init(|| fields.to_value().unwrap_or_default()),
+        )
+    }
+
+    /// The protocol whose view of the record the renderer and the filters use: the detected one,
+    /// or [`Generic`] when that can't make sense of the record after all; `None` for lines that
+    /// aren't JSON.
+    pub fn protocol(&self) -> Option<&'static dyn JsonProtocol> {
+        if !self.is_json() {
+            return None;
+        }
+        Some(*self.protocol.get_or_init(|| {
+            let Some(fields) = &self.fields else {
+                return &Generic;
+            };
+            let p = self.detection.detect(fields);
+            match p.head(fields) {
+                Some(head) => {
+                    let _ = self.head.set(head);
+                    p
+                }
+                None => &Generic,
+            }
+        }))
+    }
+
+    /// The normalized record; `None` for lines that aren't JSON.
+    pub fn record(&self) -> Option<&Record> {
+        let p = self.protocol()?;
+        let v = self.value()?;
+        Some(self.record.get_or_init(|| {
+            p.normalize(v)
+                .or_else(|| Generic.normalize(v))
+                .unwrap_or_default()
+        }))
+    }
+
+    /// The record's level, without parsing the line in full unless that happened already.
+    pub fn level(&self) -> Option<Level> {
+        match self.record.get() {
+            Some(rec) => rec.level,
+            None => self.head()?.level,
+        }
+    }
+
+    /// The record's timestamp as logged, without parsing the line in full unless that happened
+    /// already.
+    pub fn timestamp(&self) -> Option<&str> {
+        match self.record.get() {
+            Some(rec) => rec.timestamp.as_deref(),
+            None => self.head()?.timestamp.as_deref(),
+        }
+    }
+
+    fn head(&self) -> Option<&Head<'a>> {
+        let p = self.protocol()?;
+        Some(self.head.get_or_init(|| {
+            self.fields
+                .as_ref()
+                .and_then(|o| p.head(o))
+                .unwrap_or_default()
+        }))
+    }
+}
+
+impl Filter {
+    pub fn keep(&self, line: &Line<'_>) -> bool {
+        if let Some(min) = self.min_level
+            && let Some(level) = line.level()
+            && level < min
+        {
+            return false;
+        }
+        if (self.since.is_some() || self.until.is_some())
+            && let Some(ts) = line.timestamp().and_then(time::parse_timestamp)
+            && (self.since.is_some_and(|since| ts < since)
+                || self.until.is_some_and(|until| ts > until))
+        {
+            return false;
+        }
+        if !self.targets.is_empty() && !targets_allow(&self.targets, line) {
+            return false;
+        }
+        if let Some(id) = &self.trace
+            && !line.value().is_some_and(|v| has_correlation_id(v, "", id))
+        {
+            return false;
+        }
+        if !self.status.is_empty()
+            && !line
+                .record()
+                .and_then(Record::status)
+                .is_some_and(|code| self.status.iter().any(|r| r.contains(&code)))
+        {
+            return false;
+        }
+        if !self.paths.is_empty()
+            && !include_exclude(
+                &self.paths,
+                line.record().and_then(Record::path).as_deref(),
+                glob_match,
+            )
+        {
+            return false;
+        }
+        if !self.methods.is_empty()
+            && !include_exclude(
+                &self.methods,
+                line.record().and_then(Record::method).as_deref(),
+                str::eq_ignore_ascii_case,
+            )
+        {
+            return false;
+        }
+        if !self.ignore_ua.is_empty()
+            && let Some(ua) = line.record().and_then(Record::user_agent)
+            && self.ignore_ua.iter().any(|re| re.is_match(&ua))
+        {
+            return false;
+        }
+        if let Some(min) = self.min_duration
+            && line
+                .record()
+                .and_then(Record::duration)
+                .is_none_or(|d| d < min)
+        {
+            return false;
+        }
+        if !self.grep.is_empty() && self.grep.iter().any(|re| matches(re, line)) == self.invert {
+            return false;
+        }
+        if self.exclude.iter().any(|re| matches(re, line)) {
+            return false;
+        }
+        if !self.has.is_empty() || !self.missing.is_empty() {
+            let Some(rec) = line.record() else {
+                return false;
+            };
+            let present = |key: &String| rec.lookup(line.value(), key).is_some();
+            if !self.has.iter().all(present) || self.missing.iter().any(present) {
+                return false;
+            }
+        }
+        if !self.conditions.iter().all(|c| c.holds(line)) {
+            return false;
+        }
+        if let Some(expr) = &self.expr
+            && !expr.eval(line)
+        {
+            return false;
+        }
+        if !self
+            .selectors
+            .iter()
+            .all(|sel| line.value().and_then(|v| sel.query(v)).is_some())
+        {
+            return false;
+        }
+        // Last, so the fraction is of the lines that would otherwise be shown
+        if let Some(sample) = &self.sample
+            && !sample.keep()
+        {
+            return false;
+        }
+        true
+    }
+}
+
+impl Selector {
+    /// Parse `$.path` or `name=$.path`; without a name, the path minus its leading `$.` is used.
+    /// Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, path) = match s.split_once('=') {
+            Some((name, path)) if !name.starts_with('$') => (name.trim().to_string(), path.trim()),
+            _ => {
+                let name = s.strip_prefix("$.").or(s.strip_prefix('$')).unwrap_or(s);
+                (name.to_string(), s)
+            }
+        };
+        if name.is_empty() {
+            return Err(format!("`{s}`: missing field name"));
+        }
+        let path = parse_json_path(path).map_err(|e| format!("`{path}`: {e}"))?;
+        Ok(Self { name, path })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The matched value, or an array of all of them if there are several; `None` if nothing matches.
+    pub fn query(&self, v: &Value) -> Option<Value> {
+        let mut found = js_path_process(&self.path, v).ok()?;
+        match found.len() {
+            0 => None,
+            1 => found.pop().map(|r| r.val().clone()),
+            _ => Some(Value::Array(
+                found.into_iter().map(|r| r.val().clone()).collect(),
+            )),
+        }
+    }
+}
+
+/// Random line sampling with a small seeded generator (SplitMix64), so runs can be repeated.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    rate: f64,
+    state: Cell<u64>,
+}
+
+impl Sampler {
+    /// Keep each line with probability `rate`; without a seed, one is derived from the clock.
+    pub fn new(rate: f64, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            nanos ^ u64::from(std::process::id()).rotate_left(32)
+        });
+        Self {
+            rate,
+            state: Cell::new(seed),
+        }
+    }
+
+    fn keep(&self) -> bool {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // The top 53 bits as a uniform float in [0, 1)
+        ((z >> 11) as f64 / (1u64 << 53) as f64) < self.rate
+    }
+}
+
+/// Parse a sampling rate for `--sample`: a fraction like `0.01` or a ratio like `1/100`.
+/// Intended for use as a clap `value_parser`.
+pub fn parse_sample_rate(s: &str) -> Result<f64, String> {
+    let invalid = || format!("`{s}`: expected a fraction like 0.01 or a ratio like 1/100");
+    let rate = match s.split_once('/') {
+        Some((num, den)) => {
+            let num: f64 = num.trim().parse().map_err(|_| invalid())?;
+            let den: f64 = den.trim().parse().map_err(|_| invalid())?;
+            num / den
+        }
+        None => s.trim().parse().map_err(|_| invalid())?,
+    };
+    if !(rate > 0.0 && rate <= 1.0) {
+        return Err(format!(
+            "`{s}`: the rate must be greater than 0 and at most 1"
+        ));
+    }
+    Ok(rate)
+}
+
+/// Parse `500-599`, `5xx`, or a single code for `--status`. Intended for use as a clap `value_parser`.
+pub fn parse_status_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let s = s.trim();
+    let invalid = || format!("`{s}`: expected a status code, a range like 500-599, or 5xx");
+    if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+        let class: u64 = class.parse().map_err(|_| invalid())?;
+        return Ok(class * 100..=class * 100 + 99);
+    }
+    let (lo, hi) = s.split_once('-').unwrap_or((s, s));
+    let lo = lo.trim().parse().map_err(|_| invalid())?;
+    let hi = hi.trim().parse().map_err(|_| invalid())?;
+    if lo > hi {
+        return Err(format!("`{s}`: empty range"));
+    }
+    Ok(lo..=hi)
+}
+
+/// Parse a level name for `--level`. Intended for use as a clap `value_parser`.
+pub fn parse_level(s: &str) -> Result<Level, String> {
+    Level::parse(s).ok_or_else(|| {
+        format!("unknown level `{s}` (expected trace, debug, info, warn, error, or fatal)")
+    })
+}
+
+impl Condition {
+    /// Parse `key=value`, `key!=value`, or a numeric `key>=n`, `key<=n`, `key>n`, `key<n`.
+    /// Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        const OPS: [&str; 6] = ["!=", ">=", "<=", "=", ">", "<"];
+        let (i, token) = OPS
+            .iter()
+            .filter_map(|&token| s.find(token).map(|i| (i, token)))
+            // The leftmost operator wins; at the same position, the two-character one
+            .min_by_key(|&(i, token)| (i, std::cmp::Reverse(token.len())))
+            .ok_or_else(|| format!("`{s}`: expected key=value, key!=value, key>=n, or key<=n"))?;
+        let key = s[..i].trim();
+        if key.is_empty() {
+            return Err(format!("`{s}`: missing field name"));
+        }
+        Self::new(key, token, s[i + token.len()..].trim()).map_err(|e| format!("`{s}`: {e}"))
+    }
+
+    /// A comparison with one of `=` (or `==`), `!=`, `>=`, `<=`, `>`, `<`, or the regex
+    /// matches `~` and `!~`. Ordering needs a number, or a level name for `level`.
+    pub fn new(key: &str, op: &str, value: &str) -> Result<Self, String> {
+        let op = match op {
+            "=" | "==" => Op::Eq,
+            "!=" => Op::Ne,
+            ">=" => Op::Ge,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            "<" => Op::Lt,
+            "~" => Op::Match,
+            "!~" => Op::NotMatch,
+            _ => return Err(format!("unknown operator `{op}`")),
+        };
+        let regex = match op {
+            Op::Match | Op::NotMatch => Some(Regex::new(value).map_err(|e| e.to_string())?),
+            _ => None,
+        };
+        let ordered = matches!(op, Op::Ge | Op::Le | Op::Gt | Op::Lt);
+        if ordered
+            && value.parse::<f64>().is_err()
+            && !(key == "level" && Level::parse(value).is_some())
+        {
+            return Err(format!("`{value}` is not a number"));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            op,
+            value: value.to_string(),
+            regex,
+        })
+    }
+
+    pub fn holds(&self, line: &Line<'_>) -> bool {
+        let field = line
+            .record()
+            .and_then(|r| r.lookup(line.value(), &self.key))
+            .map(|v| match v {
+                Value::String(s) => s,
+                other => other.to_string(),
+            });
+        let Some(field) = field else {
+            return matches!(self.op, Op::Ne | Op::NotMatch);
+        };
+        if let Some(re) = &self.regex {
+            return re.is_match(&field) == (self.op == Op::Match);
+        }
+        // Levels order by severity, whatever their spelling
+        let levels = (self.key == "level")
+            .then(|| Level::parse(&field).zip(Level::parse(&self.value)))
+            .flatten()
+            .map(|(a, b)| (a as u8 as f64, b as u8 as f64));
+        let numbers = levels.or_else(|| {
+            field
+                .parse::<f64>()
+                .ok()
+                .zip(self.value.parse::<f64>().ok())
+        });
+        match self.op {
+            Op::Eq => numbers.map_or(field == self.value, |(a, b)| a == b),
+            Op::Ne => numbers.map_or(field != self.value, |(a, b)| a != b),
+            Op::Ge => numbers.is_some_and(|(a, b)| a >= b),
+            Op::Le => numbers.is_some_and(|(a, b)| a <= b),
+            Op::Gt => numbers.is_some_and(|(a, b)| a > b),
+            Op::Lt => numbers.is_some_and(|(a, b)| a < b),
+            Op::Match | Op::NotMatch => unreachable!("regex operators carry a regex"),
+        }
+    }
+}
+
+/// Whether the most specific matching directive lets a tracing record through: one naming a
+/// span beats one that doesn't, then the longest target wins. Other records always pass.
+fn targets_allow(directives: &[Directive], line: &Line<'_>) -> bool {
+    let Some(v) = line.value() else {
+        return true;
+    };
+    // Not `detect`: other records with a `level` may sniff as tracing without being one
+    let Some(rec) = protocols::tracing::Tracing.normalize(v) else {
+        return true;
+    };
+    let target = rec.logger.as_deref().unwrap_or_default();
+    let in_span = |name: &str| {
+        v.pointer("/span/name").and_then(Value::as_str) == Some(name)
+            || v.get("spans")
+                .and_then(Value::as_array)
+                .is_some_and(|spans| {
+                    spans
+                        .iter()
+                        .any(|s| s.get("name").and_then(Value::as_str) == Some(name))
+                })
+    };
+    let best = directives
+        .iter()
+        .filter(|d| d.target.as_deref().is_none_or(|t| target.starts_with(t)))
+        .filter(|d| d.span.as_deref().is_none_or(in_span))
+        .max_by_key(|d| (d.span.is_some(), d.target.as_deref().map_or(0, str::len)));
+    match best {
+        None => true,
+        Some(d) => d
+            .level
+            .is_some_and(|min| rec.level.is_none_or(|l| l >= min)),
+    }
+}
+
+/// Whether `subject` passes `!`-negatable patterns such as `--path` or `--method`: no
+/// exclude may match, and one of the includes must if there are any. A missing subject only
+/// passes excludes.
+fn include_exclude(
+    patterns: &[String],
+    subject: Option<&str>,
+    is_match: impl Fn(&str, &str) -> bool,
+) -> bool {
+    let mut included = None;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(exclude) => {
+                if subject.is_some_and(|s| is_match(exclude, s)) {
+                    return false;
+                }
+            }
+            None => {
+                included =
+                    Some(included.unwrap_or(false) || subject.is_some_and(|s| is_match(pattern, s)))
+            }
+        }
+    }
+    included.unwrap_or(true)
+}
+
+/// Whether a correlation field anywhere in `v` (`traceparent`, `req_id`, `trace.id`,
+/// `correlation_id`, …) holds `id`; `prefix` is the dotted path of `v`.
+fn has_correlation_id(v: &Value, prefix: &str, id: &str) -> bool {
+    let Value::Object(obj) = v else {
+        return false;
+    };
+    obj.iter().any(|(k, child)| {
+        let path = format!("{prefix}{k}");
+        match child {
+            Value::String(s) => {
+                links::correlation_id(&path, s).is_some_and(|found| found.eq_ignore_ascii_case(id))
+            }
+            Value::Object(_) => has_correlation_id(child, &format!("{path}."), id),
+            _ => false,
+        }
+    })
+}
+
+/// Whether `re` matches the line's message or its raw text.
+fn matches(re: &Regex, line: &Line<'_>) -> bool {
+    if let Some(message) = line.record().and_then(|r| r.message.as_deref())
+        && re.is_match(message)
+    {
+        return true;
+    }
+    re.is_match(&String::from_utf8_lossy(line.buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn holds(condition: &str, v: &Value) -> bool {
+        let buf = v.to_string();
+        let line = Line::new(buf.as_bytes(), Some(v), Detection::default());
+        Condition::parse(condition).unwrap().holds(&line)
+    }
+
+    #[test]
+    fn head_without_parsing() {
+        let buf = br#"{"ts":"2024-05-03T10:11:12Z","method":"GET","path":"/","status":503}"#;
+        let line = Line::split(buf, Fields::split(buf).unwrap(), Detection::default());
+        assert_eq!(line.protocol().map(|p| p.name()), Some("nginx"));
+        assert_eq!(line.level(), Some(Level::Error));
+        assert_eq!(line.timestamp(), Some("2024-05-03T10:11:12Z"));
+        assert!(line.parsed.get().is_none());
+        assert_eq!(line.record().and_then(Record::status), Some(503));
+        assert!(line.parsed.get().is_some());
+    }
+
+    #[test]
+    fn lines_that_are_not_objects() {
+        let v = json!([1, 2]);
+        let line = Line::new(b"[1,2]", Some(&v), Detection::default());
+        assert!(line.is_json());
+        assert_eq!(line.protocol().map(|p| p.name()), Some("generic"));
+        assert_eq!(line.level(), None);
+        let line = Line::new(b"text", None, Detection::default());
+        assert!(!line.is_json());
+        assert!(line.protocol().is_none() && line.value().is_none());
+    }
+
+    #[test]
+    fn condition_operators() {
+        let c = Condition::parse("status>=500").unwrap();
+        assert_eq!(
+            (c.key.as_str(), c.op, c.value.as_str()),
+            ("status", Op::Ge, "500")
+        );
+        // The leftmost operator wins, so values may hold `=`
+        let c = Condition::parse("query=a=b").unwrap();
+        assert_eq!(
+            (c.key.as_str(), c.op, c.value.as_str()),
+            ("query", Op::Eq, "a=b")
+        );
+        let c = Condition::parse(" user != root ").unwrap();
+        assert_eq!(
+            (c.key.as_str(), c.op, c.value.as_str()),
+            ("user", Op::Ne, "root")
+        );
+
+        assert!(Condition::parse("status").is_err());
+        assert!(Condition::parse("=500").is_err());
+        assert!(Condition::parse("status>high").is_err());
+        assert!(Condition::parse("level>=warn").is_ok());
+    }
+
+    #[test]
+    fn condition_values() {
+        let v = json!({"status": 200, "latency": "1.50", "level": "WARNING", "req": {"id": "x"}});
+        assert!(holds("status=200", &v));
+        assert!(holds("status<300", &v));
+        // Numbers compare as numbers, whatever their spelling
+        assert!(holds("latency=1.5", &v));
+        assert!(holds("level>=warn", &v));
+        assert!(!holds("level>warn", &v));
+        assert!(holds("req.id=x", &v));
+        // Missing fields only satisfy `!=`
+        assert!(holds("user!=root", &v));
+        assert!(!holds("user=root", &v));
+        assert!(!holds("user<1", &v));
+    }
+
+    #[test]
+    fn directives() {
+        let d = Directive::parse("warn").unwrap();
+        assert_eq!((d.target, d.span, d.level), (None, None, Some(Level::Warn)));
+        let d = Directive::parse("my_app::db").unwrap();
+        assert_eq!(d.target.as_deref(), Some("my_app::db"));
+        assert_eq!(d.level, Some(Level::Trace));
+        let d = Directive::parse("hyper=off").unwrap();
+        assert_eq!((d.target.as_deref(), d.level), (Some("hyper"), None));
+        let d = Directive::parse("my_app[request]=debug").unwrap();
+        assert_eq!(d.target.as_deref(), Some("my_app"));
+        assert_eq!(d.span.as_deref(), Some("request"));
+        assert_eq!(d.level, Some(Level::Debug));
+
+        assert!(Directive::parse("my_app=loud").is_err());
+        assert!(Directive::parse("my_app[request=debug").is_err());
+    }
+
+    #[test]
+    fn sample_rates() {
+        assert_eq!(parse_sample_rate("0.25"), Ok(0.25));
+        assert_eq!(parse_sample_rate("1/100"), Ok(0.01));
+        assert_eq!(parse_sample_rate("1"), Ok(1.0));
+        for invalid in ["0", "1.5", "2/1", "1/0", "-0.1", "half", "1/x"] {
+            assert!(parse_sample_rate(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn sampling_is_seeded() {
+        let keeps = |seed| {
+            let sampler = Sampler::new(0.1, Some(seed));
+            (0..10_000).filter(|_| sampler.keep()).count()
+        };
+        assert_eq!(keeps(7), keeps(7));
+        assert!((900..1100).contains(&keeps(7)), "{}", keeps(7));
+    }
+
+    #[test]
+    fn status_ranges() {
+        assert_eq!(parse_status_range("5xx"), Ok(500..=599));
+        assert_eq!(parse_status_range("4XX"), Ok(400..=499));
+        assert_eq!(parse_status_range("500-504"), Ok(500..=504));
+        assert_eq!(parse_status_range(" 404 "), Ok(404..=404));
+        for invalid in ["504-500", "xx", "5x", "abc", "500-"] {
+            assert!(parse_status_range(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn include_exclude_patterns() {
+        let patterns = ["/api/*".to_string(), "!/api/health".to_string()];
+        assert!(include_exclude(&patterns, Some("/api/users"), glob_match));
+        assert!(!include_exclude(&patterns, Some("/api/health"), glob_match));
+        assert!(!include_exclude(
+            &patterns,
+            Some("/static/app.js"),
+            glob_match
+        ));
+        assert!(!include_exclude(&patterns, None, glob_match));
+        let excludes = ["!GET".to_string()];
+        assert!(include_exclude(&excludes, None, str::eq_ignore_ascii_case));
+        assert!(!include_exclude(
+            &excludes,
+            Some("get"),
+            str::eq_ignore_ascii_case
+        ));
+    }
+}
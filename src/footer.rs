@@ -0,0 +1,85 @@
+//! `--rate`: a status line at the bottom of the terminal, redrawn under the streaming records.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::Palette;
+use crate::filter::Line;
+use crate::protocols::Level;
+use crate::time::humanize_duration;
+
+/// How far back the rates and the p95 look.
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// How often the text is recomputed; redraws in between repeat it.
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// Lines per second, errors per second, and the p95 latency over the last [`WINDOW`].
+#[derive(Default)]
+pub struct RateFooter {
+    /// Arrival, whether it was an error, and its duration in seconds, if any.
+    recent: VecDeque<(Instant, bool, Option<f64>)>,
+    started: Option<Instant>,
+    text: String,
+    refreshed: Option<Instant>,
+    /// Whether the footer is currently on screen.
+    drawn: bool,
+}
+
+impl RateFooter {
+    /// Account for a record that is being shown.
+    pub fn observe(&mut self, line: &Line<'_>) {
+        let now = Instant::now();
+        self.started.get_or_insert(now);
+        let rec = line.record();
+        let error = rec.and_then(|r| r.level).is_some_and(|l| l >= Level::Error);
+        let secs = rec.and_then(|r| r.duration()).map(|d| d.as_secs_f64());
+        self.recent.push_back((now, error, secs));
+    }
+
+    /// Remove the footer so that output can take its place.
+    pub fn clear(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.drawn {
+            self.drawn = false;
+            out.write_all(b"\r\x1b[2K")?;
+        }
+        Ok(())
+    }
+
+    /// Draw the footer below the output, without a newline so the next clear can erase it.
+    pub fn draw(&mut self, out: &mut dyn Write, pal: Palette) -> io::Result<()> {
+        let now = Instant::now();
+        if self.refreshed.is_none_or(|t| now - t >= REFRESH) {
+            self.refreshed = Some(now);
+            self.refresh(now);
+        }
+        self.clear(out)?;
+        write!(out, "{}", pal.paint(pal.faint, &self.text))?;
+        self.drawn = true;
+        out.flush()
+    }
+
+    fn refresh(&mut self, now: Instant) {
+        while self.recent.front().is_some_and(|(t, ..)| now - *t > WINDOW) {
+            self.recent.pop_front();
+        }
+        let span = self
+            .started
+            .map_or(WINDOW, |s| (now - s).min(WINDOW))
+            .as_secs_f64()
+            .max(1.0);
+        let lines = self.recent.len() as f64 / span;
+        let errors = self.recent.iter().filter(|(_, e, _)| *e).count() as f64 / span;
+        let mut secs: Vec<f64> = self.recent.iter().filter_map(|(.., s)| *s).collect();
+        self.text = format!("⏵ {lines:.1} lines/s  {errors:.1} errors/s");
+        if !secs.is_empty() {
+            secs.sort_by(f64::total_cmp);
+            let p95 = secs[((secs.len() - 1) as f64 * 0.95).round() as usize];
+            if let Ok(p95) = Duration::try_from_secs_f64(p95) {
+                self.text += &format!("  p95 {}", humanize_duration(p95));
+            }
+        }
+        self.text += "  (last 10s)";
+    }
+}
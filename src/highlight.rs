@@ -0,0 +1,132 @@
+//! Coloring of regex matches in the rendered output for `--highlight`.
+
+use regex::bytes::Regex;
+
+/// Colors for the patterns, in order: black on yellow, cyan, magenta, green, red, blue.
+const COLORS: [&str; 6] = [
+    "\x1b[30;43m",
+    "\x1b[30;46m",
+    "\x1b[30;45m",
+    "\x1b[30;42m",
+    "\x1b[30;41m",
+    "\x1b[30;44m",
+];
+
+/// The `--highlight` patterns, each with its own color.
+#[derive(Clone, Debug, Default)]
+pub struct Highlights {
+    patterns: Vec<Regex>,
+}
+
+impl Highlights {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self { patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Copy `rendered` to `out`, coloring matches in its visible text.
+    ///
+    /// Patterns are matched per line against the text without escape sequences, so colors and
+    /// hyperlinks inside a match don't get in the way; the styles active at the end of a match
+    /// are restored after it. Where matches overlap, the earlier pattern wins.
+    pub fn apply(&self, rendered: &[u8], reset: &str, out: &mut Vec<u8>) {
+        for line in rendered.split_inclusive(|&b| b == b'\n') {
+            self.apply_line(line, reset, out);
+        }
+    }
+
+    fn apply_line(&self, line: &[u8], reset: &str, out: &mut Vec<u8>) {
+        // Visible bytes, and for each the position in `line`
+        let mut visible = Vec::with_capacity(line.len());
+        let mut positions = Vec::with_capacity(line.len());
+        let mut i = 0;
+        while i < line.len() {
+            match escape_len(&line[i..]) {
+                Some(len) => i += len,
+                None => {
+                    visible.push(line[i]);
+                    positions.push(i);
+                    i += 1;
+                }
+            }
+        }
+
+        // Matches as ranges of `line`, without overlaps
+        let mut spans: Vec<(usize, usize, &str)> = Vec::new();
+        for (re, color) in self.patterns.iter().zip(COLORS.iter().cycle()) {
+            for m in re.find_iter(&visible).filter(|m| !m.is_empty()) {
+                let (start, end) = (positions[m.start()], positions[m.end() - 1] + 1);
+                if spans.iter().all(|&(s, e, _)| end <= s || start >= e) {
+                    spans.push((start, end, color));
+                }
+            }
+        }
+        if spans.is_empty() {
+            out.extend_from_slice(line);
+            return;
+        }
+        spans.sort_by_key(|&(start, ..)| start);
+
+        // SGR sequences since the last reset, to restore after each match
+        let mut active: Vec<u8> = Vec::new();
+        let mut spans = spans.into_iter().peekable();
+        let mut current: Option<(usize, usize, &str)> = None;
+        let mut i = 0;
+        while i < line.len() {
+            if current.is_none()
+                && let Some(&(start, ..)) = spans.peek()
+                && start == i
+            {
+                let span = spans.next().expect("peeked");
+                out.extend_from_slice(span.2.as_bytes());
+                current = Some(span);
+            }
+            let len = escape_len(&line[i..]);
+            let chunk = &line[i..i + len.unwrap_or(1)];
+            if len.is_some() && chunk.ends_with(b"m") && chunk.starts_with(b"\x1b[") {
+                if matches!(chunk, b"\x1b[0m" | b"\x1b[m") {
+                    active.clear();
+                } else {
+                    active.extend_from_slice(chunk);
+                }
+                // Inside a match, the highlight color stays
+                if current.is_none() {
+                    out.extend_from_slice(chunk);
+                }
+            } else {
+                out.extend_from_slice(chunk);
+            }
+            i += chunk.len();
+            if let Some((_, end, _)) = current
+                && i >= end
+            {
+                out.extend_from_slice(reset.as_bytes());
+                out.extend_from_slice(&active);
+                current = None;
+            }
+        }
+    }
+}
+
+/// Length of the escape sequence at the start of `s`: CSI (`ESC [ … final`) or OSC
+/// (`ESC ] … BEL` or `ESC ] … ESC \`).
+fn escape_len(s: &[u8]) -> Option<usize> {
+    match s {
+        [0x1b, b'[', rest @ ..] => {
+            let end = rest.iter().position(|b| (0x40..=0x7e).contains(b))?;
+            Some(end + 3)
+        }
+        [0x1b, b']', rest @ ..] => {
+            let end = rest
+                .windows(2)
+                .position(|w| w[0] == 0x07 || w == b"\x1b\\")
+                .map(|p| if rest[p] == 0x07 { p + 1 } else { p + 2 })
+                .or_else(|| (rest.last() == Some(&0x07)).then_some(rest.len()))?;
+            Some(end + 2)
+        }
+        _ => None,
+    }
+}
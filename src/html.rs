@@ -0,0 +1,234 @@
+//! HTML output: the pretty rendering with its ANSI colors turned into styled `<span>`s.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+const PRE_STYLE: &str = "background:#1e1e1e;color:#d4d4d4;padding:1em;\
+    font-family:ui-monospace,SFMono-Regular,Menlo,Consolas,monospace;font-size:13px;\
+    line-height:1.4;white-space:pre-wrap;overflow-wrap:anywhere";
+
+/// Opening markup; `page` wraps the `<pre>` in a standalone document.
+pub fn write_prologue(out: &mut dyn Write, page: bool) -> io::Result<()> {
+    if page {
+        out.write_all(
+            b"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>jlo</title>\n</head>\n\
+              <body style=\"margin:0;background:#1e1e1e\">\n",
+        )?;
+    }
+    writeln!(out, "<pre class=\"jlo\" style=\"{PRE_STYLE}\">")
+}
+
+/// Closing markup matching [`write_prologue`].
+pub fn write_epilogue(out: &mut dyn Write, page: bool) -> io::Result<()> {
+    out.write_all(b"</pre>\n")?;
+    if page {
+        out.write_all(b"</body>\n</html>\n")?;
+    }
+    Ok(())
+}
+
+/// Convert ANSI-colored text to escaped HTML; all spans are closed at the end of `ansi`.
+///
+/// OSC 8 hyperlinks become `<a>` elements.
+pub fn write_ansi(out: &mut dyn Write, ansi: &[u8]) -> io::Result<()> {
+    let text = String::from_utf8_lossy(ansi);
+    let mut html = String::with_capacity(text.len() + 64);
+    let mut style = Style::default();
+    let mut open = false;
+    let mut link = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&']') {
+            chars.next();
+            // OSC: runs until BEL or ST (`ESC \\`)
+            let mut osc = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\x07' => break,
+                    '\x1b' => {
+                        chars.next_if_eq(&'\\');
+                        break;
+                    }
+                    c => osc.push(c),
+                }
+            }
+            if let Some((_params, url)) = osc.strip_prefix("8;").and_then(|o| o.split_once(';')) {
+                if link {
+                    html.push_str("</a>");
+                    link = false;
+                }
+                if !url.is_empty() {
+                    html.push_str("<a href=\"");
+                    escape(&mut html, url);
+                    html.push_str("\">");
+                    link = true;
+                }
+            }
+            continue;
+        }
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            let mut params = String::new();
+            let mut terminator = None;
+            for c in chars.by_ref() {
+                if c.is_ascii_digit() || c == ';' {
+                    params.push(c);
+                } else {
+                    terminator = Some(c);
+                    break;
+                }
+            }
+            // Only SGR (colors and attributes) carries over; other sequences are dropped
+            if terminator == Some('m') {
+                style.apply(&params);
+                if open {
+                    html.push_str("</span>");
+                    open = false;
+                }
+                let css = style.css();
+                if !css.is_empty() {
+                    let _ = write!(html, "<span style=\"{css}\">");
+                    open = true;
+                }
+            }
+            continue;
+        }
+        escape(&mut html, c.encode_utf8(&mut [0; 4]));
+    }
+    if link {
+        html.push_str("</a>");
+    }
+    if open {
+        html.push_str("</span>");
+    }
+    out.write_all(html.as_bytes())
+}
+
+fn escape(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Style {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn apply(&mut self, params: &str) {
+        let codes: Vec<u16> = if params.is_empty() {
+            vec![0]
+        } else {
+            params.split(';').map(|p| p.parse().unwrap_or(0)).collect()
+        };
+        let mut i = 0;
+        while i < codes.len() {
+            match codes[i] {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                n @ 30..=37 => self.fg = Some(basic(n - 30)),
+                n @ 90..=97 => self.fg = Some(basic(n - 90 + 8)),
+                n @ 40..=47 => self.bg = Some(basic(n - 40)),
+                n @ 100..=107 => self.bg = Some(basic(n - 100 + 8)),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                n @ (38 | 48) => {
+                    let color = match codes.get(i + 1) {
+                        Some(5) => {
+                            let c = codes.get(i + 2).map(|&c| indexed(c.min(255) as u8));
+                            i += 2;
+                            c
+                        }
+                        Some(2) => {
+                            let rgb = codes.get(i + 2..i + 5).map(|c| {
+                                format!("#{:02x}{:02x}{:02x}", c[0] as u8, c[1] as u8, c[2] as u8)
+                            });
+                            i += 4;
+                            rgb
+                        }
+                        _ => None,
+                    };
+                    if n == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(fg) = &self.fg {
+            let _ = write!(css, "color:{fg};");
+        }
+        if let Some(bg) = &self.bg {
+            let _ = write!(css, "background:{bg};");
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.dim {
+            css.push_str("opacity:.6;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+}
+
+/// The 16 basic colors, as rendered by common dark terminal themes.
+fn basic(n: u16) -> String {
+    const BASIC: [&str; 16] = [
+        "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+        "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+    ];
+    BASIC[n as usize % 16].to_string()
+}
+
+/// xterm 256-color palette: 16 basic colors, a 6×6×6 cube, and a 24-step gray ramp.
+fn indexed(n: u8) -> String {
+    match n {
+        0..=15 => basic(n.into()),
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(n / 36),
+                level(n / 6 % 6),
+                level(n % 6)
+            )
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            format!("#{gray:02x}{gray:02x}{gray:02x}")
+        }
+    }
+}
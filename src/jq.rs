@@ -0,0 +1,85 @@
+//! `--jq` expressions, evaluated with [jaq](https://github.com/01mf02/jaq).
+
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Native, RcIter};
+use jaq_json::Val;
+use serde_json::Value;
+use std::fmt;
+
+/// A compiled jq filter applied to every parsed line before rendering.
+#[derive(Clone)]
+pub struct Jq {
+    expr: String,
+    filter: jaq_core::Filter<Native<Val>>,
+}
+
+impl Jq {
+    /// Compile a jq expression with the jq standard library.
+    pub fn new(expr: &str) -> Result<Self, String> {
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader
+            .load(
+                &arena,
+                File {
+                    code: expr,
+                    path: (),
+                },
+            )
+            .map_err(|errs| {
+                let mut msgs = Vec::new();
+                for (_, err) in errs {
+                    match err {
+                        jaq_core::load::Error::Io(e) => {
+                            msgs.extend(e.into_iter().map(|(path, e)| format!("{path}: {e}")));
+                        }
+                        jaq_core::load::Error::Lex(e) => msgs.extend(
+                            e.into_iter()
+                                .map(|(exp, at)| syntax_error(exp.as_str(), expr, at)),
+                        ),
+                        jaq_core::load::Error::Parse(e) => msgs.extend(
+                            e.into_iter()
+                                .map(|(exp, at)| syntax_error(exp.as_str(), expr, at)),
+                        ),
+                    }
+                }
+                msgs.join("; ")
+            })?;
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errs| {
+                errs.into_iter()
+                    .flat_map(|(_, e)| e)
+                    .map(|(name, _)| format!("undefined `{name}`"))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })?;
+        Ok(Self {
+            expr: expr.to_string(),
+            filter,
+        })
+    }
+
+    /// The outputs of the filter for `v`: none drops the line, several become separate lines.
+    pub fn run(&self, v: Value) -> Vec<Result<Value, String>> {
+        let inputs = RcIter::new(core::iter::empty());
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(v)))
+            .map(|r| r.map(Value::from).map_err(|e| e.to_string()))
+            .collect()
+    }
+}
+
+impl fmt::Debug for Jq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Jq").field(&self.expr).finish()
+    }
+}
+
+/// `expected <what>` with the column where `at`, a slice of `expr`, starts.
+fn syntax_error(expected: &str, expr: &str, at: &str) -> String {
+    let offset = (at.as_ptr() as usize).saturating_sub(expr.as_ptr() as usize);
+    let col = expr.get(..offset).map_or(0, |s| s.chars().count()) + 1;
+    format!("expected {expected} at column {col}")
+}
@@ -0,0 +1,257 @@
+//! Parsing of input lines, as JSON5 too with `--relaxed`. Objects are first split into their
+//! top-level [`Fields`], which is all that picking a protocol and the level and time filters read.
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+
+/// The JSON value of a line, if it is one. With `relaxed` (`--relaxed`), also accept comments,
+/// trailing commas, single quotes, unquoted keys, and `NaN`/`Infinity` (which become `null`),
+/// as hand-edited files and config dumps have them.
+pub fn parse(buf: &[u8], relaxed: bool) -> Option<Value> {
+    serde_json::from_slice(buf).ok().or_else(|| {
+        if !relaxed {
+            return None;
+        }
+        json5::from_str(str::from_utf8(buf).ok()?).ok()
+    })
+}
+
+/// The top-level fields of a JSON object: split out of the line with their values left
+/// unparsed, or those of a value parsed already.
+#[derive(Debug)]
+pub enum Fields<'a> {
+    Raw(Vec<(Cow<'a, str>, &'a RawValue)>),
+    Parsed(&'a Map<String, Value>),
+}
+
+impl<'a> Fields<'a> {
+    /// The fields of `buf` if it holds a single JSON object; the values are checked, not parsed.
+    pub fn split(buf: &'a [u8]) -> Option<Self> {
+        serde_json::from_slice(buf).ok()
+    }
+
+    /// The fields of `v` if it is an object.
+    pub fn of(v: &'a Value) -> Option<Self> {
+        v.as_object().map(Self::Parsed)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        match self {
+            Self::Raw(fields) => fields.iter().any(|(k, _)| k == key),
+            Self::Parsed(obj) => obj.contains_key(key),
+        }
+    }
+
+    /// A field's value; the last one of duplicate keys, as when parsed.
+    fn raw(&self, key: &str) -> Option<&'a RawValue> {
+        match self {
+            Self::Raw(fields) => fields.iter().rev().find(|(k, _)| k == key).map(|(_, v)| *v),
+            Self::Parsed(_) => None,
+        }
+    }
+
+    /// A string field, borrowed unless it has escapes.
+    pub fn str(&self, key: &str) -> Option<Cow<'a, str>> {
+        match self {
+            Self::Raw(_) => {
+                let text = self.raw(key)?.get();
+                let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+                if inner.contains('\\') {
+                    serde_json::from_str(text).ok().map(Cow::Owned)
+                } else {
+                    Some(Cow::Borrowed(inner))
+                }
+            }
+            Self::Parsed(obj) => obj.get(key)?.as_str().map(Cow::Borrowed),
+        }
+    }
+
+    /// A field holding a non-negative integer.
+    pub fn u64(&self, key: &str) -> Option<u64> {
+        match self {
+            Self::Raw(_) => self.raw(key)?.get().parse().ok(),
+            Self::Parsed(obj) => obj.get(key)?.as_u64(),
+        }
+    }
+
+    /// A field holding an object.
+    pub fn object(&self, key: &str) -> Option<Fields<'a>> {
+        match self {
+            Self::Raw(_) => serde_json::from_str(self.raw(key)?.get()).ok(),
+            Self::Parsed(obj) => obj.get(key).and_then(Self::of),
+        }
+    }
+
+    /// A field's value, parsed if it wasn't already.
+    pub fn value(&self, key: &str) -> Option<Cow<'a, Value>> {
+        match self {
+            Self::Raw(_) => serde_json::from_str(self.raw(key)?.get())
+                .ok()
+                .map(Cow::Owned),
+            Self::Parsed(obj) => obj.get(key).map(Cow::Borrowed),
+        }
+    }
+
+    /// The object as a value, parsing each field's value where that wasn't done yet.
+    pub fn to_value(&self) -> Option<Value> {
+        match self {
+            Self::Raw(fields) => {
+                let mut obj = Map::with_capacity(fields.len());
+                for (k, v) in fields {
+                    obj.insert(k.to_string(), serde_json::from_str(v.get()).ok()?);
+                }
+                Some(Value::Object(obj))
+            }
+            Self::Parsed(obj) => Some(Value::Object((*obj).clone())),
+        }
+    }
+
+    /// A field as text: a string, or the JSON of anything else, e.g. an epoch.
+    pub fn text(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.str(key).or_else(|| match self {
+            Self::Raw(_) => self.raw(key).map(|v| Cow::Borrowed(v.get())),
+            Self::Parsed(obj) => obj.get(key).map(|v| Cow::Owned(v.to_string())),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Fields<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Object;
+
+        impl<'de> Visitor<'de> for Object {
+            type Value = Fields<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(16));
+                while let Some((Key(key), value)) = map.next_entry()? {
+                    fields.push((key, value));
+                }
+                Ok(Fields::Raw(fields))
+            }
+        }
+
+        deserializer.deserialize_map(Object)
+    }
+}
+
+/// An object key, borrowed from the line unless it has escapes.
+struct Key<'a>(Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for Key<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Str;
+
+        impl<'de> Visitor<'de> for Str {
+            type Value = Key<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, s: &'de str) -> Result<Self::Value, E> {
+                Ok(Key(Cow::Borrowed(s)))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+                Ok(Key(Cow::Owned(s.to_string())))
+            }
+        }
+
+        deserializer.deserialize_str(Str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &str, relaxed: bool) -> Option<String> {
+        parse(line.as_bytes(), relaxed).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn fields_are_split_without_parsing() {
+        let line = br#"{"msg":"plain","esc":"a\"b","n":200,"s":"404","o":{"k":"v"},"m\u00e9":1,"msg":"last"}"#;
+        let o = Fields::split(line).unwrap();
+        let Fields::Raw(fields) = &o else {
+            panic!("expected raw fields");
+        };
+        assert!(matches!(fields[0].0, Cow::Borrowed("msg")));
+        assert_eq!(fields[5].0, "mé");
+        assert!(matches!(o.str("msg"), Some(Cow::Borrowed("last"))));
+        assert!(matches!(o.str("esc"), Some(Cow::Owned(s)) if s == "a\"b"));
+        assert_eq!(o.str("n"), None);
+        assert_eq!(o.u64("n"), Some(200));
+        assert_eq!(o.u64("s"), None);
+        assert_eq!(o.text("n").as_deref(), Some("200"));
+        assert_eq!(o.object("o").and_then(|o| o.str("k")).as_deref(), Some("v"));
+        assert!(o.contains_key("mé"));
+        assert_eq!(
+            o.value("o").as_deref(),
+            Some(&serde_json::json!({"k": "v"}))
+        );
+        assert_eq!(
+            o.to_value(),
+            serde_json::from_slice(line).ok(),
+            "same as a full parse, duplicate keys and all"
+        );
+    }
+
+    #[test]
+    fn only_valid_objects_split() {
+        for line in [
+            "[1,2]",
+            "\"text\"",
+            r#"{"a":[1,}"#,
+            r#"{"a":"\q"}"#,
+            r#"{"a":1} trailing"#,
+            r#"{"a":1}{"b":2}"#,
+            "{a:1}",
+        ] {
+            assert!(Fields::split(line.as_bytes()).is_none(), "{line}");
+        }
+        assert!(Fields::split(br#"  {"a" : 1 }  "#).is_some());
+    }
+
+    #[test]
+    fn numbers_as_written() {
+        for line in [
+            r#"{"a":0.050,"b":1e3,"c":-2.5E-3,"d":7}"#,
+            r#"{"n":[1,2.0,"3.0",{"x":"\"4.0","y":4.00}],"z":-0.0}"#,
+            r#"{"big":123456789012345678901234567890,"f":1.10}"#,
+        ] {
+            assert_eq!(text(line, false).as_deref(), Some(line));
+        }
+        // The last of duplicate keys wins, with the number it was written as
+        assert_eq!(
+            text(r#"{"k":1.5,"k":2.50}"#, false).as_deref(),
+            Some(r#"{"k":2.50}"#)
+        );
+    }
+
+    #[test]
+    fn key_order_is_kept() {
+        let keys: Vec<String> = (0..40).rev().map(|i| format!(r#""k{i}":{i}.0"#)).collect();
+        let line = format!("{{{}}}", keys.join(","));
+        assert_eq!(text(&line, false), Some(line));
+    }
+
+    #[test]
+    fn relaxed() {
+        let line = "{a:1, 'b':'x', /* c */ d:[1,2,],}";
+        assert_eq!(text(line, false), None);
+        assert_eq!(
+            text(line, true).as_deref(),
+            Some(r#"{"a":1,"b":"x","d":[1,2]}"#)
+        );
+        assert_eq!(text("not json", true), None);
+    }
+}
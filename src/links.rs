@@ -0,0 +1,153 @@
+//! OSC 8 terminal hyperlinks for trace IDs, request IDs, and URLs (`--hyperlinks`).
+
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Link targets; the templates contain an `{id}` placeholder.
+#[derive(Clone, Default)]
+pub struct Links {
+    pub enabled: bool,
+    pub trace_template: Option<Arc<str>>,
+    pub request_template: Option<Arc<str>>,
+}
+
+impl Links {
+    /// The link target for a key=value field, if any.
+    ///
+    /// URLs link to themselves; trace and request IDs link through their templates.
+    /// W3C `traceparent` headers and Cloud Logging `projects/…/traces/<id>` names
+    /// are reduced to the bare trace ID first. URLs holding control characters are not linked,
+    /// as they could end the escape sequence early and inject their own.
+    pub fn url_for(&self, key: &str, value: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        if value.starts_with("https://") || value.starts_with("http://") {
+            return (!value.contains(char::is_control)).then(|| value.to_string());
+        }
+        let (template, id) = match id_kind(key)? {
+            IdKind::Trace => (self.trace_template.as_deref()?, trace_id(value)),
+            IdKind::Request => (self.request_template.as_deref()?, value),
+        };
+        let id = utf8_percent_encode(id, NON_ALPHANUMERIC).to_string();
+        Some(template.replace("{id}", &id))
+    }
+}
+
+enum IdKind {
+    Trace,
+    Request,
+}
+
+/// Whether `key` (in any spelling, e.g. `trace.id` or `X-Request-ID`) holds a trace or request ID.
+fn id_kind(key: &str) -> Option<IdKind> {
+    let key: String = key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    match key.as_str() {
+        "trace" | "traceid" | "traceparent" | "xcloudtracecontext" => Some(IdKind::Trace),
+        "req" | "reqid" | "requestid" | "xrequestid" | "correlationid" | "operationid" => {
+            Some(IdKind::Request)
+        }
+        _ => None,
+    }
+}
+
+/// The bare ID if `key` is a correlation field: a trace or request ID, as for the links.
+pub fn correlation_id<'a>(key: &str, value: &'a str) -> Option<&'a str> {
+    match id_kind(key)? {
+        IdKind::Trace => Some(trace_id(value)),
+        IdKind::Request => Some(value),
+    }
+}
+
+/// The record's trace ID, or else its request ID, from the first correlation field anywhere in
+/// `v` (`traceparent`, `trace.id`, `req_id`, …).
+pub fn find_correlation_id(v: &Value) -> Option<&str> {
+    fn walk<'a>(v: &'a Value, prefix: &str, trace_only: bool) -> Option<&'a str> {
+        let Value::Object(obj) = v else {
+            return None;
+        };
+        obj.iter().find_map(|(k, child)| {
+            let path = format!("{prefix}{k}");
+            match (child, id_kind(&path)) {
+                (Value::String(s), Some(IdKind::Trace)) => Some(trace_id(s)),
+                (Value::String(s), Some(IdKind::Request)) if !trace_only => Some(s.as_str()),
+                (Value::Object(_), _) => walk(child, &format!("{path}."), trace_only),
+                _ => None,
+            }
+        })
+    }
+    walk(v, "", true).or_else(|| walk(v, "", false))
+}
+
+fn trace_id(value: &str) -> &str {
+    // traceparent: version-traceid-spanid-flags
+    let mut parts = value.split('-');
+    if let (Some(_), Some(id), Some(_), Some(_), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) {
+        return id;
+    }
+    // projects/p/traces/<id>, or <id>/<span>;o=1 (X-Cloud-Trace-Context)
+    let id = value.rsplit_once("/traces/").map_or(value, |(_, id)| id);
+    id.split(['/', ';']).next().unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links() -> Links {
+        Links {
+            enabled: true,
+            trace_template: Some("https://traces.example/{id}".into()),
+            request_template: None,
+        }
+    }
+
+    #[test]
+    fn urls_link_to_themselves() {
+        assert_eq!(
+            links()
+                .url_for("url", "https://example.com/a?b=c")
+                .as_deref(),
+            Some("https://example.com/a?b=c")
+        );
+        assert_eq!(links().url_for("url", "ftp://example.com"), None);
+    }
+
+    #[test]
+    fn urls_with_control_characters_are_not_linked() {
+        for url in [
+            "https://example.com/\x1b\\\x1b]8;;https://evil.example\x1b\\",
+            "https://example.com/\x07",
+            "https://example.com/\x7f",
+            "https://example.com/\u{9b}",
+        ] {
+            assert_eq!(links().url_for("url", url), None, "{url:?}");
+        }
+    }
+
+    #[test]
+    fn ids_are_percent_encoded_into_the_template() {
+        assert_eq!(
+            links().url_for("trace_id", "ab\x1b\\x").as_deref(),
+            Some("https://traces.example/ab%1B%5Cx")
+        );
+        assert_eq!(
+            links()
+                .url_for("traceparent", "00-4bf92f35-00f067aa-01")
+                .as_deref(),
+            Some("https://traces.example/4bf92f35")
+        );
+        assert_eq!(links().url_for("req_id", "abc"), None);
+    }
+}
@@ -1,14 +1,67 @@
+mod alert;
+mod bench;
+mod config;
+mod context;
+mod dedup;
+mod encoding;
+mod expr;
+mod filter;
+mod footer;
+mod highlight;
+mod html;
+mod jq;
+mod json;
+mod links;
+mod memory;
+mod output;
+mod pager;
+mod pipeline;
 mod pretty;
 mod protocols;
+mod rate;
+mod report;
+mod size;
+mod sources;
+mod split;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod tail;
+mod template;
+mod theme;
+mod time;
+mod traces;
 
-use crate::pretty::TwoSpacePretty;
-use clap::{ArgAction, Parser, ValueEnum};
-use serde::Serialize;
+use crate::alert::AlertRule;
+use crate::config::{Ansi, ColorConfig, Config};
+use crate::expr::Expr;
+use crate::filter::{Condition, Directive, Filter, Sampler, Selector};
+use crate::jq::Jq;
+use crate::links::Links;
+use crate::output::{Buffered, OutputFormat};
+use crate::pipeline::Pipeline;
+use crate::protocols::{Detection, JsonProtocol, Level};
+use crate::report::agg::{Agg, AggFn};
+use crate::report::{Report, ReportKind};
+use crate::split::Split;
+use crate::stats::Stats;
+use crate::tail::{KeyFilter, KeyOrder, Tail};
+use crate::template::Template;
+use crate::theme::Theme;
+use crate::time::TimeDisplay;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use jiff::Timestamp;
+use regex::Regex;
 use serde_json::Value;
-use std::fs::File;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::IsTerminal;
-use std::io::{self, BufRead, BufReader, LineWriter, Read, Write};
-use std::ops::DerefMut;
+use std::io::{self, BufReader, BufWriter, LineWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
 /// jlo: read NDJSON/JSON Lines, reformat, flush per line, ignore non-JSON.
 #[derive(Parser, Debug)]
@@ -18,16 +71,514 @@ struct Cli {
     #[arg(short, long, action = ArgAction::SetTrue)]
     compact: bool,
 
-    /// Show or hide timestamp (default: true). Example: --timestamp=false
-    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
-    timestamp: bool,
+    /// Show timestamps: `--timestamp` as logged, `--timestamp=relative` as offsets from the
+    /// first line (from startup for live sources)
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "absolute")]
+    timestamp: Option<TimestampMode>,
 
-    /// Color output: auto|always|never (default: auto)
+    /// Append the time since the previous line (from the parsed timestamps), e.g. `Δ=12ms`
+    #[arg(long, action = ArgAction::SetTrue)]
+    delta: bool,
+
+    /// Order of the key=value fields: input (as logged), alpha, or `priority:status,path,…`
+    /// to put these keys first
+    #[arg(long, value_name = "ORDER", value_parser = KeyOrder::parse, default_value = "input")]
+    key_order: KeyOrder,
+
+    /// Print the keys of JSON that no protocol recognizes sorted, also in nested objects, so
+    /// similar records diff cleanly; `--key-order` still applies to the top level
+    #[arg(long, action = ArgAction::SetTrue)]
+    sort_keys: bool,
+
+    /// Leave this field out of the key=value fields and the JSON fallback; repeatable,
+    /// with `*`/`?` wildcards, e.g. `--hide pid --hide 'kubernetes.*'`
+    #[arg(long, value_name = "KEY")]
+    hide: Vec<String>,
+
+    /// Prune keys matching these globs from each JSON line after filtering, before jq and every
+    /// renderer (`--output json` included), e.g. `'kubernetes.*,labels.*,host.*'`
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    drop_keys: Vec<String>,
+
+    /// Write lines that aren't JSON to `stderr` or a file instead of the output, and report
+    /// their count at exit
+    #[arg(long, value_name = "stderr|PATH")]
+    invalid_to: Option<String>,
+
+    /// How to show lines that aren't JSON (panics, stack traces, printf debugging); they are
+    /// always kept. `--keep-raw` alone dims them
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t, require_equals = true, num_args = 0..=1, default_missing_value = "dim")]
+    keep_raw: RawStyle,
+
+    /// Give ERROR and FATAL records a background color across the whole line
+    #[arg(long, action = ArgAction::SetTrue)]
+    highlight_errors: bool,
+
+    /// Shorten source locations (`src=`) to the last two path components
+    #[arg(long, action = ArgAction::SetTrue)]
+    short_src: bool,
+
+    /// Print the extra fields as an aligned `key: value` block under each record (pretty mode)
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "compact")]
+    details: bool,
+
+    /// Flatten nested objects in the key=value extra fields into dotted keys (`a.b.c=1`) down to
+    /// this depth; JSON output keeps its structure
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    flatten_depth: usize,
+
+    /// Keep nested objects as compact JSON instead of flattening them
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "flatten_depth")]
+    no_flatten: bool,
+
+    /// Show durations (`req_time`, `elapsed_ms`, `duration`, …) as `53ms`, `1.2s`, `3m04s`
+    /// and byte counts (`bytes_sent`, `content_length`, …) as `1.4 KiB`
+    #[arg(long, action = ArgAction::SetTrue)]
+    humanize: bool,
+
+    /// Show timestamps in the local time zone
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "tz")]
+    local: bool,
+
+    /// Show timestamps in this time zone, e.g. `UTC` or `America/New_York`
+    #[arg(long, value_name = "ZONE", value_parser = time::parse_time_zone)]
+    tz: Option<jiff::tz::TimeZone>,
+
+    /// Sniff score (0 to 1) a protocol's renderer needs to be picked for a record; below it, the
+    /// record is shown as generic JSON. Lower it for unusual field sets, raise it against false matches
+    #[arg(long, value_name = "SCORE", default_value_t = protocols::DEFAULT_THRESHOLD, value_parser = protocols::parse_threshold)]
+    sniff_threshold: f32,
+
+    /// Also read lines as JSON5: comments, trailing commas, single quotes, unquoted keys, and
+    /// NaN/Infinity (shown as null), as in config dumps and hand-edited files
+    #[arg(long, action = ArgAction::SetTrue)]
+    relaxed: bool,
+
+    /// Render every record with this protocol (nginx, tracing, gcp, azure, generic) instead of
+    /// sniffing, or choose among several, e.g. `--protocol tracing,generic`; generic JSON stays
+    /// the fallback for records a protocol can't show
+    #[arg(long, value_name = "NAME", value_delimiter = ',', value_parser = protocols::parse_protocol)]
+    protocol: Vec<&'static dyn JsonProtocol>,
+
+    /// Decide the protocol of each input from its first N records that sniff as one (20 by
+    /// default) and use it for the rest, instead of sniffing every record
+    #[arg(long, value_name = "N", require_equals = true, num_args = 0..=1, default_missing_value = "20")]
+    sticky: Option<usize>,
+
+    /// Only show records at or above this level (trace, debug, info, warn, error, fatal)
+    #[arg(short, long, value_name = "LEVEL", value_parser = filter::parse_level)]
+    level: Option<Level>,
+
+    /// Only show records whose message or raw line matches this regex; repeatable (any match)
+    #[arg(short, long, value_name = "REGEX")]
+    grep: Vec<Regex>,
+
+    /// Invert `--grep`: show the records that don't match
+    #[arg(short = 'v', long, action = ArgAction::SetTrue, requires = "grep")]
+    invert_match: bool,
+
+    /// Hide records whose message or raw line matches this regex, e.g. health checks; repeatable
+    #[arg(long, value_name = "REGEX")]
+    exclude: Vec<Regex>,
+
+    /// Only show records at or after this time: RFC 3339, a local `2024-05-03 10:00`, a time
+    /// of day today (`10:30`), or a duration ago (`15m`, `2h`)
+    #[arg(long, value_name = "TIME", value_parser = time::parse_time_bound)]
+    since: Option<Timestamp>,
+
+    /// Only show records at or before this time; same forms as `--since`
+    #[arg(long, value_name = "TIME", value_parser = time::parse_time_bound)]
+    until: Option<Timestamp>,
+
+    /// Per-target levels for tracing records in `RUST_LOG` syntax, matching `target` prefixes and
+    /// span names, e.g. `'hyper=off,my_app::db=debug,info'` or `'my_app[request]=trace'`
+    #[arg(long, value_name = "DIRECTIVES", value_delimiter = ',', value_parser = Directive::parse)]
+    target: Vec<Directive>,
+
+    /// Color matches of a regex in the output without filtering; repeatable, each pattern
+    /// gets its own color (colored output only)
+    #[arg(long, value_name = "REGEX")]
+    highlight: Vec<regex::bytes::Regex>,
+
+    /// Only show records matching a boolean expression of field comparisons, e.g.
+    /// `'(level>=WARN and target~"db") or status>=500'`; see `--where` for the operators,
+    /// plus `~`/`!~` for regex matches, `and`, `or`, `not`, and parentheses
+    #[arg(long = "filter", value_name = "EXPR", value_parser = Expr::parse)]
+    filter_expr: Option<Expr>,
+
+    /// Only show records for this trace or request ID, matched against whichever correlation
+    /// field the record has (`traceparent`, `req_id`, `trace.id`, `correlation_id`, `x_request_id`, …)
+    #[arg(long, value_name = "ID")]
+    trace: Option<String>,
+
+    /// Only show request logs with these HTTP status codes, e.g. `500-599,429` or `4xx`
+    #[arg(long, value_name = "CODES", value_delimiter = ',', value_parser = filter::parse_status_range)]
+    status: Vec<RangeInclusive<u64>>,
+
+    /// Only show access-log requests whose path matches this glob, e.g. `'/api/*'`; a leading
+    /// `!` hides matches instead, e.g. `'!/healthz'` (repeatable)
+    #[arg(long = "path", value_name = "GLOB")]
+    paths: Vec<String>,
+
+    /// Only show requests with these HTTP methods, e.g. `POST,PUT,DELETE`; a leading `!` hides
+    /// a method instead, e.g. `'!OPTIONS'`
+    #[arg(long = "method", value_name = "METHODS", value_delimiter = ',')]
+    methods: Vec<String>,
+
+    /// Only show records with a duration field (`req_time`, `duration`, `elapsed_ms`,
+    /// `latencies.request`, …) of at least this long, e.g. `500ms`
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration)]
+    min_duration: Option<Duration>,
+
+    /// Hide access-log requests whose user agent matches this regex; repeatable
+    #[arg(long, value_name = "REGEX")]
+    ignore_ua: Vec<Regex>,
+
+    /// Hide requests from health checkers, uptime monitors, and crawlers (kube-probe,
+    /// ELB-HealthChecker, Googlebot, UptimeRobot, …)
+    #[arg(long)]
+    ignore_health_checks: bool,
+
+    /// Also show N lines after each line that passes the filters, dimmed
+    #[arg(short = 'A', long, value_name = "N", default_value_t = 0)]
+    after_context: usize,
+
+    /// Also show N lines before each line that passes the filters, dimmed
+    #[arg(short = 'B', long, value_name = "N", default_value_t = 0)]
+    before_context: usize,
+
+    /// Also show N lines before and after each line that passes the filters; see `-A` and `-B`
+    #[arg(short = 'C', long, value_name = "N")]
+    context: Option<usize>,
+
+    /// Only show records that have this field, e.g. `error` or `http.status`; repeatable
+    #[arg(long, value_name = "KEY")]
+    has: Vec<String>,
+
+    /// Only show records that lack this field, e.g. `traceparent`; repeatable
+    #[arg(long, value_name = "KEY")]
+    missing: Vec<String>,
+
+    /// Only show records where a field compares true, e.g. `status>=500` or `host=api.example.com`;
+    /// repeatable (all must hold). Operators: `=`, `!=`, and numeric `>=`, `<=`, `>`, `<`
+    #[arg(long = "where", value_name = "COND", value_parser = Condition::parse)]
+    conditions: Vec<Condition>,
+
+    /// Only show JSON lines where a JSONPath query matches, and add the match to the key=value
+    /// tail, e.g. `'$.fields.user_id'` or `'user=$.fields.user_id'`; repeatable (all must match)
+    #[arg(long, value_name = "JSONPATH", value_parser = Selector::parse)]
+    select: Vec<Selector>,
+
+    /// Keep a random fraction of the lines that pass the other filters, e.g. `0.01` or `1/100`
+    #[arg(long, value_name = "RATE", value_parser = filter::parse_sample_rate)]
+    sample: Option<f64>,
+
+    /// Seed for `--sample`, to pick the same lines on every run
+    #[arg(long, value_name = "N", requires = "sample")]
+    sample_seed: Option<u64>,
+
+    /// Print repeated messages once, followed by a dimmed `(repeated 847×, 00:02:13)` when the run
+    /// ends; `--dedup=N` also collapses repeats among the last N distinct messages
+    #[arg(long, value_name = "WINDOW", require_equals = true, num_args = 0..=1, default_missing_value = "1")]
+    dedup: Option<usize>,
+
+    /// Insert a highlighted `―― gap: 2m14s ――` line where consecutive records' timestamps are
+    /// further apart than this, e.g. `30s`, to make outages and restarts stand out
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration)]
+    gap_warn: Option<Duration>,
+
+    /// Hold back records with a trace or request ID and write each trace as an indented block
+    /// under its record count, wall time, and per-service breakdown once it has been quiet for a
+    /// while (`--group-by-trace=30s`; 5s by the clock or the logs' timestamps by default)
+    #[arg(long, value_name = "IDLE", require_equals = true, num_args = 0..=1, default_missing_value = "5s", value_parser = time::parse_duration)]
+    group_by_trace: Option<Duration>,
+
+    /// Only read the first N lines of each input file (or stdin)
+    #[arg(long, value_name = "N", conflicts_with = "tail")]
+    head: Option<usize>,
+
+    /// Only read the last N lines of each input file (or stdin); regular files are read
+    /// backwards from the end
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,
+
+    /// Parse, filter, and render the lines of input files on N threads, keeping their order, for
+    /// batch formatting of large files; stdin, FIFOs, `--tail`, and options that relate lines to
+    /// each other (`--dedup`, `-C`, reports, `--stats`, …) keep to one
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+
+    /// Render at most N lines per second, e.g. while following a log storm; the excess is
+    /// dropped and reported as `… suppressed 1234 lines`
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_rate: Option<u32>,
+
+    /// Instead of printing the records that pass the filters, aggregate them into a report shown
+    /// at the end; repeatable
+    #[arg(long, value_name = "REPORT", value_enum)]
+    report: Vec<ReportKind>,
+
+    /// Leave statements faster than this out of `--report slow-queries`
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration, default_value = "100ms", requires = "report")]
+    min: Duration,
+
+    /// Bound the state kept by --dedup, --group-by-trace, and the reports to about this much, e.g.
+    /// `256M`: past it, the oldest traces are written early, the dedup window narrows, and
+    /// reports drop their rarest groups or coarsen their percentiles
+    #[arg(long, value_name = "SIZE", value_parser = size::parse_size)]
+    max_memory: Option<usize>,
+
+    /// Also write the reports (`--report`, `--timeline`, `--agg`, …) every so often while records
+    /// arrive, e.g. `10s` when following a log; they keep counting from the start
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration)]
+    report_every: Option<Duration>,
+
+    /// Instead of printing records, list the N most frequent values of the `--by` field with
+    /// their counts and share, in bounded memory
+    #[arg(long, value_name = "N", requires = "by")]
+    top: Option<usize>,
+
+    /// Instead of printing records, draw a sparkline of their volume over time, buckets with
+    /// errors in red
+    #[arg(long)]
+    timeline: bool,
+
+    /// Instead of printing records, print how many pass the filters, like `grep -c`; per value
+    /// with `--by`
+    #[arg(long)]
+    count: bool,
+
+    /// Instead of printing records, draw a histogram of this numeric (dotted) field, e.g.
+    /// `req_time`; log-scale 1-2-5 buckets unless `--buckets` are given
+    #[arg(long, value_name = "FIELD")]
+    hist: Option<String>,
+
+    /// Upper bounds of the `--hist` buckets, e.g. `0.01,0.05,0.1,0.5,1,5`
+    #[arg(long, value_name = "BOUNDS", value_delimiter = ',', requires = "hist")]
+    buckets: Vec<f64>,
+
+    /// Instead of printing records, list the distinct values of a (dotted) field with their
+    /// counts and when each was first and last seen; exact for up to 10,000 values, estimated
+    /// beyond
+    #[arg(long, value_name = "FIELD")]
+    uniq: Option<String>,
+
+    /// Instead of printing records, tabulate these aggregates per `--every` bucket and `--by`
+    /// value, e.g. `count,avg(req_time),max(req_time)` (also `sum` and `min`); a CSV/TSV table
+    /// with `--output csv`/`tsv`
+    #[arg(long, value_name = "FNS", value_delimiter = ',', value_parser = AggFn::parse)]
+    agg: Vec<AggFn>,
+
+    /// Time bucket for `--agg`, e.g. `1m`; without it, the whole run is one row per `--by` value
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration, requires = "agg")]
+    every: Option<Duration>,
+
+    /// Field for `--top`, `--count`, and `--agg`: `path`, `status`, `client`, `ua`, `target`, or
+    /// any (dotted) field
+    #[arg(long, value_name = "FIELD")]
+    by: Option<String>,
+
+    /// Ring the bell and print a banner when a condition on the recent records starts to hold:
+    /// `error_rate`, `5xx_rate` (percentages), `errors`, or `lines` over a window, e.g.
+    /// `'error_rate>5%/1m'` or `'errors>=100/5m'`; repeatable
+    #[arg(long, value_name = "CONDITION", value_parser = AlertRule::parse)]
+    alert: Vec<AlertRule>,
+
+    /// End the run with exit status 3 when an `--alert` trips
+    #[arg(long, requires = "alert")]
+    fail_on_alert: bool,
+
+    /// Exit with status 4 at the end if any record that passed the filters was at or above this
+    /// level, e.g. `--fail-on error` to fail a CI step when the service logged errors
+    #[arg(long, value_name = "LEVEL", value_parser = filter::parse_level)]
+    fail_on: Option<Level>,
+
+    /// On a terminal, keep a status line with lines/s, errors/s, and the p95 latency over the
+    /// last 10 seconds under the streaming records (turns off the pager)
+    #[arg(long)]
+    rate: bool,
+
+    /// Print a summary to stderr at the end (or on Ctrl-C): lines read, skipped, and matched,
+    /// counts per protocol and level, the time span covered, and the throughput. Without it, a
+    /// short note tells when lines weren't JSON and were skipped; --stats=false turns that off too
+    #[arg(long, action = ArgAction::Set, require_equals = true, num_args = 0..=1, default_missing_value = "true")]
+    stats: Option<bool>,
+
+    /// Transform or filter each JSON line with a jq expression before rendering,
+    /// e.g. `'select(.status >= 500) | del(.headers)'`; lines without output are dropped
+    #[arg(long, value_name = "EXPR", value_parser = Jq::new)]
+    jq: Option<Jq>,
+
+    /// Color output: auto|always|never (default: auto).
+    /// `auto` honors NO_COLOR, CLICOLOR, CLICOLOR_FORCE, and FORCE_COLOR.
     #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
     color: ColorChoice,
 
+    /// Color theme: ansi (terminal's own 8 colors), dark, light (256 colors), or solarized (truecolor)
+    #[arg(long, value_enum, default_value_t = Theme::Ansi)]
+    theme: Theme,
+
+    /// Level glyphs (✖ ▲ ● ·): `prefix` puts them before the level, `only` replaces it
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "prefix")]
+    icons: Option<Icons>,
+
+    /// Minimum width of the level column
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    level_width: usize,
+
+    /// Level spelling: upper (`ERROR`), lower (`error`), or short (`ERR`)
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = LevelStyle::Upper)]
+    level_style: LevelStyle,
+
+    /// Configuration file (default: `$XDG_CONFIG_HOME/jlo/config.toml`)
+    #[arg(long, value_name = "PATH", env = "JLO_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Output format: pretty for humans; logfmt, json, csv, or tsv for downstream tools;
+    /// html or html-fragment for sharing; sqlite with `--db`
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+    output: OutputFormat,
+
+    /// Write to this file instead of stdout (buffered like stdout, see `--buffered`; colors only
+    /// with `--color always`)
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// Append to `--output-file` instead of truncating it
+    #[arg(long, action = ArgAction::SetTrue, requires = "output_file")]
+    append: bool,
+
+    /// Buffer the output in large blocks instead of writing every line as it's done, for
+    /// formatting large files into a file or pipe; on by default when stdout isn't a terminal and
+    /// every input is a regular file. Example: --buffered=false
+    #[arg(long, action = ArgAction::Set, require_equals = true, num_args = 0..=1, default_missing_value = "true")]
+    buffered: Option<bool>,
+
+    /// Don't page long output through `$PAGER` (default: page file input on a terminal)
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_pager: bool,
+
+    /// Also write every input line, unmodified, to this file
+    #[arg(long, value_name = "PATH")]
+    tee: Option<PathBuf>,
+
+    /// Additionally write lines into one file per value of this field (e.g. `level` or `host`)
+    #[arg(long, value_name = "FIELD")]
+    split_by: Option<String>,
+
+    /// Directory for the `--split-by` files, named `<value>.log` (`.ndjson`, `.csv`, … by format)
+    #[arg(long, value_name = "DIR", default_value = ".", requires = "split_by")]
+    split_dir: PathBuf,
+
+    /// Write the original input lines to the `--split-by` files instead of rendered ones
+    #[arg(long, action = ArgAction::SetTrue, requires = "split_by")]
+    split_raw: bool,
+
+    /// SQLite database for `--output sqlite`; records are appended to its `logs` table
+    #[arg(long, value_name = "PATH", required_if_eq("output", "sqlite"))]
+    db: Option<PathBuf>,
+
+    /// Comma-separated fields to show, e.g. `ts,level,msg,status,path`: the columns of csv/tsv
+    /// output (default: `ts,level,logger,message`), a projection of every record otherwise
+    #[arg(long, value_delimiter = ',')]
+    fields: Option<Vec<String>>,
+
+    /// Custom line layout instead of the pretty renderers, e.g. `'{ts} {level:>5} {status|-} {path} rt={req_time}'`.
+    /// Placeholders: `{field}`, `{field|default}`, `{field:>5}`/`:<5`/`:^5`, color spans `{#red}...{#}`, `{#level}`.
+    #[arg(long, value_name = "TEMPLATE", value_parser = Template::parse, conflicts_with = "output")]
+    format: Option<Template>,
+
+    /// Truncate field values longer than this many characters in the key=value fields of the
+    /// pretty output, e.g. `--max-field-len 200`; JSON output is never cut
+    #[arg(long, value_name = "N")]
+    max_field_len: Option<usize>,
+
+    /// Never truncate field values, overriding an earlier `--max-field-len` (e.g. in an alias)
+    #[arg(long, action = ArgAction::SetTrue, overrides_with = "max_field_len")]
+    no_truncate: bool,
+
+    /// Make URLs, trace IDs, and request IDs clickable (OSC 8 terminal hyperlinks, colored output only)
+    #[arg(long, action = ArgAction::SetTrue)]
+    hyperlinks: bool,
+
+    /// Link target for trace IDs with `--hyperlinks`, e.g. `https://jaeger.example.com/trace/{id}`
+    #[arg(long, value_name = "URL", env = "JLO_TRACE_URL")]
+    trace_url_template: Option<String>,
+
+    /// Link target for request IDs with `--hyperlinks`, e.g. `https://kibana.example.com/app/discover#/?_a=(query:(query:'{id}'))`
+    #[arg(long, value_name = "URL", env = "JLO_REQUEST_URL")]
+    request_url_template: Option<String>,
+
+    /// Consume a Server-Sent Events endpoint, treating each `data:` payload as a log line
+    #[arg(long, value_name = "URL", conflicts_with = "files")]
+    sse_url: Option<String>,
+
+    /// Stop at the first input file that can't be opened, instead of warning about it and going
+    /// on with the others (the run then ends with status 1 either way)
+    #[arg(long)]
+    strict: bool,
+
+    /// Reopen named pipes (FIFOs) after their writer closes instead of exiting. Example: --reopen=false
+    #[arg(long, action = ArgAction::Set, require_equals = true, num_args = 0..=1, default_value_t = true, default_missing_value = "true")]
+    reopen: bool,
+
     /// Input files (read stdin if none). Each file is treated as JSON Lines.
+    /// With the `s3` feature, `s3://bucket/key` URLs and key prefixes are accepted as well.
     files: Vec<String>,
+
+    #[command(subcommand)]
+    source: Option<Source>,
+}
+
+/// Alternative log sources; without one, files or stdin are read.
+#[derive(Subcommand, Debug)]
+enum Source {
+    /// Query Elasticsearch and render the `_source` of each hit, oldest first
+    Es(sources::es::EsArgs),
+    /// Run a LogQL query against Loki (optionally tailing) and render each line
+    Loki(sources::loki::LokiArgs),
+    /// Read Google Cloud Logging entries (optionally polling for new ones)
+    Gcp(sources::gcp::GcpArgs),
+    /// Read or live-tail CloudWatch Logs (requires the `aws` feature)
+    #[cfg(feature = "aws")]
+    Cw(sources::cloudwatch::CloudWatchArgs),
+    /// Consume a Kafka topic, one log record per message (requires the `kafka` feature)
+    #[cfg(feature = "kafka")]
+    Kafka(sources::kafka::KafkaArgs),
+    /// Subscribe to a NATS subject (requires the `nats` feature)
+    #[cfg(feature = "nats")]
+    Nats(sources::nats::NatsArgs),
+    /// Follow a Redis Stream via XREAD (requires the `redis` feature)
+    #[cfg(feature = "redis")]
+    Redis(sources::redis_streams::RedisArgs),
+    /// Query an Azure Log Analytics workspace with Kusto (requires the `azure` feature)
+    #[cfg(feature = "azure")]
+    Azure(sources::azure::AzureArgs),
+    /// Measure the throughput and allocations of parsing and rendering synthesized records of
+    /// each protocol, with the other options as given
+    Bench(bench::BenchArgs),
+}
+
+impl Source {
+    /// Whether the source keeps delivering new lines as they are written.
+    fn is_live(&self) -> bool {
+        match self {
+            Source::Es(_) => false,
+            Source::Loki(args) => args.tail,
+            Source::Gcp(args) => args.tail,
+            #[cfg(feature = "aws")]
+            Source::Cw(args) => args.follow,
+            #[cfg(feature = "kafka")]
+            Source::Kafka(_) => true,
+            #[cfg(feature = "nats")]
+            Source::Nats(_) => true,
+            #[cfg(feature = "redis")]
+            Source::Redis(_) => true,
+            #[cfg(feature = "azure")]
+            Source::Azure(_) => false,
+            Source::Bench(_) => false,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
@@ -37,27 +588,74 @@ enum ColorChoice {
     Never,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum TimestampMode {
+    Absolute,
+    Relative,
+}
+
+/// How the level column spells the level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LevelStyle {
+    Upper,
+    Lower,
+    /// Three letters: `ERR`, `WRN`, `INF`, `DBG`, `TRC`.
+    Short,
+}
+
+/// How lines that aren't JSON are shown (`--keep-raw`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum RawStyle {
+    /// Verbatim.
+    #[default]
+    Plain,
+    /// Verbatim, dimmed.
+    Dim,
+    /// Verbatim after a dimmed `·`.
+    Prefix,
+}
+
+/// How `--icons` shows the level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Icons {
+    /// Glyph followed by the level name.
+    Prefix,
+    /// Glyph instead of the level name.
+    Only,
+}
+
 #[derive(Copy, Clone)]
 pub(crate) struct Palette {
-    #[allow(dead_code)]
     pub(crate) enabled: bool,
     pub(crate) info: &'static str,
     pub(crate) warn: &'static str,
     pub(crate) error: &'static str,
     pub(crate) status3xx: &'static str,
     pub(crate) faint: &'static str,
+    pub(crate) error_bg: &'static str,
+    /// Optional slots, unstyled unless configured; write them through [`Palette::paint`].
+    pub(crate) timestamp: &'static str,
+    pub(crate) message: &'static str,
+    pub(crate) key: &'static str,
+    pub(crate) value: &'static str,
     pub(crate) reset: &'static str,
 }
 impl Palette {
-    fn new(enabled: bool) -> Self {
+    fn new(enabled: bool, theme: Theme) -> Self {
         if enabled {
+            let colors = theme.colors();
             Self {
                 enabled,
-                info: "\x1b[32m",      // green
-                warn: "\x1b[33m",      // yellow
-                error: "\x1b[31m",     // red
-                status3xx: "\x1b[36m", // cyan
-                faint: "\x1b[2m",
+                info: colors.info,
+                warn: colors.warn,
+                error: colors.error,
+                status3xx: colors.status3xx,
+                faint: colors.faint,
+                error_bg: colors.error_bg,
+                timestamp: "",
+                message: "",
+                key: "",
+                value: "",
                 reset: "\x1b[0m",
             }
         } else {
@@ -68,147 +666,590 @@ impl Palette {
                 error: "",
                 status3xx: "",
                 faint: "",
+                error_bg: "",
+                timestamp: "",
+                message: "",
+                key: "",
+                value: "",
                 reset: "",
             }
         }
     }
+
+    /// Apply configured colors on top; a disabled palette stays colorless.
+    fn with_overrides(self, o: &ColorConfig) -> Self {
+        if !self.enabled {
+            return self;
+        }
+        let pick = |o: Option<Ansi>, current: &'static str| o.map_or(current, |a| a.0);
+        Self {
+            info: pick(o.info, self.info),
+            warn: pick(o.warn, self.warn),
+            error: pick(o.error, self.error),
+            status3xx: pick(o.status3xx, self.status3xx),
+            faint: pick(o.faint, self.faint),
+            error_bg: pick(o.error_bg, self.error_bg),
+            timestamp: pick(o.timestamp, self.timestamp),
+            message: pick(o.message, self.message),
+            key: pick(o.key, self.key),
+            value: pick(o.value, self.value),
+            ..self
+        }
+    }
+
+    /// The color for a normalized level; unknown and low levels are dimmed.
+    pub(crate) fn for_level(self, level: Option<Level>) -> &'static str {
+        match level {
+            Some(Level::Error | Level::Fatal) => self.error,
+            Some(Level::Warn) => self.warn,
+            Some(Level::Info) => self.info,
+            _ => self.faint,
+        }
+    }
+
+    /// Display `text` in `color`, skipping the escape codes entirely for unset slots.
+    pub(crate) fn paint<T: fmt::Display>(self, color: &'static str, text: T) -> Painted<T> {
+        Painted {
+            color,
+            reset: if color.is_empty() { "" } else { self.reset },
+            text,
+        }
+    }
 }
 
-#[derive(Copy, Clone)]
+pub(crate) struct Painted<T> {
+    color: &'static str,
+    reset: &'static str,
+    text: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.color, self.text, self.reset)
+    }
+}
+
+#[derive(Clone)]
 pub(crate) struct RenderCtx {
     pub(crate) show_ts: bool,
     pub(crate) pal: Palette,
     pub(crate) compact: bool,
+    pub(crate) output: OutputFormat,
+    /// Cut longer field values in the pretty output (`None` disables truncation).
+    pub(crate) max_field_len: Option<usize>,
+    pub(crate) links: Links,
+    pub(crate) icons: Option<Icons>,
+    pub(crate) level_width: usize,
+    pub(crate) level_style: LevelStyle,
+    pub(crate) key_order: KeyOrder,
+    /// Sort the keys of generic JSON at every level (`--sort-keys`).
+    pub(crate) sort_keys: bool,
+    pub(crate) filter: KeyFilter,
+    pub(crate) short_src: bool,
+    /// Extra fields one `key: value` per line instead of a key=value tail.
+    pub(crate) details: bool,
+    /// Levels of nested objects to turn into dotted keys (0 keeps them as JSON).
+    pub(crate) flatten_depth: usize,
+    pub(crate) time: TimeDisplay,
+    /// Show durations as `53ms`/`1.2s`/`3m04s` and sizes as `1.4 KiB` instead of raw numbers.
+    pub(crate) humanize: bool,
+}
+
+impl RenderCtx {
+    /// The render settings given on the command line, in `pal`'s colors.
+    fn new(cli: &Cli, pal: Palette) -> Self {
+        Self {
+            show_ts: cli.timestamp.is_some(),
+            pal,
+            compact: cli.compact,
+            output: cli.output,
+            max_field_len: cli.max_field_len.filter(|_| !cli.no_truncate),
+            icons: cli.icons,
+            level_width: cli.level_width,
+            level_style: cli.level_style,
+            key_order: cli.key_order.clone(),
+            sort_keys: cli.sort_keys,
+            details: cli.details,
+            short_src: cli.short_src,
+            flatten_depth: if cli.no_flatten { 0 } else { cli.flatten_depth },
+            filter: KeyFilter {
+                hide: cli.hide.clone().into(),
+            },
+            humanize: cli.humanize,
+            time: TimeDisplay {
+                zone: cli
+                    .tz
+                    .clone()
+                    .or_else(|| cli.local.then(jiff::tz::TimeZone::system)),
+                relative: (cli.timestamp == Some(TimestampMode::Relative)).then(|| {
+                    let anchor = OnceLock::new();
+                    if cli.sse_url.is_some() || cli.source.as_ref().is_some_and(Source::is_live) {
+                        let _ = anchor.set(jiff::Timestamp::now());
+                    }
+                    Arc::new(anchor)
+                }),
+            },
+            links: Links {
+                enabled: cli.hyperlinks,
+                trace_template: cli.trace_url_template.as_deref().map(Arc::from),
+                request_template: cli.request_url_template.as_deref().map(Arc::from),
+            },
+        }
+    }
 }
 
 fn main() -> io::Result<()> {
+    // Like other Unix tools, end quietly once `jlo … | head` has read enough; on a terminal,
+    // the pager closing early is handled below
+    #[cfg(unix)]
+    if !io::stdout().is_terminal() {
+        restore_sigpipe();
+    }
     let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
 
-    let want_ts = cli.timestamp;
-    let stdout_is_tty = io::stdout().is_terminal();
+    // A file is never a terminal, so `auto` leaves it uncolored
+    let stdout_is_tty = cli.output_file.is_none() && io::stdout().is_terminal();
     let colors_enabled = match cli.color {
-        ColorChoice::Auto => stdout_is_tty,
+        // HTML carries its colors as CSS, independent of the terminal
+        ColorChoice::Auto => cli.output.is_html() || auto_color(stdout_is_tty),
         ColorChoice::Always => true,
         ColorChoice::Never => false,
     };
-    let ctx = RenderCtx {
-        show_ts: want_ts,
-        pal: Palette::new(colors_enabled),
-        compact: cli.compact,
-    };
+    let ctx = RenderCtx::new(
+        &cli,
+        Palette::new(colors_enabled, cli.theme).with_overrides(&config.colors),
+    );
 
-    let stdout = io::stdout();
-    let handle = stdout.lock();
-    let mut out = LineWriter::new(handle);
+    // Per-protocol overrides from the configuration file
+    let mut styles = Vec::new();
+    for (name, style) in &config.protocols {
+        let Some(proto) = protocols::by_name(name) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("config: unknown protocol `{name}` in [protocols.{name}]"),
+            ));
+        };
+        let proto_ctx = RenderCtx {
+            show_ts: style.timestamp.unwrap_or(ctx.show_ts),
+            pal: ctx.pal.with_overrides(&style.colors),
+            compact: style.compact.unwrap_or(ctx.compact),
+            ..ctx.clone()
+        };
+        styles.push((proto.name(), proto_ctx));
+    }
 
-    if cli.files.is_empty() {
-        process_reader(
-            BufReader::new(io::stdin().lock()),
-            cli.compact,
-            ctx,
-            &mut out,
-        )?;
+    // Page finite file input on a terminal, like git
+    let finite_input = cli.source.is_none()
+        && cli.sse_url.is_none()
+        && !cli.files.is_empty()
+        && !cli.files.iter().any(|f| sources::is_fifo(f));
+    let mut pager = if !cli.no_pager && !cli.rate && stdout_is_tty && finite_input {
+        pager::spawn()?
     } else {
-        for path in &cli.files {
-            let file = File::open(path)?;
-            process_reader(BufReader::new(file), cli.compact, ctx, &mut out)?;
+        None
+    };
+
+    // Streams piped through jlo keep their lines coming as they arrive, into a file as well
+    let buffered = cli
+        .buffered
+        .unwrap_or((cli.output_file.is_some() || !stdout_is_tty) && finite_input);
+    let bench = matches!(cli.source, Some(Source::Bench(_)));
+    let out: Box<dyn Write> = match &cli.output_file {
+        // The benchmark measures the rendering, not the terminal
+        _ if bench => Box::new(io::sink()),
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(cli.append)
+                .truncate(!cli.append)
+                .open(path)?;
+            if buffered {
+                Box::new(Buffered::new(file))
+            } else {
+                Box::new(LineWriter::new(file))
+            }
+        }
+        None => match pager.as_mut().and_then(|p| p.stdin.take()) {
+            Some(stdin) => Box::new(BufWriter::new(stdin)),
+            None if buffered => Box::new(Buffered::new(io::stdout())),
+            None => Box::new(LineWriter::new(io::stdout())),
+        },
+    };
+    let tee: Option<Box<dyn Write>> = match &cli.tee {
+        Some(path) => Some(Box::new(LineWriter::new(File::create(path)?))),
+        None => None,
+    };
+    let invalid: Option<Box<dyn Write>> = match cli.invalid_to.as_deref() {
+        Some("stderr") => Some(Box::new(io::stderr())),
+        Some(path) => Some(Box::new(BufWriter::new(File::create(path)?))),
+        None => None,
+    };
+    let mut pipeline = Pipeline::new(ctx, out)
+        .fields(cli.fields.clone().unwrap_or_else(|| {
+            ["ts", "level", "logger", "message"]
+                .map(String::from)
+                .into()
+        }))
+        .project(cli.fields.is_some())
+        .template(cli.format.clone())
+        .delta(cli.delta)
+        .highlight_errors(cli.highlight_errors)
+        .filter(Filter {
+            min_level: cli.level,
+            grep: cli.grep.clone(),
+            invert: cli.invert_match,
+            exclude: cli.exclude.clone(),
+            since: cli.since,
+            until: cli.until,
+            targets: cli.target.clone(),
+            trace: cli.trace.clone(),
+            expr: cli.filter_expr.clone(),
+            status: cli.status.clone(),
+            paths: cli.paths.clone(),
+            methods: cli.methods.clone(),
+            min_duration: cli.min_duration,
+            ignore_ua: cli
+                .ignore_ua
+                .iter()
+                .cloned()
+                .chain(
+                    cli.ignore_health_checks
+                        .then_some(filter::HEALTH_CHECK_AGENTS)
+                        .into_iter()
+                        .flatten()
+                        .map(|ua| Regex::new(&regex::escape(ua)).expect("valid regex")),
+                )
+                .collect(),
+            has: cli.has.clone(),
+            missing: cli.missing.clone(),
+            conditions: cli.conditions.clone(),
+            selectors: cli.select.clone(),
+            sample: cli.sample.map(|rate| Sampler::new(rate, cli.sample_seed)),
+        })
+        .context(
+            cli.context.unwrap_or(0).max(cli.before_context),
+            cli.context.unwrap_or(0).max(cli.after_context),
+        )
+        .drop_keys(cli.drop_keys.clone())
+        .jq(cli.jq.clone())
+        .highlight(cli.highlight.clone())
+        .raw_style(cli.keep_raw)
+        .dedup(cli.dedup)
+        .gap_warn(cli.gap_warn)
+        .group_by_trace(cli.group_by_trace)
+        .max_rate(cli.max_rate)
+        .reports(reports(&cli))
+        .report_every(cli.report_every)
+        .max_memory(cli.max_memory)
+        .rate_footer(cli.rate && stdout_is_tty)
+        .alerts(cli.alert.clone(), cli.fail_on_alert)
+        .fail_on(cli.fail_on)
+        .head(cli.head)
+        .tail(cli.tail)
+        .detection(Detection::new(cli.sniff_threshold, &cli.protocol))
+        .relaxed(cli.relaxed)
+        .sticky(cli.sticky)
+        .jobs(cli.jobs.into())
+        .styles(styles)
+        .tee(tee)
+        .invalid_to(invalid, cli.invalid_to.clone().unwrap_or_default());
+    if cli.output == OutputFormat::Sqlite {
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &cli.db {
+            pipeline = pipeline.sqlite(Some(sqlite::SqliteSink::open(db)?));
         }
+        #[cfg(not(feature = "sqlite"))]
+        return Err(sources::feature_disabled("--output sqlite", "sqlite"));
     }
+    if let Some(key) = &cli.split_by {
+        let split = Split::new(
+            key.clone(),
+            cli.split_dir.clone(),
+            cli.split_raw,
+            pipeline.plain_renderer(),
+            cli.output,
+        )?;
+        pipeline = pipeline.split(Some(split));
+    }
+
+    if cli.stats == Some(true) {
+        let stats = Arc::new(Mutex::new(Stats::new()));
+        let on_interrupt = Arc::clone(&stats);
+        ctrlc::set_handler(move || {
+            let _ = stats::lock(&on_interrupt).write(&mut io::stderr());
+            std::process::exit(130);
+        })
+        .map_err(to_io_err)?;
+        pipeline = pipeline.stats(Some(stats));
+    }
+    pipeline = pipeline.tally(cli.stats.is_none() && !bench);
 
-    out.flush()
+    let mut unreadable = 0;
+    let mut result = run(&cli, &mut pipeline, &mut unreadable);
+    let tripped = result.as_ref().is_err_and(alert::is_tripped);
+    if tripped {
+        result = pipeline.finish();
+    }
+    let failed = pipeline.failed();
+    // Close the pager's input and let the user finish reading
+    drop(pipeline);
+    if let Some(mut pager) = pager {
+        let status = pager.wait()?;
+        // The shell's "command not found"
+        if status.code() == Some(127) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "pager not found; set PAGER or use --no-pager",
+            ));
+        }
+    }
+    // Quitting the pager or `head` early closes the pipe; that's not an error
+    if let Err(e) = &result
+        && e.kind() == io::ErrorKind::BrokenPipe
+    {
+        return Ok(());
+    }
+    result?;
+    // The most severe outcome sets the exit status: a tripped alert, then `--fail-on`, then
+    // skipped files
+    let status = if tripped {
+        3
+    } else if let Some((level, n)) = failed {
+        let records = if n == 1 { "record" } else { "records" };
+        eprintln!("jlo: {n} {records} at {level} or above");
+        4
+    } else if unreadable > 0 {
+        1
+    } else {
+        0
+    };
+    // The skipped files were reported as they came up, but not what the status would have said
+    if unreadable > 0 && status != 1 {
+        let files = if unreadable == 1 { "file" } else { "files" };
+        eprintln!("jlo: {unreadable} input {files} couldn't be read");
+    }
+    if status != 0 {
+        std::process::exit(status);
+    }
+    Ok(())
 }
 
-fn process_reader<R: Read, W: Write>(
-    mut reader: BufReader<R>,
-    compact: bool,
-    ctx: RenderCtx,
-    mut out: &mut W,
-) -> io::Result<()> {
-    let mut buf = Vec::with_capacity(8 * 1024);
+/// Die of SIGPIPE when writing to a closed pipe, which Rust ignores so that writes fail with
+/// `BrokenPipe` instead.
+#[cfg(unix)]
+fn restore_sigpipe() {
+    // SAFETY: called before any other thread is spawned
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
 
-    loop {
-        buf.clear();
-        let n = reader.read_until(b'\n', &mut buf)?;
-        if n == 0 {
-            break;
-        }
-        while matches!(buf.last(), Some(b'\n' | b'\r')) {
-            buf.pop();
-        }
-        if buf.is_empty() {
-            continue;
-        }
+/// The `--report`s, `--timeline`, `--agg` table, `--count`, `--hist`, `--uniq` table, and `--top`
+/// list, in the order they are printed.
+fn reports(cli: &Cli) -> Vec<Box<dyn Report>> {
+    let mut reports: Vec<_> = cli.report.iter().map(|kind| kind.report(cli.min)).collect();
+    if cli.timeline {
+        reports.push(Box::new(report::timeline::Timeline::default()));
+    }
+    if !cli.agg.is_empty() {
+        reports.push(Box::new(Agg::new(
+            cli.agg.clone(),
+            cli.every,
+            cli.by.clone(),
+        )));
+    }
+    if cli.count {
+        reports.push(Box::new(report::count::Count::new(cli.by.clone())));
+    }
+    if let Some(field) = &cli.hist {
+        reports.push(Box::new(report::hist::Hist::new(
+            field.clone(),
+            cli.buckets.clone(),
+        )));
+    }
+    if let Some(field) = &cli.uniq {
+        reports.push(Box::new(report::uniq::Uniq::new(field.clone())));
+    }
+    if let (Some(n), Some(by)) = (cli.top, &cli.by) {
+        reports.push(Box::new(report::top::Top::new(n, by.clone())));
+    }
+    reports
+}
 
-        match serde_json::from_slice::<Value>(&buf) {
-            Ok(v) => {
-                use crate::protocols::{self, JsonProtocol};
-                let protos: [&dyn JsonProtocol; 2] =
-                    [&protocols::nginx::Nginx, &protocols::tracing::Tracing];
-                let mut best: Option<(&dyn JsonProtocol, f32)> = None;
-                for p in protos.iter().copied() {
-                    let s = p.sniff(&v);
-                    if let Some((_, bs)) = best {
-                        if s > bs {
-                            best = Some((p, s));
-                        }
-                    } else {
-                        best = Some((p, s));
+/// Feed the selected input (subcommand source, SSE, stdin, or files) through the pipeline,
+/// counting the input files that couldn't be opened and were skipped in `unreadable`.
+fn run(cli: &Cli, pipeline: &mut Pipeline, unreadable: &mut usize) -> io::Result<()> {
+    if let Some(source) = &cli.source {
+        let mut sink = |line: &[u8], note: Option<&str>| pipeline.process_line(line, note);
+        match source {
+            Source::Es(args) => sources::es::run(args, &mut sink)?,
+            Source::Loki(args) => sources::loki::run(args, &mut sink)?,
+            Source::Gcp(args) => sources::gcp::run(args, &mut sink)?,
+            #[cfg(feature = "aws")]
+            Source::Cw(args) => sources::cloudwatch::run(args, &mut sink)?,
+            #[cfg(feature = "kafka")]
+            Source::Kafka(args) => sources::kafka::run(args, &mut sink)?,
+            #[cfg(feature = "nats")]
+            Source::Nats(args) => sources::nats::run(args, &mut sink)?,
+            #[cfg(feature = "redis")]
+            Source::Redis(args) => sources::redis_streams::run(args, &mut sink)?,
+            #[cfg(feature = "azure")]
+            Source::Azure(args) => sources::azure::run(args, &mut sink)?,
+            Source::Bench(args) => bench::run(args, &mut sink)?,
+        }
+    } else if let Some(url) = &cli.sse_url {
+        sources::sse::run(url, &mut |line, note| pipeline.process_line(line, note))?;
+    } else if cli.files.is_empty() {
+        pipeline.process_reader(BufReader::new(io::stdin().lock()))?;
+    } else {
+        for path in &cli.files {
+            #[cfg(feature = "s3")]
+            if sources::is_s3_url(path) {
+                sources::s3::for_each_object(path, |r| pipeline.process_reader(r))?;
+                continue;
+            }
+            #[cfg(not(feature = "s3"))]
+            if sources::is_s3_url(path) {
+                return Err(sources::feature_disabled(path, "s3"));
+            }
+            // A named pipe sees EOF whenever its last writer exits; park on it for the next one
+            let reopen = cli.reopen && sources::is_fifo(path);
+            loop {
+                let mut file = match File::open(path) {
+                    Ok(file) => file,
+                    Err(e) if cli.strict => {
+                        return Err(io::Error::new(e.kind(), format!("{path}: {e}")));
                     }
-                }
-                let mut rendered = false;
-                if let Some((p, score)) = best {
-                    if score > 0.0 {
-                        rendered = p.render(&v, ctx, out.deref_mut())?;
+                    Err(e) => {
+                        eprintln!("jlo: {path}: {e}");
+                        *unreadable += 1;
+                        break;
                     }
+                };
+                let is_file = file.metadata()?.is_file();
+                if is_file {
+                    let encoding = cli
+                        .tail
+                        .map(|n| sources::seek_to_last_lines(&mut file, n))
+                        .transpose()?;
+                    pipeline.process_file(BufReader::new(file), encoding)?;
+                } else {
+                    pipeline.process_reader(BufReader::new(file))?;
                 }
-                if !rendered {
-                    if compact {
-                        serde_json::to_writer(out.deref_mut(), &v).map_err(to_io_err)?;
-                        out.write_all(b"\n")?;
-                    } else {
-                        let mut ser = serde_json::Serializer::with_formatter(
-                            out.deref_mut(),
-                            TwoSpacePretty::default(),
-                        );
-                        v.serialize(&mut ser).map_err(to_io_err)?;
-                        out.write_all(b"\n")?;
-                    }
+                if !reopen {
+                    break;
                 }
             }
-            Err(_) => {
-                // Not valid JSON: print the original line as-is
-                out.write_all(&buf)?;
-                out.write_all(b"\n")?;
-            }
         }
     }
-    Ok(())
+
+    pipeline.finish()
+}
+
+/// Color decision for `--color auto`, following the conventions of other CLI tools:
+/// `CLICOLOR_FORCE`/`FORCE_COLOR` force colors on, `NO_COLOR` or `CLICOLOR=0` turn
+/// them off, and otherwise colors are used on terminals only.
+fn auto_color(stdout_is_tty: bool) -> bool {
+    let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    let enabled = |name: &str| var(name).is_some_and(|v| v != "0" && v != "false");
+    if enabled("CLICOLOR_FORCE") || enabled("FORCE_COLOR") {
+        return true;
+    }
+    if var("NO_COLOR").is_some() || var("CLICOLOR").is_some_and(|v| v == "0") {
+        return false;
+    }
+    stdout_is_tty
+}
+
+/// Write the colored level column (`--level-width`, `--level-style`, `--icons`) and a space;
+/// returns its width.
+pub(crate) fn write_level<W: Write>(
+    mut out: W,
+    ctx: &RenderCtx,
+    color: &str,
+    level: &str,
+) -> io::Result<usize> {
+    let upper = level.to_ascii_uppercase();
+    let glyph = match upper.as_str() {
+        "ERROR" | "FATAL" | "CRITICAL" | "EMERGENCY" | "ALERT" => '✖',
+        "WARN" | "WARNING" => '▲',
+        "INFO" | "NOTICE" => '●',
+        _ => '·',
+    };
+    let label = match ctx.level_style {
+        LevelStyle::Upper => upper,
+        LevelStyle::Lower => level.to_ascii_lowercase(),
+        LevelStyle::Short => match upper.as_str() {
+            "ERROR" => "ERR".to_string(),
+            "WARN" | "WARNING" => "WRN".to_string(),
+            "INFO" => "INF".to_string(),
+            "DEBUG" => "DBG".to_string(),
+            "TRACE" => "TRC".to_string(),
+            "FATAL" => "FTL".to_string(),
+            other => other.chars().take(3).collect(),
+        },
+    };
+    let width = ctx.level_width;
+    let reset = ctx.pal.reset;
+    match ctx.icons {
+        None => write!(out, "{color}{label:<width$}{reset} ")?,
+        Some(Icons::Prefix) => write!(out, "{color}{glyph} {label:<width$}{reset} ")?,
+        Some(Icons::Only) => write!(out, "{color}{glyph}{reset} ")?,
+    }
+    let cols = match ctx.icons {
+        None => label.chars().count().max(width),
+        Some(Icons::Prefix) => 2 + label.chars().count().max(width),
+        Some(Icons::Only) => 1,
+    };
+    Ok(cols + 1)
 }
 
 /// Helper: write key=value for string-ish fields if present & non-empty.
-pub(crate) fn write_kv_str<W: Write>(mut out: W, key: &str, val: Option<&str>) -> io::Result<()> {
+pub(crate) fn write_kv_str(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    val: Option<&str>,
+) -> io::Result<()> {
     let Some(s) = val else {
         return Ok(());
     };
 
     if !s.is_empty() {
-        write!(out, " {}=", key)?;
-        // bare if safe, else JSON-quoted
-        if s.chars()
-            .all(|c| c.is_ascii_graphic() && c != ' ' && c != '=')
-        {
-            write!(out, "{}", s)?;
-        } else {
-            let mut buf = Vec::new();
-            serde_json::to_writer(&mut buf, &Value::String(s.to_string())).map_err(to_io_err)?;
-            out.write_all(&buf)?;
-        }
+        let out = tail.push(key);
+        write_value(out, ctx, key, s, true)?;
     }
     Ok(())
 }
 
+/// Helper: write a dimmed `src=file:line` source location, cut to its last two path
+/// components with `--short-src`.
+pub(crate) fn write_kv_src(tail: &mut Tail, ctx: &RenderCtx, val: Option<&str>) -> io::Result<()> {
+    let Some(src) = val.filter(|s| !s.is_empty()) else {
+        return Ok(());
+    };
+    let src = if ctx.short_src {
+        let mut cut = src.rmatch_indices(['/', '\\']).map(|(i, _)| i);
+        match (cut.next(), cut.next()) {
+            (Some(_), Some(i)) => &src[i + 1..],
+            _ => src,
+        }
+    } else {
+        src
+    };
+    let out = tail.push_dimmed("src");
+    write!(out, "{}", ctx.pal.paint(ctx.pal.faint, src))
+}
+
 /// Helper: write key=value for numeric (f64) with trimmed trailing zeros.
-pub(crate) fn write_kv_num<W: Write>(mut out: W, key: &str, val: Option<f64>) -> io::Result<()> {
+pub(crate) fn write_kv_num(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    val: Option<f64>,
+) -> io::Result<()> {
     let Some(mut f) = val else {
         return Ok(());
     };
@@ -216,49 +1257,233 @@ pub(crate) fn write_kv_num<W: Write>(mut out: W, key: &str, val: Option<f64>) ->
     if f == -0.0 {
         f = 0.0;
     }
-    write!(out, " {}=", key)?;
+    let out = tail.push(key);
     // Trim trailing zeros
     let s = format!("{:.6}", f);
     let s = s.trim_end_matches('0').trim_end_matches('.');
-    write!(out, "{}", s)?;
+    write!(out, "{}", ctx.pal.paint(ctx.pal.value, s))?;
     Ok(())
 }
 
-/// Write a compact single-atom JSON value for key=value lists.
+/// Helper: write key=value for a byte count, humanized (`1.4 KiB`) with `--humanize`.
+pub(crate) fn write_kv_bytes(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    val: Option<u64>,
+) -> io::Result<()> {
+    let Some(n) = val else {
+        return Ok(());
+    };
+    let text = if ctx.humanize {
+        size::humanize_bytes(n)
+    } else {
+        n.to_string()
+    };
+    let out = tail.push(key);
+    write_value(out, ctx, key, &text, false)
+}
+
+/// Helper: write key=value for a duration in seconds, humanized (`53ms`) with `--humanize`.
+pub(crate) fn write_kv_secs(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    val: Option<f64>,
+) -> io::Result<()> {
+    match val.and_then(|secs| Duration::try_from_secs_f64(secs).ok()) {
+        Some(d) if ctx.humanize => {
+            let out = tail.push(key);
+            write!(
+                out,
+                "{}",
+                ctx.pal.paint(ctx.pal.value, time::humanize_duration(d))
+            )
+        }
+        _ => write_kv_num(tail, ctx, key, val),
+    }
+}
+
+/// Write key=value with a compact single-atom JSON value, for key=value lists.
 ///
 /// Strings are printed without quotes when safe (no spaces or `=`),
 /// everything else is serialized as compact JSON.
-pub(crate) fn write_json_atom<W: Write>(mut out: W, v: &Value) -> io::Result<()> {
-    match v {
-        Value::String(s) => {
-            if s.chars()
-                .all(|c| c.is_ascii_graphic() && c != ' ' && c != '=')
-            {
-                // Safe to print bare
-                write!(out, "{}", s)?;
-            } else {
-                // Fallback to proper JSON string escaping
-                let mut buf = Vec::new();
-                serde_json::to_writer(&mut buf, v).map_err(to_io_err)?;
-                out.write_all(&buf)?;
-            }
+pub(crate) fn write_kv_json(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    v: &Value,
+) -> io::Result<()> {
+    write_kv_nested(tail, ctx, key, v, ctx.flatten_depth)
+}
+
+fn write_kv_nested(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    key: &str,
+    v: &Value,
+    depth: usize,
+) -> io::Result<()> {
+    if let Value::Object(obj) = v
+        && depth > 0
+        && !obj.is_empty()
+    {
+        for (k, v) in obj {
+            write_kv_nested(tail, ctx, &format!("{key}.{k}"), v, depth - 1)?;
         }
-        _ => {
-            // Non-string → write as compact JSON
-            let mut buf = Vec::new();
-            serde_json::to_writer(&mut buf, v).map_err(to_io_err)?;
-            out.write_all(&buf)?;
+        return Ok(());
+    }
+    let out = tail.push(key);
+    match v {
+        Value::String(s) => write_value(out, ctx, key, s, true),
+        Value::Number(n) if ctx.humanize => {
+            let text = time::duration_unit(key)
+                .and_then(|unit| Duration::try_from_secs_f64(n.as_f64()? * unit).ok())
+                .map(time::humanize_duration)
+                .or_else(|| {
+                    n.as_u64()
+                        .filter(|_| size::is_size_field(key))
+                        .map(size::humanize_bytes)
+                })
+                .unwrap_or_else(|| n.to_string());
+            write_value(out, ctx, key, &text, false)
         }
+        // Non-string → compact JSON
+        _ => write_value(
+            out,
+            ctx,
+            key,
+            &serde_json::to_string(v).map_err(to_io_err)?,
+            false,
+        ),
+    }
+}
+
+/// The value half of key=value: cut to `--max-field-len`, bare if safe (or not a string),
+/// else JSON-quoted, and wrapped in a hyperlink with `--hyperlinks`.
+fn write_value<W: Write>(
+    mut out: W,
+    ctx: &RenderCtx,
+    key: &str,
+    text: &str,
+    is_string: bool,
+) -> io::Result<()> {
+    let (s, dropped) = truncate(text, ctx.max_field_len);
+    let link = ctx.links.url_for(key, text).filter(|_| ctx.pal.enabled);
+    if let Some(url) = &link {
+        write!(out, "\x1b]8;;{url}\x1b\\")?;
+    }
+    if !is_string
+        || s.chars()
+            .all(|c| c.is_ascii_graphic() && c != ' ' && c != '=')
+    {
+        write!(out, "{}", ctx.pal.paint(ctx.pal.value, s))?;
+    } else {
+        let quoted = serde_json::to_string(&*s).map_err(to_io_err)?;
+        write!(out, "{}", ctx.pal.paint(ctx.pal.value, quoted))?;
+    }
+    if link.is_some() {
+        out.write_all(b"\x1b]8;;\x1b\\")?;
+    }
+    write_truncation_marker(&mut out, ctx, dropped)
+}
+
+/// Cut `s` to at most `max` characters, ending in `…`.
+///
+/// Returns the (possibly shortened) text and the number of characters dropped.
+pub(crate) fn truncate(s: &str, max: Option<usize>) -> (Cow<'_, str>, usize) {
+    let Some(max) = max else {
+        return (Cow::Borrowed(s), 0);
+    };
+    if s.char_indices().nth(max).is_none() {
+        return (Cow::Borrowed(s), 0);
+    }
+    // Keep one character less to make room for the ellipsis
+    let keep = s
+        .char_indices()
+        .nth(max.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let dropped = s[keep..].chars().count();
+    (Cow::Owned(format!("{}…", &s[..keep])), dropped)
+}
+
+/// Dimmed ` (+N chars)` after a truncated value.
+fn write_truncation_marker<W: Write>(
+    mut out: W,
+    ctx: &RenderCtx,
+    dropped: usize,
+) -> io::Result<()> {
+    if dropped > 0 {
+        write!(
+            out,
+            " {}(+{} chars){}",
+            ctx.pal.faint, dropped, ctx.pal.reset
+        )?;
     }
     Ok(())
 }
 
 /// Map arbitrary errors into `io::Error` so callers can stay on `io::Result`.
 pub(crate) fn to_io_err<E: std::error::Error + Send + Sync + 'static>(e: E) -> std::io::Error {
-    io::Error::new(io::ErrorKind::Other, e)
+    io::Error::other(e)
 }
 
 /// Some fields come as strings like `"0.053"`. Parse leniently into f64.
 pub(crate) fn as_f64_lossy(v: &Value) -> Option<f64> {
     v.as_f64().or_else(|| v.as_str()?.parse::<f64>().ok())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The render settings of `jlo <args>`, without colors.
+    pub(crate) fn ctx(args: &[&str]) -> RenderCtx {
+        let cli = Cli::parse_from(["jlo"].iter().chain(args));
+        RenderCtx::new(&cli, Palette::new(false, cli.theme))
+    }
+
+    fn value(args: &[&str], key: &str, text: &str) -> String {
+        let mut out = Vec::new();
+        write_value(&mut out, &ctx(args), key, text, true).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn values_are_quoted_when_needed() {
+        assert_eq!(value(&[], "k", "plain"), "plain");
+        assert_eq!(value(&[], "k", "a b"), "\"a b\"");
+        assert_eq!(value(&[], "k", "a=b"), "\"a=b\"");
+        assert_eq!(value(&[], "k", "\u{1b}[2J"), "\"\\u001b[2J\"");
+    }
+
+    #[test]
+    fn long_values_are_cut() {
+        assert_eq!(
+            value(&["--max-field-len", "5"], "k", "abcdefgh"),
+            "\"abcd…\" (+4 chars)"
+        );
+        assert_eq!(
+            value(&["--max-field-len", "5", "--no-truncate"], "k", "abcdefgh"),
+            "abcdefgh"
+        );
+    }
+
+    #[test]
+    fn hyperlinks_need_colors() {
+        let url = "https://example.com/a";
+        assert_eq!(value(&["--hyperlinks"], "url", url), url);
+        let mut out = Vec::new();
+        let ctx = RenderCtx {
+            pal: Palette::new(true, Theme::Ansi),
+            ..ctx(&["--hyperlinks"])
+        };
+        write_value(&mut out, &ctx, "url", url, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with(&format!("\u{1b}]8;;{url}\u{1b}\\")),
+            "{out:?}"
+        );
+        assert!(out.ends_with("\u{1b}]8;;\u{1b}\\"), "{out:?}");
+    }
+}
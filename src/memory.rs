@@ -0,0 +1,38 @@
+//! `--max-memory`: a budget for the state that `--dedup`, `--group-by-trace`, and the reports
+//! keep, so that following a stream for days doesn't grow without bound.
+
+/// Records between checks of the usage; adding it up walks the state.
+const CHECK_EVERY: u64 = 4096;
+
+/// Rough cost of a map or list entry besides its contents: the hash, pointers, and padding.
+pub const ENTRY: usize = 48;
+
+pub struct Budget {
+    limit: usize,
+    records: u64,
+    /// Whether the limit was reached before, to say so only once.
+    reached: bool,
+}
+
+impl Budget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            records: 0,
+            reached: false,
+        }
+    }
+
+    /// Count a record; the limit when it's time to check the usage against it.
+    pub fn due(&mut self) -> Option<usize> {
+        self.records += 1;
+        self.records
+            .is_multiple_of(CHECK_EVERY)
+            .then_some(self.limit)
+    }
+
+    /// Note that state has to be given up; `true` the first time.
+    pub fn reach(&mut self) -> bool {
+        !std::mem::replace(&mut self.reached, true)
+    }
+}
@@ -0,0 +1,421 @@
+//! Machine-readable output formats, built on the normalized [`Record`] view of each line.
+
+use clap::ValueEnum;
+use serde_json::Value;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crate::html;
+use crate::protocols::Record;
+
+/// How records are written to the output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-oriented, colorized rendering (default)
+    #[default]
+    Pretty,
+    /// Canonical logfmt: `ts=... level=... msg=... key=value...`
+    Logfmt,
+    /// One normalized JSON object per line: `ts`, `level`, `message`, `logger`, then the fields
+    Json,
+    /// RFC 4180 CSV with a header row, one column per `--fields` entry
+    Csv,
+    /// Tab-separated values with a header row, one column per `--fields` entry
+    Tsv,
+    /// Standalone HTML page with the pretty rendering, colors as CSS
+    Html,
+    /// Just the `<pre>` block of the HTML output, for embedding
+    HtmlFragment,
+    /// Insert normalized records into the SQLite database given by `--db` (requires the `sqlite` feature)
+    Sqlite,
+}
+
+impl OutputFormat {
+    /// Machine-readable formats get every line in their own format, including non-JSON input.
+    pub fn is_machine_readable(self) -> bool {
+        !matches!(self, Self::Pretty | Self::Html | Self::HtmlFragment)
+    }
+
+    /// The pretty rendering, converted to HTML.
+    pub fn is_html(self) -> bool {
+        matches!(self, Self::Html | Self::HtmlFragment)
+    }
+}
+
+/// Write whatever precedes the first record: the header row of the tabular formats,
+/// or the opening markup of the HTML output.
+pub fn write_header(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    fields: &[String],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Html => html::write_prologue(out, true),
+        OutputFormat::HtmlFragment => html::write_prologue(out, false),
+        OutputFormat::Csv => {
+            write_delimited(out, fields.iter().map(|f| Some(f.as_str())), b',', csv_cell)
+        }
+        OutputFormat::Tsv => write_delimited(
+            out,
+            fields.iter().map(|f| Some(f.as_str())),
+            b'\t',
+            tsv_cell,
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Write whatever follows the last record.
+pub fn write_footer(out: &mut dyn Write, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Html => html::write_epilogue(out, true),
+        OutputFormat::HtmlFragment => html::write_epilogue(out, false),
+        _ => Ok(()),
+    }
+}
+
+/// Write a normalized record in one of the machine-readable formats.
+///
+/// `fields` selects the columns of the tabular formats and is ignored otherwise.
+pub fn write_record(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    rec: &Record,
+    fields: &[String],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Logfmt => write_logfmt(out, rec),
+        OutputFormat::Json => write_json(out, rec),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let cells: Vec<Option<String>> = fields
+                .iter()
+                .map(|f| rec.get(f).map(|v| cell_text(&v)))
+                .collect();
+            let cells = cells.iter().map(Option::as_deref);
+            if format == OutputFormat::Csv {
+                write_delimited(out, cells, b',', csv_cell)
+            } else {
+                write_delimited(out, cells, b'\t', tsv_cell)
+            }
+        }
+        // Written by the SQLite sink instead
+        OutputFormat::Sqlite => Ok(()),
+        OutputFormat::Pretty | OutputFormat::Html | OutputFormat::HtmlFragment => {
+            unreachable!("pretty output renders through the protocols")
+        }
+    }
+}
+
+/// Write one record as a logfmt line.
+pub fn write_logfmt(out: &mut dyn Write, rec: &Record) -> io::Result<()> {
+    let mut pairs: Vec<(&str, Value)> = Vec::with_capacity(rec.fields.len() + 4);
+    if let Some(ts) = &rec.timestamp {
+        pairs.push(("ts", Value::String(ts.clone())));
+    }
+    if let Some(level) = rec.level {
+        pairs.push(("level", Value::String(level.as_str().to_ascii_lowercase())));
+    }
+    if let Some(msg) = &rec.message {
+        pairs.push(("msg", Value::String(msg.clone())));
+    }
+    if let Some(logger) = &rec.logger {
+        pairs.push(("logger", Value::String(logger.clone())));
+    }
+    for (k, v) in &rec.fields {
+        pairs.push((k.as_str(), v.clone()));
+    }
+
+    for (i, (key, val)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b" ")?;
+        }
+        write_logfmt_key(&mut *out, key)?;
+        out.write_all(b"=")?;
+        match val {
+            Value::String(s) => write_logfmt_value(&mut *out, s)?,
+            Value::Null => {}
+            Value::Bool(_) | Value::Number(_) => write!(out, "{}", val)?,
+            _ => write_logfmt_value(&mut *out, &val.to_string())?,
+        }
+    }
+    out.write_all(b"\n")
+}
+
+/// Write one record as a single-line JSON object with the canonical keys first.
+///
+/// Fields whose names clash with a canonical key are prefixed with `fields.`.
+pub fn write_json(out: &mut dyn Write, rec: &Record) -> io::Result<()> {
+    const CANONICAL: [&str; 4] = ["ts", "level", "message", "logger"];
+    let level = rec.level.map(|l| l.as_str().to_ascii_lowercase());
+    let canonical = [
+        rec.timestamp.as_deref(),
+        level.as_deref(),
+        rec.message.as_deref(),
+        rec.logger.as_deref(),
+    ];
+
+    let mut first = true;
+    let mut sep = |out: &mut dyn Write| {
+        let s: &[u8] = if first { b"{" } else { b"," };
+        first = false;
+        out.write_all(s)
+    };
+    for (key, val) in CANONICAL.iter().zip(canonical) {
+        if let Some(val) = val {
+            sep(&mut *out)?;
+            serde_json::to_writer(&mut *out, key)?;
+            out.write_all(b":")?;
+            serde_json::to_writer(&mut *out, val)?;
+        }
+    }
+    for (key, val) in &rec.fields {
+        sep(&mut *out)?;
+        if CANONICAL.contains(&key.as_str()) {
+            serde_json::to_writer(&mut *out, &format!("fields.{key}"))?;
+        } else {
+            serde_json::to_writer(&mut *out, key)?;
+        }
+        out.write_all(b":")?;
+        serde_json::to_writer(&mut *out, val)?;
+    }
+    if first {
+        out.write_all(b"{")?;
+    }
+    out.write_all(b"}\n")
+}
+
+/// Keys may not contain spaces, `=` or quotes; replace those with `_`.
+fn write_logfmt_key(out: &mut dyn Write, key: &str) -> io::Result<()> {
+    if key
+        .chars()
+        .all(|c| c.is_ascii_graphic() && c != '=' && c != '"')
+    {
+        out.write_all(key.as_bytes())
+    } else {
+        let key: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_graphic() && c != '=' && c != '"' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        out.write_all(key.as_bytes())
+    }
+}
+
+/// Values are bare when safe, otherwise double-quoted with backslash escapes.
+fn write_logfmt_value(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    let bare = !s.is_empty()
+        && s.chars()
+            .all(|c| !c.is_whitespace() && !c.is_control() && c != '=' && c != '"' && c != '\\');
+    if bare {
+        return out.write_all(s.as_bytes());
+    }
+    out.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\t' => out.write_all(b"\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+/// Strings are written as-is, everything else as compact JSON.
+fn cell_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        _ => v.to_string(),
+    }
+}
+
+/// Write one row of a CSV or TSV table, e.g. of a report.
+pub fn write_cells(out: &mut dyn Write, format: OutputFormat, cells: &[&str]) -> io::Result<()> {
+    let cells = cells.iter().copied().map(Some);
+    if format == OutputFormat::Tsv {
+        write_delimited(out, cells, b'\t', tsv_cell)
+    } else {
+        write_delimited(out, cells, b',', csv_cell)
+    }
+}
+
+/// Write one row of cells; missing cells stay empty.
+fn write_delimited<'a>(
+    out: &mut dyn Write,
+    cells: impl Iterator<Item = Option<&'a str>>,
+    sep: u8,
+    cell: fn(&mut dyn Write, &str) -> io::Result<()>,
+) -> io::Result<()> {
+    for (i, c) in cells.enumerate() {
+        if i > 0 {
+            out.write_all(&[sep])?;
+        }
+        cell(&mut *out, c.unwrap_or_default())?;
+    }
+    out.write_all(b"\n")
+}
+
+/// RFC 4180: quote cells containing separators, quotes or line breaks; double inner quotes.
+fn csv_cell(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    if s.contains([',', '"', '\r', '\n']) {
+        out.write_all(b"\"")?;
+        out.write_all(s.replace('"', "\"\"").as_bytes())?;
+        out.write_all(b"\"")?;
+    } else {
+        out.write_all(s.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// TSV cannot quote, so tabs, line breaks and backslashes are backslash-escaped.
+fn tsv_cell(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    for c in s.chars() {
+        match c {
+            '\t' => out.write_all(b"\\t")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\\' => out.write_all(b"\\\\")?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Output buffer of a batch run (`--buffered`).
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// How often a batch run flushes its output anyway, so that it shows progress.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Block-buffered output for formatting large files, flushed when full, every
+/// [`FLUSH_INTERVAL`], and at the end, instead of after every line.
+pub struct Buffered<W: Write> {
+    inner: BufWriter<W>,
+    flushed: Instant,
+}
+
+impl<W: Write> Buffered<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: BufWriter::with_capacity(BUFFER_SIZE, inner),
+            flushed: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write> Write for Buffered<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed = Instant::now();
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::Level;
+    use serde_json::json;
+
+    fn rec(fields: Value) -> Record {
+        Record {
+            timestamp: Some("2024-05-03T10:00:00Z".into()),
+            level: Some(Level::Warn),
+            message: Some("disk almost full".into()),
+            logger: Some("app::disk".into()),
+            fields: fields
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    fn logfmt(rec: &Record) -> String {
+        let mut out = Vec::new();
+        write_logfmt(&mut out, rec).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn logfmt_line() {
+        let rec = rec(json!({"free": 0.5, "ok": false, "none": null, "tags": ["a", "b"]}));
+        assert_eq!(
+            logfmt(&rec),
+            "ts=2024-05-03T10:00:00Z level=warn msg=\"disk almost full\" logger=app::disk \
+             free=0.5 ok=false none= tags=\"[\\\"a\\\",\\\"b\\\"]\"\n"
+        );
+        assert_eq!(logfmt(&Record::default()), "\n");
+    }
+
+    #[test]
+    fn logfmt_escapes() {
+        let mut rec = Record::default();
+        rec.push("a key=\"x\"", Some(json!("v")));
+        rec.push("path", Some(json!("C:\\tmp")));
+        rec.push("text", Some(json!("line\nnext\ttab\u{1b}[31m")));
+        rec.push("eq", Some(json!("a=b")));
+        assert_eq!(
+            logfmt(&rec),
+            "a_key__x_=v path=\"C:\\\\tmp\" text=\"line\\nnext\\ttab\\u001b[31m\" eq=\"a=b\"\n"
+        );
+    }
+
+    #[test]
+    fn json_line() {
+        let rec = rec(json!({"level": "shadowed", "n": 1.5, "nested": {"a": [1]}}));
+        let mut out = Vec::new();
+        write_json(&mut out, &rec).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"ts\":\"2024-05-03T10:00:00Z\",\"level\":\"warn\",\"message\":\"disk almost full\",\
+             \"logger\":\"app::disk\",\"fields.level\":\"shadowed\",\"n\":1.5,\"nested\":{\"a\":[1]}}\n"
+        );
+        let mut out = Vec::new();
+        write_json(&mut out, &Record::default()).unwrap();
+        assert_eq!(out, b"{}\n");
+    }
+
+    #[test]
+    fn csv_and_tsv_rows() {
+        let rec = rec(
+            json!({"path": "/a,b", "quote": "say \"hi\"", "multi": "a\tb\nc\\d", "http": {"status": 503}}),
+        );
+        let fields: Vec<String> = "ts,level,path,quote,multi,http.status,missing"
+            .split(',')
+            .map(String::from)
+            .collect();
+        let table = |format| {
+            let mut out = Vec::new();
+            write_header(&mut out, format, &fields).unwrap();
+            write_record(&mut out, format, &rec, &fields).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            table(OutputFormat::Csv),
+            "ts,level,path,quote,multi,http.status,missing\n\
+             2024-05-03T10:00:00Z,warn,\"/a,b\",\"say \"\"hi\"\"\",\"a\tb\nc\\d\",503,\n"
+        );
+        assert_eq!(
+            table(OutputFormat::Tsv),
+            "ts\tlevel\tpath\tquote\tmulti\thttp.status\tmissing\n\
+             2024-05-03T10:00:00Z\twarn\t/a,b\tsay \"hi\"\ta\\tb\\nc\\\\d\t503\t\n"
+        );
+    }
+}
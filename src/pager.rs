@@ -0,0 +1,40 @@
+//! Paging of long output through `$PAGER` (default `less`), like git does.
+
+use std::io;
+use std::process::{Child, Command, Stdio};
+
+/// Start the pager with its stdin piped, or `None` if paging is disabled.
+///
+/// `JLO_PAGER` takes precedence over `PAGER`; an empty value or `cat` disables paging.
+/// Unless `LESS` is set, `less` runs with `-FRX`: quit right away if the output fits
+/// on one screen, pass colors through, and leave the output on the screen.
+pub fn spawn() -> io::Result<Option<Child>> {
+    let pager = std::env::var("JLO_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| "less".to_string());
+    let pager = pager.trim();
+    if pager.is_empty() || pager == "cat" {
+        return Ok(None);
+    }
+
+    let mut cmd = if cfg!(windows) {
+        let mut parts = pager.split_whitespace();
+        let mut cmd = Command::new(parts.next().unwrap_or(pager));
+        cmd.args(parts);
+        cmd
+    } else {
+        // Like git, let the shell handle pagers given with arguments
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(pager);
+        cmd
+    };
+    if std::env::var_os("LESS").is_none() {
+        cmd.env("LESS", "FRX");
+    }
+    match cmd.stdin(Stdio::piped()).spawn() {
+        Ok(child) => Ok(Some(child)),
+        // No pager installed: just print
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
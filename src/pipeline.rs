@@ -0,0 +1,1475 @@
+//! The per-line processing shared by every input: parse, detect the protocol, and write.
+
+use jiff::Timestamp;
+use serde_json::Value;
+use std::cell::{Cell, OnceCell};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::alert::{Alert, AlertRule, Tripped};
+use crate::context::{Context, Shown};
+use crate::dedup::Dedup;
+use crate::encoding;
+use crate::filter::{Filter, Line, Selector};
+use crate::footer::RateFooter;
+use crate::highlight::Highlights;
+use crate::html;
+use crate::jq::Jq;
+use crate::json::{self, Fields};
+use crate::memory::Budget;
+use crate::output::{self, OutputFormat};
+use crate::protocols::generic::Generic;
+use crate::protocols::{Detection, JsonProtocol, Level, Record, Sticky};
+use crate::rate::RateLimit;
+use crate::report::Report;
+use crate::size::humanize_bytes;
+use crate::split::Split;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteSink;
+use crate::stats::{self, Stats, Tally};
+use crate::tail::{KeyFilter, Tail};
+use crate::template::Template;
+use crate::theme::Theme;
+use crate::time;
+use crate::traces::{Group, TraceGroups};
+use crate::{Palette, RawStyle, RenderCtx, write_kv_json, write_kv_str, write_level};
+
+mod jobs;
+
+/// Output destination plus everything needed to turn input lines into output records.
+pub struct Pipeline {
+    out: Box<dyn Write>,
+    /// Scratch buffer a record is rendered into, then written to `out` in one go.
+    rendered: Vec<u8>,
+    /// Whether anything preceding the first record (e.g. a CSV header) was written.
+    started: bool,
+    renderer: Renderer,
+    /// Receives every input line verbatim.
+    tee: Option<Box<dyn Write>>,
+    /// Receives the lines that aren't JSON instead of the output.
+    invalid: Option<Invalid>,
+    /// How records are matched to protocols, with the protocol locked in for the current input.
+    detection: Detection,
+    /// Lines that aren't strict JSON are read as JSON5 (`--relaxed`).
+    relaxed: bool,
+    /// Locks in the protocol of each input from its first records (`--sticky`).
+    sticky: Option<Sticky>,
+    /// Lines that don't pass are dropped before rendering (but still reach `tee`).
+    filter: Filter,
+    /// Also shows lines around those that pass `filter`.
+    context: Option<Context>,
+    /// Colorless renderer for context lines, made on first use.
+    context_renderer: OnceCell<Renderer>,
+    /// Keys pruned from each JSON line that passes `filter`, before `jq` and rendering.
+    drop_keys: Option<KeyFilter>,
+    /// Transforms each JSON line that passes `filter`; every output becomes its own line.
+    jq: Option<Jq>,
+    /// Collapses repeated messages in the rendered output (but not in `split` or `db`).
+    dedup: Option<Dedup>,
+    /// Only the first this many lines of each input (`--head`).
+    head: Option<usize>,
+    /// Only the last this many lines of each input (`--tail`).
+    tail: Option<usize>,
+    /// Threads handling the lines of regular files (`--jobs`).
+    jobs: usize,
+    /// Caps the rendered lines per second.
+    rate: Option<RateLimit>,
+    /// Additionally routes lines into one file per value of a field.
+    split: Option<Split>,
+    /// Threshold for `--gap-warn` and the timestamp of the previous rendered record.
+    gap_warn: Option<(Duration, Option<Timestamp>)>,
+    /// Holds back records with a trace ID to write each trace as a block (`--group-by-trace`).
+    traces: Option<TraceGroups>,
+    /// Aggregate the records that pass instead of rendering them (`--report`).
+    reports: Vec<Box<dyn Report>>,
+    /// Also write the reports at this interval, and when they were last written (`--report-every`).
+    report_every: Option<(Duration, Instant)>,
+    /// Conditions on the recent records that pass `filter` (`--alert`).
+    alerts: Vec<Alert>,
+    /// End the run when an alert trips (`--fail-on-alert`).
+    fail_on_alert: bool,
+    /// Level from which records that pass `filter` fail the run (`--fail-on`), and how many did.
+    fail_on: Option<(Level, u64)>,
+    /// Live status line under the output (`--rate`).
+    footer: Option<RateFooter>,
+    /// Counters for `--stats`, shared with the Ctrl-C handler.
+    stats: Option<Arc<Mutex<Stats>>>,
+    /// Lines that weren't JSON or fell back to the generic renderer, noted at the end.
+    tally: Option<Tally>,
+    /// Bound on the state of `dedup`, `traces`, and `reports` (`--max-memory`).
+    memory: Option<Budget>,
+    #[cfg(feature = "sqlite")]
+    db: Option<SqliteSink>,
+}
+
+/// What holds state under `--max-memory`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Holder {
+    Traces,
+    Dedup,
+    Report(usize),
+}
+
+/// Where `--invalid-to` sends lines that aren't JSON.
+struct Invalid {
+    out: Box<dyn Write>,
+    dest: String,
+    count: u64,
+}
+
+/// How a parsed line is turned into output.
+#[derive(Clone)]
+pub struct Renderer {
+    ctx: RenderCtx,
+    /// Columns for the tabular output formats.
+    fields: Vec<String>,
+    /// User-defined line layout replacing the pretty renderers.
+    template: Option<Template>,
+    /// Render settings for protocols with their own style in the configuration file.
+    styles: Vec<(&'static str, RenderCtx)>,
+    /// Show only `fields` in every output format, not just as the csv/tsv columns.
+    project: bool,
+    /// Background across ERROR and FATAL records.
+    highlight_errors: bool,
+    /// Timestamp of the previous line for `--delta`; `None` when deltas are off.
+    delta: Option<Cell<Option<Timestamp>>>,
+    /// JSONPath values appended to the tail.
+    selectors: Vec<Selector>,
+    /// Patterns colored in the output.
+    highlights: Highlights,
+    /// How lines that aren't JSON are shown.
+    raw_style: RawStyle,
+}
+
+impl Pipeline {
+    pub fn new(ctx: RenderCtx, out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            rendered: Vec::new(),
+            started: false,
+            renderer: Renderer {
+                ctx,
+                fields: Vec::new(),
+                template: None,
+                project: false,
+                highlight_errors: false,
+                styles: Vec::new(),
+                delta: None,
+                selectors: Vec::new(),
+                highlights: Highlights::default(),
+                raw_style: RawStyle::default(),
+            },
+            tee: None,
+            invalid: None,
+            detection: Detection::default(),
+            relaxed: false,
+            sticky: None,
+            filter: Filter::default(),
+            context: None,
+            context_renderer: OnceCell::new(),
+            drop_keys: None,
+            jq: None,
+            dedup: None,
+            head: None,
+            tail: None,
+            jobs: 1,
+            rate: None,
+            split: None,
+            gap_warn: None,
+            traces: None,
+            reports: Vec::new(),
+            report_every: None,
+            alerts: Vec::new(),
+            fail_on_alert: false,
+            fail_on: None,
+            footer: None,
+            stats: None,
+            tally: None,
+            memory: None,
+            #[cfg(feature = "sqlite")]
+            db: None,
+        }
+    }
+
+    /// Columns for the tabular output formats.
+    pub fn fields(mut self, fields: Vec<String>) -> Self {
+        self.renderer.fields = fields;
+        self
+    }
+
+    /// Restrict every record to the fields given to [`Pipeline::fields`].
+    pub fn project(mut self, project: bool) -> Self {
+        self.renderer.project = project;
+        self
+    }
+
+    /// Render lines through a user-defined template instead of the protocol renderers.
+    pub fn template(mut self, template: Option<Template>) -> Self {
+        self.renderer.template = template;
+        self
+    }
+
+    /// Color matches of these patterns in the rendered output, each pattern in its own color.
+    pub fn highlight(mut self, patterns: Vec<regex::bytes::Regex>) -> Self {
+        self.renderer.highlights = Highlights::new(patterns);
+        self
+    }
+
+    /// How to show lines that aren't JSON in the pretty output.
+    pub fn raw_style(mut self, style: RawStyle) -> Self {
+        self.renderer.raw_style = style;
+        self
+    }
+
+    /// Per-protocol render settings, keyed by [`JsonProtocol::name`].
+    pub fn styles(mut self, styles: Vec<(&'static str, RenderCtx)>) -> Self {
+        self.renderer.styles = styles;
+        self
+    }
+
+    /// Append the time since the previous line's timestamp to every pretty record.
+    pub fn delta(mut self, delta: bool) -> Self {
+        self.renderer.delta = delta.then(Cell::default);
+        self
+    }
+
+    /// Give error records a background color spanning the whole line.
+    pub fn highlight_errors(mut self, highlight: bool) -> Self {
+        self.renderer.highlight_errors = highlight;
+        self
+    }
+
+    /// Copy every input line, unmodified, to `tee`.
+    pub fn tee(mut self, tee: Option<Box<dyn Write>>) -> Self {
+        self.tee = tee;
+        self
+    }
+
+    /// Write lines that aren't JSON to `out` instead of rendering them; `dest` names it in the
+    /// count reported at the end.
+    pub fn invalid_to(mut self, out: Option<Box<dyn Write>>, dest: String) -> Self {
+        self.invalid = out.map(|out| Invalid {
+            out,
+            dest,
+            count: 0,
+        });
+        self
+    }
+
+    /// Only render lines that pass `filter`; its `--select` values are also shown in the tail.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.renderer.selectors = filter.selectors.clone();
+        self.filter = filter;
+        self
+    }
+
+    /// Show `before` and `after` lines around each line that passes the filter, dimmed.
+    pub fn context(mut self, before: usize, after: usize) -> Self {
+        self.context = (before > 0 || after > 0).then(|| Context::new(before, after));
+        self
+    }
+
+    /// Prune keys matching these globs (`--drop-keys`) from JSON lines after filtering.
+    pub fn drop_keys(mut self, patterns: Vec<String>) -> Self {
+        self.drop_keys = (!patterns.is_empty()).then(|| KeyFilter {
+            hide: patterns.into(),
+        });
+        self
+    }
+
+    /// Run JSON lines through a jq expression after filtering.
+    pub fn jq(mut self, jq: Option<Jq>) -> Self {
+        self.jq = jq;
+        self
+    }
+
+    /// Collapse repeats of the last `window` distinct messages into a summary line.
+    pub fn dedup(mut self, window: Option<usize>) -> Self {
+        self.dedup = window.map(Dedup::new);
+        self
+    }
+
+    /// Match records to protocols by `detection` instead of every protocol's default threshold.
+    pub fn detection(mut self, detection: Detection) -> Self {
+        self.detection = detection;
+        self
+    }
+
+    /// Also read lines that aren't strict JSON as JSON5.
+    pub fn relaxed(mut self, relaxed: bool) -> Self {
+        self.relaxed = relaxed;
+        self
+    }
+
+    /// Decide the protocol of each input from its first `lines` records sniffed as one.
+    pub fn sticky(mut self, lines: Option<usize>) -> Self {
+        self.sticky = lines.map(Sticky::new);
+        self
+    }
+
+    /// Only process the first `n` lines of each reader.
+    pub fn head(mut self, n: Option<usize>) -> Self {
+        self.head = n;
+        self
+    }
+
+    /// Only process the last `n` lines of each reader; see [`crate::sources::seek_to_last_lines`]
+    /// for skipping most of a regular file.
+    pub fn tail(mut self, n: Option<usize>) -> Self {
+        self.tail = n;
+        self
+    }
+
+    /// Parse, filter, and render the lines of regular files on `n` threads; see
+    /// [`Pipeline::process_file`].
+    pub fn jobs(mut self, n: usize) -> Self {
+        self.jobs = n.max(1);
+        self
+    }
+
+    /// Render at most `max` lines per second, reporting how many were dropped.
+    pub fn max_rate(mut self, max: Option<u32>) -> Self {
+        self.rate = max.map(RateLimit::new);
+        self
+    }
+
+    /// Also route lines into per-value files; see [`Split`].
+    pub fn split(mut self, split: Option<Split>) -> Self {
+        self.split = split;
+        self
+    }
+
+    /// Mark where consecutive rendered records are more than `threshold` apart.
+    pub fn gap_warn(mut self, threshold: Option<Duration>) -> Self {
+        self.gap_warn = threshold.map(|t| (t, None));
+        self
+    }
+
+    /// Write the records of each trace together, under a summary, once it has been quiet for `idle`.
+    pub fn group_by_trace(mut self, idle: Option<Duration>) -> Self {
+        self.traces = idle.map(TraceGroups::new);
+        self
+    }
+
+    /// Feed the records that pass into `reports`, written at the end, instead of rendering them.
+    pub fn reports(mut self, reports: Vec<Box<dyn Report>>) -> Self {
+        self.reports = reports;
+        self
+    }
+
+    /// Write the reports so far every `interval` while records arrive, e.g. while following.
+    pub fn report_every(mut self, interval: Option<Duration>) -> Self {
+        self.report_every = interval.map(|i| (i, Instant::now()));
+        self
+    }
+
+    /// Ring the bell and print a banner when one of `rules` starts to hold; with `fail`, the
+    /// run then ends with a [`Tripped`] error.
+    pub fn alerts(mut self, rules: Vec<AlertRule>, fail: bool) -> Self {
+        self.alerts = rules.into_iter().map(Alert::new).collect();
+        self.fail_on_alert = fail;
+        self
+    }
+
+    /// Count the records at or above `level` that pass the filters, to fail the run with them.
+    pub fn fail_on(mut self, level: Option<Level>) -> Self {
+        self.fail_on = level.map(|level| (level, 0));
+        self
+    }
+
+    /// The `--fail-on` level and how many records reached it, if any did.
+    pub fn failed(&self) -> Option<(Level, u64)> {
+        self.fail_on.filter(|(_, n)| *n > 0)
+    }
+
+    /// Keep a status line with the current rates under the output; for terminals only.
+    pub fn rate_footer(mut self, enabled: bool) -> Self {
+        self.footer = enabled.then(RateFooter::default);
+        self
+    }
+
+    /// Count every input line into `stats`, written to stderr by [`Pipeline::finish`].
+    pub fn stats(mut self, stats: Option<Arc<Mutex<Stats>>>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Count how each line was read, to note on stderr at the end whether some weren't JSON or
+    /// fell back to the generic renderer.
+    pub fn tally(mut self, enabled: bool) -> Self {
+        self.tally = enabled.then(Tally::default);
+        self
+    }
+
+    /// Give up state of `--dedup`, `--group-by-trace`, and the reports once it takes more than
+    /// about `limit` bytes.
+    pub fn max_memory(mut self, limit: Option<usize>) -> Self {
+        self.memory = limit.map(Budget::new);
+        self
+    }
+
+    /// Insert every line into a SQLite database (`--output sqlite`).
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(mut self, db: Option<SqliteSink>) -> Self {
+        self.db = db;
+        self
+    }
+
+    /// The render settings for writing to files, without ANSI colors.
+    pub fn plain_renderer(&self) -> Renderer {
+        self.renderer.plain()
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            // Reports write their own tables instead of the records' columns
+            let tabular = matches!(
+                self.renderer.ctx.output,
+                OutputFormat::Csv | OutputFormat::Tsv
+            );
+            if !tabular || self.reports.is_empty() {
+                self.renderer.write_header(&mut self.out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process every line of `reader` until EOF, or the `--head`/`--tail` lines of it.
+    pub fn process_reader<R: BufRead>(&mut self, reader: R) -> io::Result<()> {
+        self.start_stream();
+        let reader = encoding::decode(reader)?;
+        self.read_lines(reader)
+    }
+
+    /// Forget what was learned about the previous input.
+    fn start_stream(&mut self) {
+        if let Some(sticky) = &mut self.sticky {
+            sticky.reset();
+            self.detection.locked = None;
+        }
+    }
+
+    fn read_lines(&mut self, mut reader: impl BufRead) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(8 * 1024);
+        let mut processed = 0;
+        let mut last = VecDeque::new();
+
+        loop {
+            if self.head.is_some_and(|n| processed >= n) {
+                break;
+            }
+            buf.clear();
+            let n = reader.read_until(b'\n', &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            while matches!(buf.last(), Some(b'\n' | b'\r')) {
+                buf.pop();
+            }
+            if buf.is_empty() {
+                continue;
+            }
+
+            if let Some(n) = self.tail {
+                if last.len() == n {
+                    last.pop_front();
+                }
+                if n > 0 {
+                    last.push_back(buf.clone());
+                }
+                continue;
+            }
+            self.process_line(&buf, None)?;
+            processed += 1;
+        }
+        for line in last {
+            self.process_line(&line, None)?;
+        }
+        Ok(())
+    }
+
+    /// Render a single input line (without its line terminator).
+    ///
+    /// A `note` from the source (e.g. Kafka partition/offset) is printed dimmed before the record.
+    pub fn process_line(&mut self, buf: &[u8], note: Option<&str>) -> io::Result<()> {
+        let Some(footer) = &mut self.footer else {
+            return self.handle_line(buf, note);
+        };
+        footer.clear(&mut self.out)?;
+        self.handle_line(buf, note)?;
+        if let Some(footer) = &mut self.footer {
+            footer.draw(&mut self.out, self.renderer.ctx.pal)?;
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self, buf: &[u8], note: Option<&str>) -> io::Result<()> {
+        self.start()?;
+        if let Some(tee) = &mut self.tee {
+            tee.write_all(buf)?;
+            tee.write_all(b"\n")?;
+        }
+
+        // Once per line, however many records it holds
+        if let Some(tally) = &mut self.tally {
+            tally.line();
+        }
+        if let Some(stats) = &self.stats {
+            stats::lock(stats).line(buf.len());
+        }
+        let relaxed = self.relaxed;
+        for_each_record(buf, note, relaxed, |buf, json, note| {
+            json.line(buf, self.detection, |line| self.handle_record(line, note))
+        })?;
+        self.fit_memory()
+    }
+
+    /// Give up state, from whatever holds the most, while it adds up to more than
+    /// `--max-memory`.
+    fn fit_memory(&mut self) -> io::Result<()> {
+        let Some(limit) = self.memory.as_mut().and_then(Budget::due) else {
+            return Ok(());
+        };
+        let total = |used: &[(usize, Holder)]| used.iter().map(|(bytes, _)| bytes).sum::<usize>();
+        let mut used = self.memory_used();
+        while total(&used) > limit {
+            if self.memory.as_mut().is_some_and(Budget::reach) {
+                eprintln!(
+                    "jlo: over --max-memory ({}), giving up the oldest or rarest state",
+                    humanize_bytes(limit as u64)
+                );
+            }
+            let Some(&(_, holder)) = used.iter().max() else {
+                break;
+            };
+            match holder {
+                Holder::Traces => {
+                    for group in self
+                        .traces
+                        .as_mut()
+                        .map(TraceGroups::evict)
+                        .unwrap_or_default()
+                    {
+                        self.renderer.write_trace(&group, &mut self.out)?;
+                    }
+                }
+                Holder::Dedup => {
+                    for summary in self.dedup.as_mut().map(Dedup::shrink).unwrap_or_default() {
+                        self.renderer.write_notice(&summary, &mut self.out)?;
+                    }
+                }
+                Holder::Report(i) => self.reports[i].shrink(),
+            }
+            let before = total(&used);
+            used = self.memory_used();
+            // Nothing left to give up
+            if total(&used) >= before {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Roughly how many bytes each stateful part holds.
+    fn memory_used(&self) -> Vec<(usize, Holder)> {
+        let mut used = Vec::with_capacity(self.reports.len() + 2);
+        if let Some(traces) = &self.traces {
+            used.push((traces.memory(), Holder::Traces));
+        }
+        if let Some(dedup) = &self.dedup {
+            used.push((dedup.memory(), Holder::Dedup));
+        }
+        for (i, report) in self.reports.iter().enumerate() {
+            used.push((report.memory(), Holder::Report(i)));
+        }
+        used
+    }
+
+    /// Filter and emit one line, or one of several documents on a line.
+    fn handle_record(&mut self, line: &Line<'_>, note: Option<&str>) -> io::Result<()> {
+        if let (Some(sticky), Some(p)) = (&mut self.sticky, line.protocol())
+            && let Some(locked) = sticky.observe(p)
+        {
+            self.detection.locked = Some(locked);
+        }
+        if let Some(tally) = &mut self.tally {
+            tally.count(line);
+        }
+        if !line.is_json()
+            && let Some(invalid) = &mut self.invalid
+        {
+            if let Some(stats) = &self.stats {
+                stats::lock(stats).record(line, false);
+            }
+            invalid.count += 1;
+            invalid.out.write_all(line.buf)?;
+            return invalid.out.write_all(b"\n");
+        }
+        let matched = self.filter.keep(line);
+        if let Some(stats) = &self.stats {
+            stats::lock(stats).record(line, matched);
+        }
+        if matched {
+            let fired: Vec<_> = self
+                .alerts
+                .iter_mut()
+                .filter_map(|a| a.observe(line))
+                .collect();
+            for text in fired {
+                self.renderer.write_alert(&text, &mut self.out)?;
+                if self.fail_on_alert {
+                    return Err(io::Error::other(Tripped(text)));
+                }
+            }
+        }
+        let Some(context) = &mut self.context else {
+            if !matched {
+                return Ok(());
+            }
+            return self.transform(line, note);
+        };
+        for shown in context.push(line.buf, matched) {
+            match shown {
+                Shown::Separator => self.renderer.write_notice("--", &mut self.out)?,
+                Shown::Context(held) => self.write_context(&held)?,
+                Shown::Match => self.transform(line, note)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a line that passed filtering through `--drop-keys` and `--jq`, then emit it.
+    fn transform(&mut self, line: &Line<'_>, note: Option<&str>) -> io::Result<()> {
+        // `emit` borrows all of `self`
+        let (drop_keys, jq) = (self.drop_keys.take(), self.jq.take());
+        let result = for_each_output(line, drop_keys.as_ref(), jq.as_ref(), |line| {
+            self.emit(line, note)
+        });
+        (self.drop_keys, self.jq) = (drop_keys, jq);
+        result
+    }
+
+    /// Render a `-A`/`-B`/`-C` context line dimmed; it skips the other sinks.
+    fn write_context(&mut self, buf: &[u8]) -> io::Result<()> {
+        let v = json::parse(buf, self.relaxed);
+        let line = Line::new(buf, v.as_ref(), self.detection);
+        let pal = self.renderer.ctx.pal;
+        if !pal.enabled || self.renderer.ctx.output.is_html() {
+            return self.renderer.render(&line, None, &mut self.out);
+        }
+        let plain = self.context_renderer.get_or_init(|| self.renderer.plain());
+        let mut rendered = Vec::new();
+        plain.render(&line, None, &mut rendered)?;
+        for line in rendered.split_inclusive(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            self.out.write_all(pal.faint.as_bytes())?;
+            self.out.write_all(line)?;
+            writeln!(self.out, "{}", pal.reset)?;
+        }
+        Ok(())
+    }
+
+    /// Render a line that passed filtering and hand it to the other sinks.
+    fn emit(&mut self, line: &Line<'_>, note: Option<&str>) -> io::Result<()> {
+        if let Some((min, n)) = &mut self.fail_on
+            && line.level().is_some_and(|level| level >= *min)
+        {
+            *n += 1;
+        }
+        if !self.reports.is_empty() {
+            for report in &mut self.reports {
+                report.record(line);
+            }
+            let due = self.report_every.as_mut().is_some_and(|(interval, last)| {
+                let due = last.elapsed() >= *interval;
+                if due {
+                    *last = Instant::now();
+                }
+                due
+            });
+            if due {
+                let now = jiff::Zoned::now().strftime("%H:%M:%S").to_string();
+                self.renderer
+                    .write_notice(&format!("―― {now} ――"), &mut self.out)?;
+                self.write_reports()?;
+                self.out.flush()?;
+            }
+            return Ok(());
+        }
+        let repeat = match &mut self.dedup {
+            Some(dedup) => {
+                let (repeat, expired) = dedup.push(line);
+                if let Some(summary) = expired {
+                    self.renderer.write_notice(&summary, &mut self.out)?;
+                }
+                repeat
+            }
+            None => false,
+        };
+        let admitted = match &mut self.rate {
+            Some(rate) if !repeat => {
+                let (admitted, suppressed) = rate.admit();
+                if let Some(n) = suppressed {
+                    write_suppressed(&self.renderer, n, &mut self.out)?;
+                }
+                admitted
+            }
+            _ => !repeat,
+        };
+        if admitted {
+            if let Some(footer) = &mut self.footer {
+                footer.observe(line);
+            }
+            if let Some((threshold, prev)) = &mut self.gap_warn
+                && let Some(ts) = line.timestamp().and_then(time::parse_timestamp)
+            {
+                let gap = prev
+                    .replace(ts)
+                    .map(|prev| ts.as_millisecond() - prev.as_millisecond());
+                if let Some(gap) = gap
+                    && gap > threshold.as_millis() as i64
+                {
+                    let gap = time::humanize_duration(Duration::from_millis(gap as u64));
+                    self.renderer.write_gap(&gap, &mut self.out)?;
+                }
+            }
+            self.render_or_hold(line, note)?;
+        }
+        if let Some(split) = &mut self.split {
+            split.write(line)?;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &mut self.db {
+            db.insert(line)?;
+        }
+        Ok(())
+    }
+
+    /// Render a record, or hold it back until its trace is quiet; traces that went quiet come first.
+    fn render_or_hold(&mut self, line: &Line<'_>, note: Option<&str>) -> io::Result<()> {
+        let Some(traces) = &mut self.traces else {
+            return self.write_rendered(line, note);
+        };
+        let held = traces.push(line, note);
+        for group in traces.quiet() {
+            self.renderer.write_trace(&group, &mut self.out)?;
+        }
+        if held {
+            return Ok(());
+        }
+        self.write_rendered(line, note)
+    }
+
+    /// Render a record and write it in one call: one syscall per record on unbuffered output.
+    fn write_rendered(&mut self, line: &Line<'_>, note: Option<&str>) -> io::Result<()> {
+        self.rendered.clear();
+        self.renderer.render(line, note, &mut self.rendered)?;
+        self.out.write_all(&self.rendered)
+    }
+
+    fn write_reports(&mut self) -> io::Result<()> {
+        for report in &self.reports {
+            self.renderer.write_report(report.as_ref(), &mut self.out)?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered output at the end of the run.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(footer) = &mut self.footer {
+            footer.clear(&mut self.out)?;
+        }
+        self.start()?;
+        if let Some(tee) = &mut self.tee {
+            tee.flush()?;
+        }
+        if let Some(invalid) = &mut self.invalid {
+            invalid.out.flush()?;
+            let lines = if invalid.count == 1 {
+                "line was"
+            } else {
+                "lines were"
+            };
+            eprintln!(
+                "jlo: {} {lines} not valid JSON, written to {}",
+                invalid.count, invalid.dest
+            );
+        }
+        if let Some(traces) = &mut self.traces {
+            for group in traces.finish() {
+                self.renderer.write_trace(&group, &mut self.out)?;
+            }
+        }
+        if let Some(split) = &mut self.split {
+            split.finish()?;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &mut self.db {
+            db.finish()?;
+        }
+        if let Some(n) = self.rate.as_mut().and_then(RateLimit::take_suppressed) {
+            write_suppressed(&self.renderer, n, &mut self.out)?;
+        }
+        if let Some(dedup) = &mut self.dedup {
+            for summary in dedup.finish() {
+                self.renderer.write_notice(&summary, &mut self.out)?;
+            }
+        }
+        self.write_reports()?;
+        self.renderer.write_footer(&mut self.out)?;
+        self.out.flush()?;
+        if let Some(stats) = &self.stats {
+            stats::lock(stats).write(&mut io::stderr())?;
+        }
+        if let Some(tally) = &self.tally {
+            tally.write_note(&mut io::stderr())?;
+        }
+        Ok(())
+    }
+}
+
+impl Renderer {
+    /// Whatever precedes the first record, e.g. the header row of the tabular formats.
+    pub fn write_header(&self, out: &mut dyn Write) -> io::Result<()> {
+        output::write_header(out, self.ctx.output, &self.fields)
+    }
+
+    /// Whatever follows the last record, e.g. the closing markup of the HTML output.
+    pub fn write_footer(&self, out: &mut dyn Write) -> io::Result<()> {
+        output::write_footer(out, self.ctx.output)
+    }
+
+    /// A dimmed line of jlo's own, e.g. a `--dedup` summary; left out of machine-readable output.
+    pub fn write_notice(&self, text: &str, out: &mut dyn Write) -> io::Result<()> {
+        if self.ctx.output.is_machine_readable() {
+            return Ok(());
+        }
+        let pal = self.ctx.pal;
+        let line = format!("{}\n", pal.paint(pal.faint, text));
+        if self.ctx.output.is_html() {
+            return html::write_ansi(out, line.as_bytes());
+        }
+        out.write_all(line.as_bytes())
+    }
+
+    /// A highlighted `―― gap: 2m14s ――` separator for `--gap-warn`; left out of machine-readable
+    /// output.
+    pub fn write_gap(&self, gap: &str, out: &mut dyn Write) -> io::Result<()> {
+        if self.ctx.output.is_machine_readable() {
+            return Ok(());
+        }
+        let pal = self.ctx.pal;
+        let line = format!("{}\n", pal.paint(pal.warn, format!("―― gap: {gap} ――")));
+        if self.ctx.output.is_html() {
+            return html::write_ansi(out, line.as_bytes());
+        }
+        out.write_all(line.as_bytes())
+    }
+
+    /// A highlighted `--alert` banner with a bell; on stderr for machine-readable output.
+    pub fn write_alert(&self, text: &str, out: &mut dyn Write) -> io::Result<()> {
+        if self.ctx.output.is_machine_readable() {
+            eprintln!("\x07jlo: ALERT {text}");
+            return Ok(());
+        }
+        let pal = self.ctx.pal;
+        let banner = format!("{}{} ALERT {text} {}\n", pal.error_bg, pal.error, pal.reset);
+        if self.ctx.output.is_html() {
+            return html::write_ansi(out, banner.as_bytes());
+        }
+        out.write_all(b"\x07")?;
+        out.write_all(banner.as_bytes())
+    }
+
+    /// A `--group-by-trace` block: the summary, then the records indented under it.
+    pub fn write_trace(&self, group: &Group, out: &mut dyn Write) -> io::Result<()> {
+        if self.ctx.output.is_machine_readable() {
+            for held in &group.records {
+                let line = Line::new(&held.buf, Some(&held.v), held.detection);
+                self.render(&line, held.note.as_deref(), out)?;
+            }
+            return Ok(());
+        }
+        let pal = self.ctx.pal;
+        let mut block = format!("{}\n", pal.paint(pal.info, &group.header)).into_bytes();
+        for held in &group.records {
+            let mut rendered = Vec::new();
+            let line = Line::new(&held.buf, Some(&held.v), held.detection);
+            self.render_ansi(&line, held.note.as_deref(), &mut rendered)?;
+            for line in rendered.split_inclusive(|&b| b == b'\n') {
+                block.extend_from_slice(b"  ");
+                block.extend_from_slice(line);
+            }
+        }
+        if self.ctx.output.is_html() {
+            return html::write_ansi(out, &block);
+        }
+        out.write_all(&block)
+    }
+
+    /// Write a `--report`, converted to HTML for the HTML output.
+    pub fn write_report(&self, report: &dyn Report, out: &mut dyn Write) -> io::Result<()> {
+        if !self.ctx.output.is_html() {
+            return report.write(&self.ctx, out);
+        }
+        let mut ansi = Vec::new();
+        report.write(&self.ctx, &mut ansi)?;
+        html::write_ansi(out, &ansi)
+    }
+
+    /// Write one line, as JSON if it parsed as such.
+    ///
+    /// A `note` from the source (e.g. Kafka partition/offset) is printed dimmed before the record.
+    pub fn render(
+        &self,
+        line: &Line<'_>,
+        note: Option<&str>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        if self.ctx.output.is_html() {
+            let mut ansi = Vec::new();
+            self.render_ansi(line, note, &mut ansi)?;
+            return html::write_ansi(out, &ansi);
+        }
+        self.render_ansi(line, note, out)
+    }
+
+    /// [`Renderer::render`] before the conversion to HTML, with the `--highlight` patterns.
+    fn render_ansi(
+        &self,
+        line: &Line<'_>,
+        note: Option<&str>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        if !self.highlights.is_empty()
+            && self.ctx.pal.enabled
+            && !self.ctx.output.is_machine_readable()
+        {
+            let mut rendered = Vec::new();
+            self.render_line(line, note, &mut rendered)?;
+            let mut highlighted = Vec::with_capacity(rendered.len() + 32);
+            self.highlights
+                .apply(&rendered, self.ctx.pal.reset, &mut highlighted);
+            return out.write_all(&highlighted);
+        }
+        self.render_line(line, note, out)
+    }
+
+    fn render_line(
+        &self,
+        line: &Line<'_>,
+        note: Option<&str>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let pal = self.ctx.pal;
+        if self.highlight_errors
+            && !pal.error_bg.is_empty()
+            && !self.ctx.output.is_machine_readable()
+            && line.level() >= Some(Level::Error)
+        {
+            let mut record = Vec::new();
+            self.render_record(line, note, &mut record)?;
+            for row in record.split_inclusive(|&b| b == b'\n') {
+                let row = row.strip_suffix(b"\n").unwrap_or(row);
+                out.write_all(pal.error_bg.as_bytes())?;
+                // Resets inside the line would end the background early
+                let mut rest = row;
+                while let Some(i) = find(rest, pal.reset.as_bytes()) {
+                    let end = i + pal.reset.len();
+                    out.write_all(&rest[..end])?;
+                    out.write_all(pal.error_bg.as_bytes())?;
+                    rest = &rest[end..];
+                }
+                out.write_all(rest)?;
+                // Erase to the end of the line, filling it with the background
+                writeln!(out, "\x1b[K{}", pal.reset)?;
+            }
+            return Ok(());
+        }
+        self.render_record(line, note, out)
+    }
+
+    /// The record, followed by `--select` values and `--delta` if enabled.
+    fn render_record(
+        &self,
+        line: &Line<'_>,
+        note: Option<&str>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let mut extra = Tail::default();
+        if let Some(v) = line.value()
+            && self.template.is_none()
+            && !self.ctx.output.is_machine_readable()
+        {
+            for sel in &self.selectors {
+                if let Some(found) = sel.query(v) {
+                    write_kv_json(&mut extra, &self.ctx, sel.name(), &found)?;
+                }
+            }
+            if let Some(prev) = &self.delta
+                && let Some(delta) = delta(prev, line)
+            {
+                let pal = self.ctx.pal;
+                write!(extra.push_dimmed("Δ"), "{}", pal.paint(pal.faint, delta))?;
+            }
+        }
+        if extra.is_empty() {
+            return self.render_text(line, note, out);
+        }
+
+        // Appended before the record's final line break
+        let mut rendered = Vec::new();
+        self.render_text(line, note, &mut rendered)?;
+        if rendered.last() == Some(&b'\n') {
+            rendered.pop();
+        }
+        out.write_all(&rendered)?;
+        out.write_all(b" ")?;
+        extra.write_inline(out, &self.ctx)
+    }
+
+    fn render_text(
+        &self,
+        line: &Line<'_>,
+        note: Option<&str>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let ctx = &self.ctx;
+        let buf = line.buf;
+        if let Some(note) = note
+            && !ctx.output.is_machine_readable()
+        {
+            write!(out, "{}{}{} ", ctx.pal.faint, note, ctx.pal.reset)?;
+        }
+        match (line.value(), line.protocol()) {
+            (Some(v), Some(proto))
+                if self.template.is_none()
+                    && !self.project
+                    && !ctx.output.is_machine_readable() =>
+            {
+                if !proto.render(v, self.style(proto), out)? {
+                    Generic.render(v, self.style(&Generic), out)?;
+                }
+            }
+            (Some(v), Some(_)) => {
+                let rec = line
+                    .record()
+                    .unwrap_or_else(|| unreachable!("JSON lines have a record"));
+                if let Some(template) = &self.template {
+                    template.render(rec, Some(v), ctx, out)?;
+                } else if self.project {
+                    let rec = rec.project(Some(v), &self.fields);
+                    if ctx.output.is_machine_readable() {
+                        output::write_record(out, ctx.output, &rec, &self.fields)?;
+                    } else {
+                        write_pretty(&rec, ctx, out)?;
+                    }
+                } else {
+                    output::write_record(out, ctx.output, rec, &self.fields)?;
+                }
+            }
+            _ if ctx.output.is_machine_readable() => {
+                // Keep the output parseable: wrap the raw line as the message
+                let rec = Record {
+                    message: Some(String::from_utf8_lossy(buf).into_owned()),
+                    ..Default::default()
+                };
+                output::write_record(out, ctx.output, &rec, &self.fields)?;
+            }
+            _ => {
+                // Not valid JSON: print the original line as-is
+                let pal = ctx.pal;
+                match self.raw_style {
+                    RawStyle::Plain => out.write_all(buf)?,
+                    RawStyle::Dim => {
+                        out.write_all(pal.faint.as_bytes())?;
+                        out.write_all(buf)?;
+                        out.write_all(pal.reset.as_bytes())?;
+                    }
+                    RawStyle::Prefix => {
+                        write!(out, "{} ", pal.paint(pal.faint, "·"))?;
+                        out.write_all(buf)?;
+                    }
+                }
+                out.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Render settings for `proto`, including its overrides from the configuration file.
+    fn style(&self, proto: &dyn JsonProtocol) -> &RenderCtx {
+        self.styles
+            .iter()
+            .find(|(name, _)| *name == proto.name())
+            .map_or(&self.ctx, |(_, ctx)| ctx)
+    }
+
+    /// The same settings without ANSI colors; HTML keeps them, as they become CSS.
+    fn plain(&self) -> Self {
+        if self.ctx.output.is_html() {
+            return self.clone();
+        }
+        let plain = Palette::new(false, Theme::Ansi);
+        let strip = |ctx: &RenderCtx| RenderCtx {
+            pal: plain,
+            ..ctx.clone()
+        };
+        Self {
+            ctx: strip(&self.ctx),
+            fields: self.fields.clone(),
+            template: self.template.clone(),
+            project: self.project,
+            highlight_errors: self.highlight_errors,
+            styles: self
+                .styles
+                .iter()
+                .map(|(name, ctx)| (*name, strip(ctx)))
+                .collect(),
+            delta: self.delta.clone(),
+            selectors: self.selectors.clone(),
+            highlights: self.highlights.clone(),
+            raw_style: self.raw_style,
+        }
+    }
+}
+
+/// A normalized record in the pretty layout: `[ts] LEVEL message key=value…`.
+fn write_pretty(rec: &Record, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+    let mut indent = 0;
+    if let Some(ts) = &rec.timestamp {
+        let ts = ctx.time.show(ts);
+        write!(out, "[{}] ", ctx.pal.paint(ctx.pal.timestamp, &ts))?;
+        indent += 2 + ts.len() + 1;
+    }
+    if let Some(level) = rec.level {
+        indent += write_level(
+            &mut *out,
+            ctx,
+            ctx.pal.for_level(Some(level)),
+            level.as_str(),
+        )?;
+    }
+
+    let mut tail = Tail::default();
+    write_kv_str(&mut tail, ctx, "logger", rec.logger.as_deref())?;
+    for (k, v) in &rec.fields {
+        write_kv_json(&mut tail, ctx, k, v)?;
+    }
+    match &rec.message {
+        Some(message) => {
+            write!(out, "{}", ctx.pal.paint(ctx.pal.message, message))?;
+            tail.write(out, ctx, indent)
+        }
+        None => tail.write_inline(out, ctx),
+    }
+}
+
+/// Call `f` with each record of an input line and its note: the line itself, each of several
+/// documents on it, or the JSON after a text prefix, which joins the note. Invalid UTF-8 is
+/// replaced; with `relaxed`, JSON5 is read as well.
+fn for_each_record(
+    buf: &[u8],
+    note: Option<&str>,
+    relaxed: bool,
+    mut f: impl FnMut(&[u8], Json<'_>, Option<&str>) -> io::Result<()>,
+) -> io::Result<()> {
+    // Left by Windows tools at the start of a file, which may be in the middle of the input
+    let buf = buf.strip_prefix("\u{feff}".as_bytes()).unwrap_or(buf);
+    // A stray invalid byte shouldn't hide the record or garble the terminal: it becomes U+FFFD
+    let text = String::from_utf8_lossy(buf);
+    let buf = text.as_bytes();
+    // Most lines: one object, of which the fields are all that may be needed
+    if let Some(fields) = Fields::split(buf) {
+        return f(buf, Json::Fields(fields), note);
+    }
+    let v = json::parse(buf, relaxed);
+    if v.is_none()
+        && let Some(docs) = split_documents(buf)
+    {
+        for (doc, v) in docs {
+            f(doc, Json::Value(Some(v)), note)?;
+        }
+        return Ok(());
+    }
+    if v.is_none()
+        && let Some((prefix, json, v)) = strip_prefix(buf, relaxed)
+    {
+        // Shown dimmed in front of the record, like a note from the source
+        let note = match note {
+            Some(note) => format!("{note} {prefix}"),
+            None => prefix,
+        };
+        return f(json, Json::Value(Some(v)), Some(&note));
+    }
+    f(buf, Json::Value(v), note)
+}
+
+/// The JSON of a record as [`for_each_record`] found it.
+enum Json<'a> {
+    /// An object, split into its top-level fields.
+    Fields(Fields<'a>),
+    /// Anything else, parsed; `None` if it isn't JSON.
+    Value(Option<Value>),
+}
+
+impl Json<'_> {
+    /// Call `f` with the record of `buf` as a [`Line`].
+    fn line<R>(self, buf: &[u8], detection: Detection, f: impl FnOnce(&Line<'_>) -> R) -> R {
+        match self {
+            Self::Fields(fields) => f(&Line::split(buf, fields, detection)),
+            Self::Value(v) => f(&Line::new(buf, v.as_ref(), detection)),
+        }
+    }
+}
+
+/// Call `f` with what a line that passed filtering turns into: itself, with the `drop_keys`
+/// pruned, or each output of `jq`.
+fn for_each_output(
+    line: &Line<'_>,
+    drop_keys: Option<&KeyFilter>,
+    jq: Option<&Jq>,
+    mut f: impl FnMut(&Line<'_>) -> io::Result<()>,
+) -> io::Result<()> {
+    if drop_keys.is_none() && jq.is_none() {
+        return f(line);
+    }
+    let Some(v) = line.value() else {
+        return f(line);
+    };
+    let mut v = v.clone();
+    let pruned = match drop_keys {
+        Some(keys) => {
+            keys.apply(&mut v);
+            Some(serde_json::to_vec(&v)?)
+        }
+        None => None,
+    };
+    let Some(jq) = jq else {
+        let buf = pruned.as_deref().unwrap_or(line.buf);
+        return f(&line.other(buf, Some(&v)));
+    };
+    for out in jq.run(v) {
+        match out {
+            Ok(v) => f(&line.other(&serde_json::to_vec(&v)?, Some(&v)))?,
+            Err(e) => eprintln!("jlo: jq: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// The documents of a line holding several JSON objects back to back (`{"a":1}{"b":2}`), with
+/// their bytes; `None` unless the whole line parses into more than one.
+fn split_documents(buf: &[u8]) -> Option<Vec<(&[u8], Value)>> {
+    let mut stream = serde_json::Deserializer::from_slice(buf).into_iter::<Value>();
+    let mut docs = Vec::new();
+    let mut start = 0;
+    while let Some(v) = stream.next() {
+        let v = v.ok().filter(Value::is_object)?;
+        let end = stream.byte_offset();
+        docs.push((buf[start..end].trim_ascii(), v));
+        start = end;
+    }
+    (docs.len() > 1).then_some(docs)
+}
+
+/// Where to look for JSON after a text prefix, at most.
+const PREFIX_ATTEMPTS: usize = 8;
+
+/// A line with a text prefix before its JSON, such as `2024-05-03T10:11:12Z stdout F {…}`
+/// (kubectl/CRI) or `app[web.1]: {…}`: the prefix, the JSON's bytes, and its value. The JSON
+/// has to be an object, or an array of objects, running to the end of the line.
+fn strip_prefix(buf: &[u8], relaxed: bool) -> Option<(String, &[u8], Value)> {
+    buf.iter()
+        .enumerate()
+        .filter(|&(i, &b)| i > 0 && (b == b'{' || b == b'['))
+        .take(PREFIX_ATTEMPTS)
+        .find_map(|(i, _)| {
+            let v = json::parse(&buf[i..], relaxed)?;
+            let record = match &v {
+                Value::Object(_) => true,
+                Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+                _ => false,
+            };
+            let prefix = String::from_utf8_lossy(buf[..i].trim_ascii()).into_owned();
+            (record && !prefix.is_empty()).then_some((prefix, &buf[i..], v))
+        })
+}
+
+fn write_suppressed(renderer: &Renderer, n: u64, out: &mut dyn Write) -> io::Result<()> {
+    let lines = if n == 1 { "line" } else { "lines" };
+    renderer.write_notice(&format!("… suppressed {n} {lines}"), out)
+}
+
+/// Time since the previous timestamped line for `--delta`, e.g. `12ms`; `prev` is updated.
+fn delta(prev: &Cell<Option<Timestamp>>, line: &Line<'_>) -> Option<String> {
+    let ts = line.timestamp().and_then(time::parse_timestamp)?;
+    let last = prev.replace(Some(ts))?;
+    let millis = ts.as_millisecond() - last.as_millisecond();
+    let sign = if millis < 0 { "-" } else { "" };
+    Some(format!(
+        "{sign}{}",
+        time::humanize_duration(Duration::from_millis(millis.unsigned_abs()))
+    ))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Output that outlives the pipeline writing it.
+    #[derive(Clone, Default)]
+    pub(super) struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Output {
+        pub(super) fn text(&self) -> String {
+            String::from_utf8(self.0.take()).unwrap()
+        }
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A pipeline with compact output and no colors, set up by `options`.
+    pub(super) fn build(options: impl FnOnce(Pipeline) -> Pipeline) -> (Pipeline, Output) {
+        let out = Output::default();
+        let ctx = crate::tests::ctx(&["--compact"]);
+        (options(Pipeline::new(ctx, Box::new(out.clone()))), out)
+    }
+
+    /// What `input` turns into through the pipeline set up by `options`.
+    pub(super) fn run(input: &str, options: impl FnOnce(Pipeline) -> Pipeline) -> String {
+        let (mut pipeline, out) = build(options);
+        pipeline.process_reader(input.as_bytes()).unwrap();
+        pipeline.finish().unwrap();
+        out.text()
+    }
+
+    const REPEATS: &str = r#"{"msg":"a"}
+{"msg":"a"}
+{"msg":"a"}
+{"msg":"b"}
+{"msg":"a"}
+x
+x
+"#;
+
+    #[test]
+    fn dedup_collapses_runs() {
+        assert_eq!(
+            run(REPEATS, |p| p.dedup(Some(1))),
+            "{\"msg\":\"a\"}\n(repeated 2×)\n{\"msg\":\"b\"}\n{\"msg\":\"a\"}\nx\n(repeated 1×)\n"
+        );
+        assert_eq!(run(REPEATS, |p| p.dedup(None)), REPEATS);
+    }
+
+    #[test]
+    fn dedup_window() {
+        // Counted among the last two distinct messages, and reported as they leave them
+        assert_eq!(
+            run(REPEATS, |p| p.dedup(Some(2))),
+            "{\"msg\":\"a\"}\n{\"msg\":\"b\"}\n(repeated 3×): a\nx\n(repeated 1×): x\n"
+        );
+    }
+
+    /// Keep the lines matching `pattern`, like `--grep`.
+    fn grep(pattern: &str) -> Filter {
+        Filter {
+            grep: vec![regex::Regex::new(pattern).unwrap()],
+            ..Filter::default()
+        }
+    }
+
+    #[test]
+    fn context_lines() {
+        let input = "1\n2\n3\n4\nERR\n5\n6\n7\n8\n9\nERR\n10\nERR\n";
+        assert_eq!(
+            run(input, |p| p.filter(grep("ERR")).context(1, 1)),
+            "4\nERR\n5\n--\n9\nERR\n10\nERR\n"
+        );
+        // Windows that touch aren't separated
+        assert_eq!(
+            run(input, |p| p.filter(grep("ERR")).context(2, 3)),
+            "3\n4\nERR\n5\n6\n7\n8\n9\nERR\n10\nERR\n"
+        );
+        assert_eq!(
+            run(input, |p| p.filter(grep("ERR")).context(0, 1)),
+            "ERR\n5\n--\nERR\n10\nERR\n"
+        );
+    }
+
+    #[test]
+    fn head_and_tail() {
+        let input = "1\n\n2\n3\r\n4\n5";
+        assert_eq!(run(input, |p| p.head(Some(2))), "1\n2\n");
+        assert_eq!(run(input, |p| p.tail(Some(2))), "4\n5\n");
+        assert_eq!(run(input, |p| p.tail(Some(9))), "1\n2\n3\n4\n5\n");
+    }
+
+    #[test]
+    fn fail_on_counts_what_passes() {
+        let input = r#"{"level":"info","msg":"a"}
+{"level":"error","msg":"b"}
+{"level":"fatal","msg":"c"}
+{"level":"error","msg":"dropped"}
+plain error
+"#;
+        let (mut pipeline, out) = build(|p| {
+            p.fail_on(Some(Level::Error)).filter(Filter {
+                exclude: vec![regex::Regex::new("dropped").unwrap()],
+                ..Filter::default()
+            })
+        });
+        pipeline.process_reader(input.as_bytes()).unwrap();
+        pipeline.finish().unwrap();
+        assert_eq!(pipeline.failed(), Some((Level::Error, 2)));
+        assert_eq!(out.text().lines().count(), 4);
+
+        let (mut pipeline, _) = build(|p| p.fail_on(Some(Level::Fatal)));
+        pipeline
+            .process_reader(&b"{\"level\":\"error\"}\n"[..])
+            .unwrap();
+        assert_eq!(pipeline.failed(), None);
+    }
+
+    #[test]
+    fn reports_replace_the_records_and_are_written_at_the_end() {
+        let input = r#"{"level":"info","msg":"a"}
+{"level":"error","msg":"b"}
+{"level":"info","msg":"c"}
+{"msg":"d"}
+plain
+"#;
+        let count = |by: Option<&str>| -> Vec<Box<dyn Report>> {
+            vec![Box::new(crate::report::count::Count::new(
+                by.map(String::from),
+            ))]
+        };
+        assert_eq!(run(input, |p| p.reports(count(None))), "5\n");
+        assert_eq!(
+            run(input, |p| p.reports(count(None)).filter(grep("^[ab]$"))),
+            "2\n"
+        );
+        assert_eq!(
+            run(input, |p| p.reports(count(Some("level")))),
+            "       2 info\n       1 error\n       2 (none)\n"
+        );
+    }
+}
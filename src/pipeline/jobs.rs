@@ -0,0 +1,286 @@
+//! `--jobs`: the lines of a regular file parsed, filtered, and rendered in batches on worker
+//! threads, and written in their input order.
+
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use super::{Pipeline, Renderer, for_each_output, for_each_record};
+use crate::encoding::{self, Encoding};
+use crate::filter::Filter;
+use crate::jq::Jq;
+use crate::protocols::{Detection, Level};
+use crate::stats::Tally;
+use crate::tail::KeyFilter;
+
+/// Input handed to a worker at once, in bytes.
+const BATCH_BYTES: usize = 256 * 1024;
+
+/// Batches in flight per worker, at most.
+const QUEUED: usize = 2;
+
+/// Everything a worker needs to turn lines into output on its own.
+#[derive(Clone)]
+struct Worker {
+    detection: Detection,
+    relaxed: bool,
+    filter: Filter,
+    renderer: Renderer,
+    drop_keys: Option<KeyFilter>,
+    jq: Option<Jq>,
+    fail_on: Option<Level>,
+    /// Whether to count how lines were read, for the note at the end.
+    tally: bool,
+}
+
+/// A worker's output for a batch.
+struct Rendered {
+    out: Vec<u8>,
+    /// Records that reached the `--fail-on` level.
+    failing: u64,
+    tally: Tally,
+}
+
+impl Worker {
+    /// The output for a batch of lines, each ending in `\n`.
+    fn render(&self, batch: &[u8]) -> io::Result<Rendered> {
+        let mut out = Vec::with_capacity(batch.len() * 2);
+        let mut failing = 0;
+        let mut tally = Tally::default();
+        for line in batch.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+            if self.tally {
+                tally.line();
+            }
+            for_each_record(line, None, self.relaxed, |buf, json, note| {
+                json.line(buf, self.detection, |line| {
+                    if self.tally {
+                        tally.count(line);
+                    }
+                    if !self.filter.keep(line) {
+                        return Ok(());
+                    }
+                    for_each_output(line, self.drop_keys.as_ref(), self.jq.as_ref(), |line| {
+                        if let Some(min) = self.fail_on
+                            && line.level().is_some_and(|level| level >= min)
+                        {
+                            failing += 1;
+                        }
+                        self.renderer.render(line, note, &mut out)
+                    })
+                })
+            })?;
+        }
+        Ok(Rendered {
+            out,
+            failing,
+            tally,
+        })
+    }
+}
+
+impl Pipeline {
+    /// Process every line of a regular file like [`Pipeline::process_reader`], in batches on the
+    /// `--jobs` threads unless the options in use need the lines one after the other.
+    ///
+    /// `encoding` is the file's if it was already sniffed, as before seeking into it.
+    pub fn process_file<R: BufRead>(
+        &mut self,
+        reader: R,
+        encoding: Option<Encoding>,
+    ) -> io::Result<()> {
+        self.start_stream();
+        let mut reader = match encoding {
+            Some(encoding) => encoding::decode_as(reader, encoding),
+            None => encoding::decode(reader)?,
+        };
+        if self.jobs < 2 || !self.independent_lines() {
+            return self.read_lines(reader);
+        }
+        self.start()?;
+        let worker = Worker {
+            detection: self.detection,
+            relaxed: self.relaxed,
+            filter: self.filter.clone(),
+            renderer: self.renderer.clone(),
+            drop_keys: self.drop_keys.clone(),
+            jq: self.jq.clone(),
+            fail_on: self.fail_on.map(|(level, _)| level),
+            tally: self.tally.is_some(),
+        };
+        let jobs = self.jobs;
+        thread::scope(|scope| {
+            let lanes: Vec<_> = (0..jobs)
+                .map(|_| {
+                    let (batches, todo) = mpsc::sync_channel::<Vec<u8>>(QUEUED);
+                    let (results, done) = mpsc::sync_channel(QUEUED);
+                    let worker = worker.clone();
+                    scope.spawn(move || {
+                        for batch in todo {
+                            if results.send(worker.render(&batch)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                    (batches, done)
+                })
+                .collect();
+            // Batch `n` goes to lane `n % jobs`, so taking the results from the lanes in turn
+            // restores the input order; with at most `QUEUED` batches per lane, no send blocks
+            let (mut sent, mut written) = (0, 0);
+            let mut lines = 0;
+            loop {
+                let batch = self.read_batch(&mut reader, &mut lines)?;
+                if batch.is_empty() {
+                    break;
+                }
+                if sent - written == jobs * QUEUED {
+                    self.write_batch(&lanes[written % jobs].1)?;
+                    written += 1;
+                }
+                lanes[sent % jobs]
+                    .0
+                    .send(batch)
+                    .map_err(|_| worker_stopped())?;
+                sent += 1;
+            }
+            while written < sent {
+                self.write_batch(&lanes[written % jobs].1)?;
+                written += 1;
+            }
+            Ok(())
+        })
+    }
+
+    /// Whether each line can be handled on its own, with nothing carried over to the next one
+    /// besides the output.
+    fn independent_lines(&self) -> bool {
+        #[cfg(feature = "sqlite")]
+        if self.db.is_some() {
+            return false;
+        }
+        self.tail.is_none()
+            && self.sticky.is_none()
+            && self.invalid.is_none()
+            && self.context.is_none()
+            && self.dedup.is_none()
+            && self.rate.is_none()
+            && self.split.is_none()
+            && self.gap_warn.is_none()
+            && self.traces.is_none()
+            && self.reports.is_empty()
+            && self.alerts.is_empty()
+            && self.footer.is_none()
+            && self.stats.is_none()
+            && self.filter.sample.is_none()
+            && self.renderer.delta.is_none()
+            // The first timestamp sets the `--timestamp=relative` anchor, unless it was set up
+            // front; on the workers that would be whichever batch got there first
+            && self
+                .renderer
+                .ctx
+                .time
+                .relative
+                .as_ref()
+                .is_none_or(|anchor| anchor.get().is_some())
+    }
+
+    /// The next lines of `reader`, each ending in `\n`, up to about [`BATCH_BYTES`] and the
+    /// `--head` limit; empty at the end.
+    fn read_batch(&mut self, reader: &mut impl BufRead, lines: &mut usize) -> io::Result<Vec<u8>> {
+        let mut batch = Vec::with_capacity(BATCH_BYTES + 8 * 1024);
+        while batch.len() < BATCH_BYTES && self.head.is_none_or(|n| *lines < n) {
+            let start = batch.len();
+            if reader.read_until(b'\n', &mut batch)? == 0 {
+                break;
+            }
+            while batch.len() > start && matches!(batch.last(), Some(b'\n' | b'\r')) {
+                batch.pop();
+            }
+            if batch.len() == start {
+                continue;
+            }
+            if let Some(tee) = &mut self.tee {
+                tee.write_all(&batch[start..])?;
+                tee.write_all(b"\n")?;
+            }
+            batch.push(b'\n');
+            *lines += 1;
+        }
+        Ok(batch)
+    }
+
+    fn write_batch(&mut self, done: &Receiver<io::Result<Rendered>>) -> io::Result<()> {
+        let rendered = done.recv().map_err(|_| worker_stopped())??;
+        if let Some((_, n)) = &mut self.fail_on {
+            *n += rendered.failing;
+        }
+        if let Some(tally) = &mut self.tally {
+            tally.merge(&rendered.tally);
+        }
+        self.out.write_all(&rendered.out)
+    }
+}
+
+fn worker_stopped() -> io::Error {
+    io::Error::other("worker thread stopped")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::tests::Output;
+
+    /// `input` as a regular file through `jlo <args> --jobs <jobs>`, and how many records
+    /// reached `--fail-on error`.
+    fn run(args: &[&str], jobs: usize, input: &[u8]) -> (String, u64) {
+        let out = Output::default();
+        let mut pipeline = Pipeline::new(crate::tests::ctx(args), Box::new(out.clone()))
+            .fail_on(Some(Level::Error))
+            .jobs(jobs);
+        pipeline.process_file(input, None).unwrap();
+        pipeline.finish().unwrap();
+        let failed = pipeline.failed().map_or(0, |(_, n)| n);
+        (out.text(), failed)
+    }
+
+    /// Enough lines for several batches per worker.
+    fn lines() -> Vec<u8> {
+        let levels = ["INFO", "ERROR", "WARN"];
+        (0..15_000)
+            .map(|i| {
+                let line = format!(
+                    "{{\"timestamp\":\"2024-05-03T10:{:02}:{:02}Z\",\"level\":\"{}\",\"target\":\"app\",\
+                     \"fields\":{{\"message\":\"line {i}\"}}}}\n",
+                    i / 60 % 60,
+                    i % 60,
+                    levels[i % levels.len()],
+                );
+                if i % 1000 == 999 { format!("not json {i}\n") } else { line }
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn output_keeps_the_input_order() {
+        let input = lines();
+        assert!(input.len() > 2 * QUEUED * BATCH_BYTES);
+        let (sequential, failed) = run(&["--compact"], 1, &input);
+        assert_eq!(sequential.lines().count(), 15_000);
+        assert_eq!(failed, 4_995);
+        assert_eq!(run(&["--compact"], 2, &input), (sequential, failed));
+    }
+
+    #[test]
+    fn relative_timestamps_keep_the_lines_in_order() {
+        let pipeline = |args| Pipeline::new(crate::tests::ctx(args), Box::new(io::sink()));
+        assert!(pipeline(&[]).independent_lines());
+        // The first line sets the anchor, not the first worker to get to a timestamp
+        let relative = pipeline(&["--timestamp=relative"]);
+        assert!(!relative.independent_lines());
+        // Set up front, as for live sources
+        let anchor = relative.renderer.ctx.time.relative.as_ref().unwrap();
+        anchor.set(jiff::Timestamp::UNIX_EPOCH).unwrap();
+        assert!(relative.independent_lines());
+    }
+}
@@ -1,17 +1,541 @@
+pub mod azure;
+pub mod gcp;
+pub mod generic;
 pub mod nginx;
 pub mod tracing;
 
 use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
 use std::io::{self, Write};
+use std::time::Duration;
 
-use crate::RenderCtx;
+use crate::json::Fields;
+use crate::{RenderCtx, time};
+
+pub trait JsonProtocol: Sync {
+    /// Short lowercase identifier, used to address the protocol in the configuration file.
+    fn name(&self) -> &'static str;
 
-pub trait JsonProtocol {
     /// Return a confidence score in [0.0, 1.0] indicating how likely this
-    /// protocol can render the given JSON value.
-    fn sniff(&self, v: &Value) -> f32;
+    /// protocol can render the JSON object with these fields.
+    fn sniff(&self, o: &Fields<'_>) -> f32;
 
     /// Attempt to render the given JSON value. Returns Ok(true) if rendered,
     /// Ok(false) if not applicable.
-    fn render(&self, v: &Value, ctx: RenderCtx, out: &mut dyn Write) -> io::Result<bool>;
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool>;
+
+    /// Extract the protocol-independent view of the record used by the
+    /// machine-readable output modes. Returns `None` if not applicable.
+    fn normalize(&self, v: &Value) -> Option<Record>;
+
+    /// The timestamp and level [`JsonProtocol::normalize`] would extract, read from the
+    /// object's fields without parsing the rest of the line: what the level and time filters
+    /// need of every line. `None` where `normalize` would be.
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>>;
+}
+
+impl fmt::Debug for dyn JsonProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// All protocols with a dedicated renderer, in sniffing order.
+pub const ALL: [&dyn JsonProtocol; 4] =
+    [&nginx::Nginx, &tracing::Tracing, &gcp::Gcp, &azure::Azure];
+
+/// Look up a protocol by its [`JsonProtocol::name`], including the generic fallback.
+pub fn by_name(name: &str) -> Option<&'static dyn JsonProtocol> {
+    if name == generic::Generic.name() {
+        return Some(&generic::Generic);
+    }
+    ALL.into_iter().find(|p| p.name() == name)
+}
+
+/// The sniff score a protocol needs by default: a single telltale field (a tracing `level`, an
+/// nginx `status`) isn't enough.
+pub const DEFAULT_THRESHOLD: f32 = 0.5;
+
+/// How records are matched to protocols: the options of the run, and the protocol [`Sticky`]
+/// locked in for the current input stream.
+#[derive(Clone, Copy, Debug)]
+pub struct Detection {
+    /// The sniff score a protocol needs (`--sniff-threshold`).
+    pub threshold: f32,
+    /// The protocols of [`ALL`] to choose from (`--protocol`), by their place there.
+    pub only: [bool; ALL.len()],
+    /// Used for every record without sniffing (`--protocol` with a single one).
+    pub forced: Option<&'static dyn JsonProtocol>,
+    /// Used for every record without sniffing, once decided (`--sticky`).
+    pub locked: Option<&'static dyn JsonProtocol>,
+}
+
+impl Default for Detection {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD, &[])
+    }
+}
+
+impl Detection {
+    /// Choose among `protocols` (`--protocol`): a single one is used without sniffing, none
+    /// means all of them.
+    pub fn new(threshold: f32, protocols: &[&'static dyn JsonProtocol]) -> Self {
+        let mut only = [protocols.is_empty(); ALL.len()];
+        for p in protocols {
+            if let Some(i) = ALL.iter().position(|a| a.name() == p.name()) {
+                only[i] = true;
+            }
+        }
+        Self {
+            threshold,
+            only,
+            forced: match protocols {
+                [forced] => Some(*forced),
+                _ => None,
+            },
+            locked: None,
+        }
+    }
+
+    /// Pick the protocol with the highest sniff score, the first one in [`ALL`] on a tie, as long
+    /// as it reaches the threshold; [`generic::Generic`] otherwise.
+    pub fn detect(&self, o: &Fields<'_>) -> &'static dyn JsonProtocol {
+        if let Some(p) = self.forced.or(self.locked) {
+            return p;
+        }
+        let mut best: Option<(&dyn JsonProtocol, f32)> = None;
+        for (p, _) in ALL.into_iter().zip(self.only).filter(|&(_, only)| only) {
+            let s = p.sniff(o);
+            if s > 0.0 && s >= self.threshold && best.is_none_or(|(_, bs)| s > bs) {
+                best = Some((p, s));
+            }
+        }
+        best.map_or(&generic::Generic, |(p, _)| p)
+    }
+}
+
+/// Decides the protocol of an input stream from its first records, to lock in for the rest as
+/// [`Detection::locked`] (`--sticky`), so odd records don't flip to another renderer and
+/// sniffing stops.
+pub struct Sticky {
+    /// Records sniffed as one of [`ALL`] before deciding.
+    lines: usize,
+    /// Records per protocol of [`ALL`] so far.
+    votes: [usize; ALL.len()],
+}
+
+impl Sticky {
+    pub fn new(lines: usize) -> Self {
+        Self {
+            lines: lines.max(1),
+            votes: [0; ALL.len()],
+        }
+    }
+
+    /// Start over for a new input stream.
+    pub fn reset(&mut self) {
+        self.votes = [0; ALL.len()];
+    }
+
+    /// Count the protocol of a record; the most frequent one so far, the first in [`ALL`] on a
+    /// tie, is returned to lock in once enough records were sniffed as any of them.
+    pub fn observe(&mut self, p: &dyn JsonProtocol) -> Option<&'static dyn JsonProtocol> {
+        let sniffed: usize = self.votes.iter().sum();
+        if sniffed >= self.lines {
+            return None;
+        }
+        let i = ALL.iter().position(|a| a.name() == p.name())?;
+        self.votes[i] += 1;
+        if sniffed + 1 < self.lines {
+            return None;
+        }
+        let best = (0..ALL.len())
+            .rev()
+            .max_by_key(|&i| self.votes[i])
+            .unwrap_or(0);
+        Some(ALL[best])
+    }
+}
+
+/// Parse a `--protocol` name.
+pub fn parse_protocol(name: &str) -> Result<&'static dyn JsonProtocol, String> {
+    by_name(name).ok_or_else(|| {
+        let names: Vec<_> = ALL.iter().map(|p| p.name()).collect();
+        format!(
+            "{name}: unknown protocol, expected one of {}, {}",
+            names.join(", "),
+            generic::Generic.name()
+        )
+    })
+}
+
+/// Parse a `--sniff-threshold` between 0 and 1.
+pub fn parse_threshold(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(t) if (0.0..=1.0).contains(&t) => Ok(t),
+        _ => Err(format!("{s}: expected a score between 0 and 1")),
+    }
+}
+
+/// The timestamp and level of a record, borrowed from its line where possible.
+#[derive(Debug, Default)]
+pub struct Head<'v> {
+    pub timestamp: Option<Cow<'v, str>>,
+    pub level: Option<Level>,
+}
+
+/// Protocol-independent view of a log record.
+#[derive(Debug, Default, Clone)]
+pub struct Record {
+    pub timestamp: Option<String>,
+    pub level: Option<Level>,
+    pub message: Option<String>,
+    pub logger: Option<String>,
+    /// Remaining fields in display order, using the names the pretty renderer shows.
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Record {
+    /// Append a field unless the value is missing, null, or an empty string.
+    pub fn push(&mut self, key: impl Into<String>, val: Option<Value>) {
+        match val {
+            None | Some(Value::Null) => {}
+            Some(Value::String(s)) if s.is_empty() => {}
+            Some(v) => self.fields.push((key.into(), v)),
+        }
+    }
+
+    /// Look up a field by name for column-oriented output.
+    ///
+    /// `ts`, `level`, `msg`, and `logger` address the canonical slots; anything else
+    /// is matched against the remaining fields, descending into objects on `.`.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match name {
+            "ts" | "timestamp" => return self.timestamp.clone().map(Value::String),
+            "level" => {
+                return self
+                    .level
+                    .map(|l| Value::String(l.as_str().to_ascii_lowercase()));
+            }
+            "msg" | "message" => return self.message.clone().map(Value::String),
+            "logger" => return self.logger.clone().map(Value::String),
+            _ => {}
+        }
+        if let Some((_, v)) = self.fields.iter().find(|(k, _)| k == name) {
+            return Some(v.clone());
+        }
+        let mut parts = name.split('.');
+        let head = parts.next()?;
+        let mut v = &self.fields.iter().find(|(k, _)| k == head)?.1;
+        for part in parts {
+            v = v.get(part)?;
+        }
+        Some(v.clone())
+    }
+
+    /// [`Record::get`], falling back to the original JSON for fields the protocol doesn't surface.
+    pub fn lookup(&self, raw: Option<&Value>, name: &str) -> Option<Value> {
+        self.get(name).or_else(|| {
+            let mut v = raw?;
+            for part in name.split('.') {
+                v = v.get(part)?;
+            }
+            (!v.is_null()).then(|| v.clone())
+        })
+    }
+
+    /// HTTP status code of request logs: nginx `status`, GCP `httpRequest.status`, or an
+    /// Application Insights `ResultCode`.
+    pub fn status(&self) -> Option<u64> {
+        ["status", "http.status", "ResultCode"]
+            .into_iter()
+            .find_map(|name| match self.get(name)? {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            })
+    }
+
+    /// HTTP method of request logs: nginx `method` or GCP `httpRequest.requestMethod`.
+    pub fn method(&self) -> Option<String> {
+        ["method", "http.requestMethod"]
+            .into_iter()
+            .find_map(|name| match self.get(name)? {
+                Value::String(s) => Some(s),
+                _ => None,
+            })
+    }
+
+    /// Longest of the fields named like a duration (`req_time`, `elapsed_ms`, `latencies.request`,
+    /// GCP `httpRequest.latency`, …; see [`time::duration_unit`]), numbers or strings like `0.5s`.
+    pub fn duration(&self) -> Option<Duration> {
+        fn walk(key: &str, v: &Value, longest: &mut Option<Duration>) {
+            let unit = || time::duration_unit(key);
+            let d = match v {
+                Value::Object(obj) => {
+                    for (k, child) in obj {
+                        walk(&format!("{key}.{k}"), child, longest);
+                    }
+                    return;
+                }
+                Value::Number(n) => {
+                    unit().and_then(|unit| Duration::try_from_secs_f64(n.as_f64()? * unit).ok())
+                }
+                Value::String(s) => unit().and_then(|unit| match s.trim().parse::<f64>() {
+                    Ok(n) => Duration::try_from_secs_f64(n * unit).ok(),
+                    Err(_) => time::parse_duration(s).ok(),
+                }),
+                _ => None,
+            };
+            *longest = (*longest).max(d);
+        }
+        let mut longest = None;
+        for (key, v) in &self.fields {
+            walk(key, v, &mut longest);
+        }
+        longest
+    }
+
+    /// Response size of request logs: nginx `bytes_sent` or GCP `httpRequest.responseSize`.
+    pub fn bytes(&self) -> Option<u64> {
+        ["bytes", "http.responseSize"]
+            .into_iter()
+            .find_map(|name| match self.get(name)? {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            })
+    }
+
+    /// User agent of request logs: nginx `user_agent` or GCP `httpRequest.userAgent`.
+    pub fn user_agent(&self) -> Option<String> {
+        ["ua", "http.userAgent"]
+            .into_iter()
+            .find_map(|name| match self.get(name)? {
+                Value::String(s) => Some(s),
+                _ => None,
+            })
+    }
+
+    /// Request path of access logs, without the query: nginx `path`, or the path part of
+    /// GCP `httpRequest.requestUrl` or an Application Insights `Url`.
+    pub fn path(&self) -> Option<String> {
+        if let Some(Value::String(path)) = self.get("path") {
+            return Some(path);
+        }
+        ["http.requestUrl", "Url"].into_iter().find_map(|name| {
+            let Value::String(url) = self.get(name)? else {
+                return None;
+            };
+            let rest = match url.split_once("://") {
+                Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+                None => &url,
+            };
+            Some(rest.split(['?', '#']).next().unwrap_or(rest).to_string())
+        })
+    }
+
+    /// Keep only the canonical slots and fields named in `names`, fields in that order.
+    pub fn project(&self, raw: Option<&Value>, names: &[String]) -> Record {
+        let named = |aliases: &[&str]| names.iter().any(|n| aliases.contains(&n.as_str()));
+        let mut rec = Record {
+            timestamp: self
+                .timestamp
+                .clone()
+                .filter(|_| named(&["ts", "timestamp"])),
+            level: self.level.filter(|_| named(&["level"])),
+            message: self.message.clone().filter(|_| named(&["msg", "message"])),
+            logger: self.logger.clone().filter(|_| named(&["logger"])),
+            fields: Vec::new(),
+        };
+        for name in names {
+            if !matches!(
+                name.as_str(),
+                "ts" | "timestamp" | "level" | "msg" | "message" | "logger"
+            ) {
+                rec.push(name.as_str(), self.lookup(raw, name));
+            }
+        }
+        rec
+    }
+}
+
+/// Normalized severity, ordered from least to most severe.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    /// Parse the level names and numeric levels used by common logging libraries.
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Ok(n) = s.parse::<u64>() {
+            return Self::from_number(n);
+        }
+        Some(match s.to_ascii_lowercase().as_str() {
+            "trace" | "verbose" => Self::Trace,
+            "debug" | "dbg" => Self::Debug,
+            "info" | "information" | "informational" | "notice" => Self::Info,
+            "warn" | "warning" => Self::Warn,
+            "error" | "err" => Self::Error,
+            "fatal" | "critical" | "crit" | "alert" | "emergency" | "emerg" | "panic" => {
+                Self::Fatal
+            }
+            _ => return None,
+        })
+    }
+
+    /// Level from a JSON string or number field.
+    pub fn from_value(v: &Value) -> Option<Self> {
+        match v {
+            Value::String(s) => Self::parse(s),
+            Value::Number(n) => n.as_u64().and_then(Self::from_number),
+            _ => None,
+        }
+    }
+
+    /// Bunyan/pino style numeric levels (10 = trace … 60 = fatal).
+    pub fn from_number(n: u64) -> Option<Self> {
+        Some(match n {
+            10 => Self::Trace,
+            20 => Self::Debug,
+            30 => Self::Info,
+            40 => Self::Warn,
+            50 => Self::Error,
+            60 => Self::Fatal,
+            _ => return None,
+        })
+    }
+
+    /// Level for an HTTP status code, matching the nginx renderer.
+    pub fn from_status(status: u64) -> Self {
+        match status {
+            400..=499 => Self::Warn,
+            500..=599 => Self::Error,
+            _ => Self::Info,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+            Self::Fatal => "FATAL",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// `v` as `protocol` shows it with `args` on the command line, without colors; `None` if
+    /// it leaves the record to the generic renderer.
+    fn render(protocol: &dyn JsonProtocol, args: &[&str], v: Value) -> Option<String> {
+        let mut out = Vec::new();
+        let shown = protocol
+            .render(&v, &crate::tests::ctx(args), &mut out)
+            .unwrap();
+        shown.then(|| String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn nginx() {
+        let v = json!({
+            "ts": "2024-05-03T10:00:00Z", "method": "POST", "path": "/api", "query": "a=1",
+            "host": "x.io", "protocol": "HTTP/1.1", "status": 503, "bytes_sent": 2048,
+            "req_time": 0.25, "req_id": "r1",
+        });
+        assert_eq!(
+            render(&nginx::Nginx, &["--timestamp", "--humanize"], v.clone()).unwrap(),
+            "[2024-05-03T10:00:00Z] ERROR 503 POST x.io /api?a=1 HTTP/1.1\n\
+             \x20                            bytes=2.0 KiB rt=250ms req=r1\n"
+        );
+        assert_eq!(
+            render(&nginx::Nginx, &["--compact"], v).unwrap(),
+            "ERROR 503 POST x.io /api?a=1 HTTP/1.1 bytes=2048 rt=0.25 req=r1\n"
+        );
+        assert_eq!(render(&nginx::Nginx, &[], json!({"method": "GET"})), None);
+    }
+
+    #[test]
+    fn tracing() {
+        let v = json!({
+            "timestamp": "2024-05-03T10:00:01Z", "level": "DEBUG", "target": "app::db",
+            "fields": {"message": "query", "rows": 3}, "span": {"name": "req"},
+        });
+        assert_eq!(
+            render(&tracing::Tracing, &["--details"], v).unwrap(),
+            "DEBUG query\n      logger: app::db\n      span:   req\n      rows:   3\n"
+        );
+    }
+
+    #[test]
+    fn gcp() {
+        let v = json!({
+            "timestamp": "2024-05-03T10:00:02Z", "logName": "projects/p/logs/lb",
+            "severity": "DEFAULT",
+            "httpRequest": {"requestMethod": "POST", "requestUrl": "https://x/api", "status": 502},
+        });
+        assert_eq!(
+            render(&gcp::Gcp, &["--compact"], v).unwrap(),
+            "DEFAULT POST 502 https://x/api log=lb http.requestMethod=POST \
+             http.requestUrl=https://x/api http.status=502\n"
+        );
+        let v = json!({
+            "timestamp": "2024-05-03T10:00:02Z", "logName": "projects/p/logs/app",
+            "severity": "WARNING", "jsonPayload": {"message": "slow", "ms": 900},
+        });
+        assert_eq!(
+            render(&gcp::Gcp, &[], v).unwrap(),
+            "WARN  slow\n      log=app ms=900\n"
+        );
+    }
+
+    #[test]
+    fn azure() {
+        let v = json!({
+            "TimeGenerated": "2024-05-03T10:00:03Z", "Type": "AppTraces", "Message": "m",
+            "SeverityLevel": 2,
+        });
+        assert_eq!(
+            render(&azure::Azure, &[], v).unwrap(),
+            "WARN  m\n      table=AppTraces\n"
+        );
+        // No severity, no level: as `--level` and the other outputs see it
+        let v = json!({"TimeGenerated": "2024-05-03T10:00:03Z", "Type": "AppRequests", "Name": "GET /"});
+        assert_eq!(
+            render(&azure::Azure, &[], v.clone()).unwrap(),
+            "      \n      table=AppRequests Name=\"GET /\"\n"
+        );
+        let line = serde_json::to_vec(&v).unwrap();
+        let fields = Fields::split(&line).unwrap();
+        assert_eq!(azure::Azure.head(&fields).unwrap().level, None);
+        assert_eq!(azure::Azure.normalize(&v).unwrap().level, None);
+    }
+
+    #[test]
+    fn generic() {
+        let v = json!({"z": 1, "a": {"y": 2, "b": 3}, "secret": "x"});
+        assert_eq!(
+            render(&generic::Generic, &["--sort-keys", "--hide", "secret"], v).unwrap(),
+            "{\"a\":{\"b\":3,\"y\":2},\"z\":1}\n"
+        );
+    }
 }
@@ -0,0 +1,134 @@
+use serde_json::Value;
+use std::io::{self, Write};
+
+use super::{Head, JsonProtocol, Level, Record};
+use crate::json::Fields;
+use crate::tail::Tail;
+use crate::{RenderCtx, write_kv_json, write_kv_str, write_level};
+
+/// Azure Monitor / Application Insights (Log Analytics row) renderer
+pub struct Azure;
+
+/// Columns rendered in the headline or carrying no information per row.
+const SKIPPED: &[&str] = &[
+    "TimeGenerated",
+    "SeverityLevel",
+    "Message",
+    "TenantId",
+    "SourceSystem",
+    "Type",
+];
+
+impl JsonProtocol for Azure {
+    fn name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn sniff(&self, o: &Fields<'_>) -> f32 {
+        let mut score = 0.0f32;
+        if o.str("TimeGenerated").is_some() {
+            score += 0.4;
+        }
+        if o.contains_key("SeverityLevel") {
+            score += 0.2;
+        }
+        if o.str("Message").is_some() {
+            score += 0.2;
+        }
+        for k in ["Type", "TenantId", "_ResourceId", "AppRoleName"] {
+            if o.contains_key(k) {
+                score += 0.05;
+            }
+        }
+        score.min(1.0)
+    }
+
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
+        let obj = match v.as_object() {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+        let Some(timestamp) = obj.get("TimeGenerated").and_then(Value::as_str) else {
+            return Ok(false);
+        };
+        let message = obj
+            .get("Message")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        // Like `--level` sees it; rows without a severity keep the level column blank
+        let (lvl_color, lvl) = match obj
+            .get("SeverityLevel")
+            .and_then(Value::as_u64)
+            .map(severity_level)
+        {
+            Some(level) => (ctx.pal.for_level(Some(level)), level.as_str()),
+            None => (ctx.pal.faint, ""),
+        };
+
+        // Compute indent columns for continuation: [ts] + space (if any) + level column
+        let mut indent_cols: usize = 0;
+        if ctx.show_ts {
+            let timestamp = ctx.time.show(timestamp);
+            write!(out, "[{}] ", ctx.pal.paint(ctx.pal.timestamp, &timestamp))?;
+            indent_cols += 2 + timestamp.len() + 1;
+        }
+        indent_cols += write_level(&mut *out, ctx, lvl_color, lvl)?;
+        write!(out, "{}", ctx.pal.paint(ctx.pal.message, message))?;
+
+        let mut tail = Tail::default();
+        let table = obj.get("Type").and_then(Value::as_str).unwrap_or("row");
+        write_kv_str(&mut tail, ctx, "table", Some(table))?;
+        for (k, val) in obj {
+            if SKIPPED.contains(&k.as_str()) {
+                continue;
+            }
+            write_kv_json(&mut tail, ctx, k, val)?;
+        }
+        tail.write(out, ctx, indent_cols)?;
+        Ok(true)
+    }
+
+    fn normalize(&self, v: &Value) -> Option<Record> {
+        let obj = v.as_object()?;
+        let timestamp = obj.get("TimeGenerated").and_then(Value::as_str)?;
+        let mut rec = Record {
+            timestamp: Some(timestamp.to_string()),
+            level: obj
+                .get("SeverityLevel")
+                .and_then(Value::as_u64)
+                .map(severity_level),
+            message: obj
+                .get("Message")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            logger: obj.get("Type").and_then(Value::as_str).map(str::to_string),
+            fields: Vec::new(),
+        };
+        for (k, val) in obj {
+            if !SKIPPED.contains(&k.as_str()) {
+                rec.push(k.as_str(), Some(val.clone()));
+            }
+        }
+        Some(rec)
+    }
+
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>> {
+        let timestamp = o.str("TimeGenerated")?;
+        Some(Head {
+            timestamp: Some(timestamp),
+            level: o.u64("SeverityLevel").map(severity_level),
+        })
+    }
+}
+
+/// Application Insights severity: 0 Verbose, 1 Information, 2 Warning, 3 Error, 4 Critical
+fn severity_level(n: u64) -> Level {
+    match n {
+        0 => Level::Debug,
+        1 => Level::Info,
+        2 => Level::Warn,
+        3 => Level::Error,
+        _ => Level::Fatal,
+    }
+}
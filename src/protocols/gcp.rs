@@ -0,0 +1,214 @@
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+use super::{Head, JsonProtocol, Level, Record};
+use crate::json::Fields;
+use crate::tail::Tail;
+use crate::{RenderCtx, write_kv_json, write_kv_str, write_level};
+
+/// Google Cloud Logging `LogEntry` renderer
+pub struct Gcp;
+
+impl JsonProtocol for Gcp {
+    fn name(&self) -> &'static str {
+        "gcp"
+    }
+
+    fn sniff(&self, o: &Fields<'_>) -> f32 {
+        let mut score = 0.0f32;
+        if o.str("logName").is_some() {
+            score += 0.35;
+        }
+        if o.contains_key("insertId") {
+            score += 0.2;
+        }
+        if o.object("resource").and_then(|r| r.str("type")).is_some() {
+            score += 0.2;
+        }
+        if ["jsonPayload", "textPayload", "protoPayload", "httpRequest"]
+            .iter()
+            .any(|k| o.contains_key(k))
+        {
+            score += 0.2;
+        }
+        if o.str("severity").is_some() {
+            score += 0.05;
+        }
+        score.min(1.0)
+    }
+
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
+        let obj = match v.as_object() {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+        let Some(log_name) = obj.get("logName").and_then(Value::as_str) else {
+            return Ok(false);
+        };
+
+        let payload = obj.get("jsonPayload").and_then(Value::as_object);
+        let http = obj.get("httpRequest").and_then(Value::as_object);
+        let message = payload
+            .and_then(|p| p.get("message"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or_else(|| {
+                obj.get("textPayload")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .or_else(|| http.map(http_summary))
+            .unwrap_or_default();
+
+        // Like `--level` sees it; `DEFAULT` and other unknown severities are shown as they are
+        let severity = obj
+            .get("severity")
+            .and_then(Value::as_str)
+            .unwrap_or("DEFAULT");
+        let (lvl_color, lvl) = match Level::parse(severity) {
+            Some(level) => (ctx.pal.for_level(Some(level)), level.as_str()),
+            None => (ctx.pal.faint, severity),
+        };
+
+        let timestamp = obj
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        // Compute indent columns for continuation: [ts] + space (if any) + level column
+        let mut indent_cols: usize = 0;
+        if ctx.show_ts && !timestamp.is_empty() {
+            let timestamp = ctx.time.show(timestamp);
+            write!(out, "[{}] ", ctx.pal.paint(ctx.pal.timestamp, &timestamp))?;
+            indent_cols += 2 + timestamp.len() + 1;
+        }
+        indent_cols += write_level(&mut *out, ctx, lvl_color, lvl)?;
+        write!(
+            out,
+            "{}",
+            ctx.pal.paint(ctx.pal.message, message.trim_end())
+        )?;
+
+        let mut tail = Tail::default();
+        write_kv_str(&mut tail, ctx, "log", Some(&short_log_name(log_name)))?;
+        if let Some(resource) = obj.get("resource").and_then(Value::as_object) {
+            write_kv_str(
+                &mut tail,
+                ctx,
+                "resource",
+                resource.get("type").and_then(Value::as_str),
+            )?;
+            if let Some(labels) = resource.get("labels").and_then(Value::as_object) {
+                write_fields(&mut tail, ctx, labels, None)?;
+            }
+        }
+        if let Some(http) = http {
+            write_fields(&mut tail, ctx, http, Some("http."))?;
+        }
+        write_kv_str(
+            &mut tail,
+            ctx,
+            "trace",
+            obj.get("trace").and_then(Value::as_str),
+        )?;
+        if let Some(payload) = payload {
+            write_fields(&mut tail, ctx, payload, None)?;
+        }
+        tail.write(out, ctx, indent_cols)?;
+        Ok(true)
+    }
+
+    fn normalize(&self, v: &Value) -> Option<Record> {
+        let obj = v.as_object()?;
+        let log_name = obj.get("logName").and_then(Value::as_str)?;
+        let payload = obj.get("jsonPayload").and_then(Value::as_object);
+        let http = obj.get("httpRequest").and_then(Value::as_object);
+
+        let mut rec = Record {
+            timestamp: obj
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            level: obj
+                .get("severity")
+                .and_then(Value::as_str)
+                .and_then(Level::parse),
+            message: payload
+                .and_then(|p| p.get("message"))
+                .or_else(|| obj.get("textPayload"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| http.map(http_summary)),
+            logger: Some(short_log_name(log_name)),
+            fields: Vec::new(),
+        };
+        if let Some(resource) = obj.get("resource") {
+            rec.push("resource", resource.get("type").cloned());
+            for (k, val) in resource
+                .get("labels")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+            {
+                rec.push(k.as_str(), Some(val.clone()));
+            }
+        }
+        for (k, val) in http.into_iter().flatten() {
+            rec.push(format!("http.{k}"), Some(val.clone()));
+        }
+        rec.push("trace", obj.get("trace").cloned());
+        for (k, val) in payload.into_iter().flatten() {
+            if k != "message" {
+                rec.push(k.as_str(), Some(val.clone()));
+            }
+        }
+        Some(rec)
+    }
+
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>> {
+        o.str("logName")?;
+        Some(Head {
+            timestamp: o.str("timestamp"),
+            level: o.str("severity").and_then(|s| Level::parse(&s)),
+        })
+    }
+}
+
+/// `projects/p/logs/run.googleapis.com%2Fstdout` → `run.googleapis.com/stdout`
+fn short_log_name(log_name: &str) -> String {
+    log_name
+        .rsplit_once("/logs/")
+        .map_or(log_name, |(_, l)| l)
+        .replace("%2F", "/")
+}
+
+/// Headline for request logs without a message: `GET 200 https://...`.
+fn http_summary(http: &Map<String, Value>) -> String {
+    let get = |k: &str| {
+        http.get(k)
+            .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+            .unwrap_or_default()
+    };
+    format!(
+        "{} {} {}",
+        get("requestMethod"),
+        get("status"),
+        get("requestUrl")
+    )
+}
+
+fn write_fields(
+    tail: &mut Tail,
+    ctx: &RenderCtx,
+    fields: &Map<String, Value>,
+    prefix: Option<&str>,
+) -> io::Result<()> {
+    for (k, val) in fields {
+        if k == "message" {
+            continue;
+        }
+        let key = format!("{}{}", prefix.unwrap_or_default(), k);
+        write_kv_json(tail, ctx, &key, val)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Write};
+
+use super::{Head, JsonProtocol, Level, Record};
+use crate::json::Fields;
+use crate::pretty::TwoSpacePretty;
+use crate::tail::KeyOrder;
+use crate::{RenderCtx, to_io_err};
+
+/// Fallback for JSON that no dedicated protocol recognizes: re-serialized as-is.
+pub struct Generic;
+
+/// Well-known keys for the normalized view, in order of preference.
+const TS_KEYS: &[&str] = &["ts", "time", "timestamp", "@timestamp", "t"];
+const LEVEL_KEYS: &[&str] = &["level", "lvl", "severity", "log.level"];
+const MESSAGE_KEYS: &[&str] = &["msg", "message", "@message"];
+const LOGGER_KEYS: &[&str] = &["logger", "target", "name", "logger_name"];
+
+impl JsonProtocol for Generic {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn sniff(&self, _o: &Fields<'_>) -> f32 {
+        // Never preferred; only used when nothing else matches
+        0.0
+    }
+
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
+        let reorder = (ctx.sort_keys || !matches!(ctx.key_order, KeyOrder::Input))
+            && matches!(v, Value::Object(_) | Value::Array(_));
+        let hide = !ctx.filter.hide.is_empty();
+        let rewritten;
+        let v = if reorder || hide {
+            let mut copy = v.clone();
+            ctx.filter.apply(&mut copy);
+            if ctx.sort_keys {
+                sort_keys(&mut copy);
+            }
+            if let Value::Object(obj) = &mut copy {
+                let mut entries: Vec<_> = std::mem::take(obj).into_iter().collect();
+                ctx.key_order.sort_by_key(&mut entries, |(k, _)| k);
+                *obj = entries.into_iter().collect();
+            }
+            rewritten = copy;
+            &rewritten
+        } else {
+            v
+        };
+        if ctx.compact {
+            serde_json::to_writer(&mut *out, v).map_err(to_io_err)?;
+        } else {
+            let mut ser =
+                serde_json::Serializer::with_formatter(&mut *out, TwoSpacePretty::default());
+            v.serialize(&mut ser).map_err(to_io_err)?;
+        }
+        out.write_all(b"\n")?;
+        Ok(true)
+    }
+
+    fn normalize(&self, v: &Value) -> Option<Record> {
+        let Some(obj) = v.as_object() else {
+            return Some(Record {
+                fields: vec![("value".to_string(), v.clone())],
+                ..Record::default()
+            });
+        };
+        let find = |keys: &[&'static str]| keys.iter().copied().find(|k| obj.contains_key(*k));
+        let ts_key = find(TS_KEYS);
+        let level_key = find(LEVEL_KEYS);
+        let msg_key = find(MESSAGE_KEYS);
+        let logger_key = find(LOGGER_KEYS);
+
+        let as_text = |key: Option<&str>| {
+            let v = obj.get(key?)?;
+            Some(v.as_str().map_or_else(|| v.to_string(), str::to_string))
+        };
+        let mut rec = Record {
+            timestamp: as_text(ts_key),
+            level: level_key.and_then(|k| Level::from_value(&obj[k])),
+            message: as_text(msg_key),
+            logger: as_text(logger_key),
+            fields: Vec::new(),
+        };
+        for (k, val) in obj {
+            let k = k.as_str();
+            // Keep unparseable level values as a field rather than losing them
+            let is_level = Some(k) == level_key && rec.level.is_some();
+            if Some(k) == ts_key || Some(k) == msg_key || Some(k) == logger_key || is_level {
+                continue;
+            }
+            rec.push(k, Some(val.clone()));
+        }
+        Some(rec)
+    }
+
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>> {
+        let find = |keys: &[&'static str]| keys.iter().copied().find(|k| o.contains_key(k));
+        Some(Head {
+            timestamp: find(TS_KEYS).and_then(|k| o.text(k)),
+            level: find(LEVEL_KEYS)
+                .and_then(|k| o.value(k))
+                .and_then(|v| Level::from_value(&v)),
+        })
+    }
+}
+
+/// Sort the keys of every object in `v`, at any depth.
+fn sort_keys(v: &mut Value) {
+    match v {
+        Value::Object(obj) => {
+            let mut entries: Vec<_> = std::mem::take(obj).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, v) in &mut entries {
+                sort_keys(v);
+            }
+            *obj = entries.into_iter().collect();
+        }
+        Value::Array(a) => a.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
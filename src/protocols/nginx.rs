@@ -1,26 +1,28 @@
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::io::{self, Write};
 
-use super::JsonProtocol;
-use crate::{RenderCtx, as_f64_lossy, write_kv_num, write_kv_str};
+use super::{Head, JsonProtocol, Level, Record};
+use crate::json::Fields;
+use crate::tail::Tail;
+use crate::{RenderCtx, as_f64_lossy, write_kv_bytes, write_kv_secs, write_kv_str, write_level};
 
 /// Nginx-like access log JSON renderer
 pub struct Nginx;
 
 impl JsonProtocol for Nginx {
-    fn sniff(&self, v: &Value) -> f32 {
-        let o = match v.as_object() {
-            Some(m) => m,
-            None => return 0.0,
-        };
+    fn name(&self) -> &'static str {
+        "nginx"
+    }
+
+    fn sniff(&self, o: &Fields<'_>) -> f32 {
         let mut score = 0.0f32;
-        if o.get("method").and_then(Value::as_str).is_some() {
+        if o.str("method").is_some() {
             score += 0.4;
         }
-        if o.get("path").and_then(Value::as_str).is_some() {
+        if o.str("path").is_some() {
             score += 0.4;
         }
-        if o.get("status").is_some() {
+        if o.contains_key("status") {
             score += 0.2;
         }
         // tiny bonus for other typical fields (capped at 1.0)
@@ -39,7 +41,7 @@ impl JsonProtocol for Nginx {
         score.min(1.0)
     }
 
-    fn render(&self, v: &Value, ctx: RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
         let o = match v.as_object() {
             Some(m) => m,
             None => return Ok(false),
@@ -48,11 +50,7 @@ impl JsonProtocol for Nginx {
         let ts = o.get("ts").and_then(Value::as_str);
         let method = o.get("method").and_then(Value::as_str);
         let path = o.get("path").and_then(Value::as_str);
-        let status = o.get("status").and_then(Value::as_u64).or_else(|| {
-            o.get("status")
-                .and_then(Value::as_str)
-                .and_then(|s| s.parse::<u64>().ok())
-        });
+        let status = status(o);
         if method.is_none() || path.is_none() || status.is_none() {
             return Ok(false);
         }
@@ -72,91 +70,179 @@ impl JsonProtocol for Nginx {
         let host = o.get("host").and_then(Value::as_str).unwrap_or("");
         let remote_addr = o.get("remote_addr").and_then(Value::as_str);
 
-        // Compute indent for aligned continuation: [ts] + space + 5-char level + 1 space
+        // Compute indent for aligned continuation: [ts] + space + level column
         let mut indent_cols: usize = 0;
-        if ctx.show_ts {
-            if let Some(ts) = ts {
-                write!(out, "[{}] ", ts)?;
-                indent_cols += 2 + ts.len() + 1; // '[' + ']' + ts + space
-            }
+        if ctx.show_ts
+            && let Some(ts) = ts
+        {
+            let ts = ctx.time.show(ts);
+            write!(out, "[{}] ", ctx.pal.paint(ctx.pal.timestamp, &ts))?;
+            indent_cols += 2 + ts.len() + 1; // '[' + ']' + ts + space
         }
 
         // colored fixed-width level
-        let lvl_fixed = format!("{:<5}", level);
-        write!(out, "{}{}{} ", lvl_color, lvl_fixed, ctx.pal.reset)?;
-        indent_cols += 5 + 1;
+        indent_cols += write_level(&mut *out, ctx, lvl_color, level)?;
         // status and request line (dim method/proto)
         write!(
             out,
             "{} {}{}{} ",
-            status,
+            ctx.pal.paint(ctx.pal.message, status),
             ctx.pal.faint,
             method.unwrap(),
             ctx.pal.reset
         )?;
         if !host.is_empty() {
-            write!(out, "{} ", host)?;
+            write!(out, "{} ", ctx.pal.paint(ctx.pal.message, host))?;
         }
 
-        write!(out, "{}", path.unwrap())?;
-        if !query.is_empty() {
-            write!(out, "?{}", query)?;
+        if query.is_empty() {
+            write!(out, "{}", ctx.pal.paint(ctx.pal.message, path.unwrap()))?;
+        } else {
+            write!(
+                out,
+                "{}",
+                ctx.pal
+                    .paint(ctx.pal.message, format!("{}?{}", path.unwrap(), query))
+            )?;
         }
         if !protocol.is_empty() {
             write!(out, " {}{}{}", ctx.pal.faint, protocol, ctx.pal.reset)?;
         }
 
-        if ctx.compact {
-            // stay on same line; next key/values will start with a leading space
-        } else {
-            out.write_all(b"\n")?;
-            let spaces = vec![b' '; indent_cols.saturating_sub(1)];
-            out.write_all(&spaces)?; // align continuation under message (account for leading space from key writer)
-        }
-
-        write_kv_str(
-            &mut *out,
+        let mut tail = Tail::default();
+        write_kv_bytes(
+            &mut tail,
+            ctx,
             "bytes",
-            o.get("bytes_sent")
-                .and_then(Value::as_u64)
-                .map(|n| n.to_string())
-                .as_deref(),
+            o.get("bytes_sent").and_then(Value::as_u64),
+        )?;
+        write_kv_secs(
+            &mut tail,
+            ctx,
+            "rt",
+            o.get("req_time").and_then(Value::as_f64),
         )?;
-        write_kv_num(&mut *out, "rt", o.get("req_time").and_then(Value::as_f64))?;
-        write_kv_num(
-            &mut *out,
+        write_kv_secs(
+            &mut tail,
+            ctx,
             "up",
             o.get("upstream_time").and_then(as_f64_lossy),
         )?;
         write_kv_str(
-            &mut *out,
+            &mut tail,
+            ctx,
             "up_addr",
             o.get("upstream_addr").and_then(Value::as_str),
         )?;
-        write_kv_str(&mut *out, "req", o.get("req_id").and_then(Value::as_str))?;
         write_kv_str(
-            &mut *out,
+            &mut tail,
+            ctx,
+            "req",
+            o.get("req_id").and_then(Value::as_str),
+        )?;
+        write_kv_str(
+            &mut tail,
+            ctx,
             "trace",
             o.get("traceparent").and_then(Value::as_str),
         )?;
-        write_kv_str(&mut *out, "xff", o.get("xff").and_then(Value::as_str))?;
+        write_kv_str(&mut tail, ctx, "xff", o.get("xff").and_then(Value::as_str))?;
         if let Some(ip) = remote_addr {
-            write_kv_str(&mut *out, "client", Some(ip))?;
+            write_kv_str(&mut tail, ctx, "client", Some(ip))?;
         }
         write_kv_str(
-            &mut *out,
+            &mut tail,
+            ctx,
             "referer",
             o.get("referer").and_then(Value::as_str),
         )?;
-        write_kv_str(&mut *out, "ua", o.get("user_agent").and_then(Value::as_str))?;
+        write_kv_str(
+            &mut tail,
+            ctx,
+            "ua",
+            o.get("user_agent").and_then(Value::as_str),
+        )?;
 
-        if let Some(cache) = o.get("cache").and_then(Value::as_str) {
-            if !cache.is_empty() {
-                write_kv_str(&mut *out, "cache", Some(cache))?;
-            }
+        if let Some(cache) = o.get("cache").and_then(Value::as_str)
+            && !cache.is_empty()
+        {
+            write_kv_str(&mut tail, ctx, "cache", Some(cache))?;
         }
 
-        out.write_all(b"\n")?;
+        tail.write(out, ctx, indent_cols)?;
         Ok(true)
     }
+
+    fn normalize(&self, v: &Value) -> Option<Record> {
+        let o = v.as_object()?;
+        let method = o.get("method").and_then(Value::as_str)?;
+        let path = o.get("path").and_then(Value::as_str)?;
+        let status = status(o)?;
+        let query = o.get("query").and_then(Value::as_str).unwrap_or("");
+
+        let mut rec = Record {
+            timestamp: o.get("ts").and_then(Value::as_str).map(str::to_string),
+            level: Some(Level::from_status(status)),
+            message: Some(if query.is_empty() {
+                format!("{status} {method} {path}")
+            } else {
+                format!("{status} {method} {path}?{query}")
+            }),
+            logger: None,
+            fields: Vec::new(),
+        };
+        // Same names as the pretty renderer's key=value tail
+        rec.push("status", Some(status.into()));
+        rec.push("method", Some(method.into()));
+        rec.push("path", Some(path.into()));
+        for (key, src) in [
+            ("query", "query"),
+            ("protocol", "protocol"),
+            ("host", "host"),
+        ] {
+            rec.push(key, o.get(src).cloned());
+        }
+        rec.push("bytes", o.get("bytes_sent").cloned());
+        rec.push(
+            "rt",
+            o.get("req_time").and_then(as_f64_lossy).map(Into::into),
+        );
+        rec.push(
+            "up",
+            o.get("upstream_time")
+                .and_then(as_f64_lossy)
+                .map(Into::into),
+        );
+        for (key, src) in [
+            ("up_addr", "upstream_addr"),
+            ("req", "req_id"),
+            ("trace", "traceparent"),
+            ("xff", "xff"),
+            ("client", "remote_addr"),
+            ("referer", "referer"),
+            ("ua", "user_agent"),
+            ("cache", "cache"),
+        ] {
+            rec.push(key, o.get(src).cloned());
+        }
+        Some(rec)
+    }
+
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>> {
+        o.str("method")?;
+        o.str("path")?;
+        let status = o.u64("status").or_else(|| o.str("status")?.parse().ok())?;
+        Some(Head {
+            timestamp: o.str("ts"),
+            level: Some(Level::from_status(status)),
+        })
+    }
+}
+
+/// The HTTP status, a number or a numeric string.
+fn status(o: &Map<String, Value>) -> Option<u64> {
+    let status = o.get("status")?;
+    status
+        .as_u64()
+        .or_else(|| status.as_str().and_then(|s| s.parse().ok()))
 }
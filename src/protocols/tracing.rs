@@ -1,40 +1,37 @@
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::io::{self, Write};
 
-use super::JsonProtocol;
-use crate::{RenderCtx, write_json_atom};
+use super::{Head, JsonProtocol, Level, Record};
+use crate::json::Fields;
+use crate::tail::Tail;
+use crate::{RenderCtx, write_kv_json, write_kv_src, write_kv_str, write_level};
 
 /// Rust tracing JSON renderer
 pub struct Tracing;
 
 impl JsonProtocol for Tracing {
-    fn sniff(&self, v: &Value) -> f32 {
-        let o = match v.as_object() {
-            Some(m) => m,
-            None => return 0.0,
-        };
+    fn name(&self) -> &'static str {
+        "tracing"
+    }
+
+    fn sniff(&self, o: &Fields<'_>) -> f32 {
         let mut score = 0.0f32;
-        if o.get("level").and_then(Value::as_str).is_some() {
+        if o.str("level").is_some() {
             score += 0.35;
         }
-        if o.get("target").and_then(Value::as_str).is_some() {
+        if o.str("target").is_some() {
             score += 0.35;
         }
-        if o.get("fields")
-            .and_then(Value::as_object)
-            .and_then(|f| f.get("message"))
-            .and_then(Value::as_str)
-            .is_some()
-        {
+        if o.object("fields").and_then(|f| f.str("message")).is_some() {
             score += 0.25;
         }
-        if o.get("timestamp").is_some() {
+        if o.contains_key("timestamp") {
             score += 0.05;
         }
         score.min(1.0)
     }
 
-    fn render(&self, v: &Value, ctx: RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
+    fn render(&self, v: &Value, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<bool> {
         let obj = match v.as_object() {
             Some(m) => m,
             None => return Ok(false),
@@ -68,61 +65,102 @@ impl JsonProtocol for Tracing {
             .and_then(|s| s.get("name"))
             .and_then(Value::as_str);
 
-        // Compute indent columns for continuation: [ts] + space (if any) + 5-char level + 1 space
+        // Compute indent columns for continuation: [ts] + space (if any) + level column
         let mut indent_cols: usize = 0;
         if ctx.show_ts && !timestamp.is_empty() {
-            write!(out, "[{}] ", timestamp)?;
+            let timestamp = ctx.time.show(timestamp);
+            write!(out, "[{}] ", ctx.pal.paint(ctx.pal.timestamp, &timestamp))?;
             indent_cols += 2 + timestamp.len() + 1; // '[' + ']' + ts + space
         }
         // Fixed-width level (5 chars) and no dash before message
-        let lvl_fixed = format!("{:<5}", lvl);
-        write!(out, "{}{}{} ", lvl_color, lvl_fixed, ctx.pal.reset)?;
-        indent_cols += 5 + 1; // level field + space
-        write!(out, "{}", message.unwrap())?;
+        indent_cols += write_level(&mut *out, ctx, lvl_color, lvl)?;
+        write!(out, "{}", ctx.pal.paint(ctx.pal.message, message.unwrap()))?;
 
-        if ctx.compact {
-            // Single-line: append logger/target and other details inline
-            write!(out, " logger={}", target.unwrap())?;
-            if let Some(span_name) = span {
-                write!(out, " span={}", span_name)?;
-            }
-            if let Some(tid) = thread_id {
-                write!(out, " threadId={}", tid)?;
-            }
-        } else {
-            // Pretty: move the logger/target and details to the next aligned continuation line
-            out.write_all(b"\n")?;
-            // write indent spaces to align under the message
-            let mut spaces = vec![b' '; indent_cols];
-            out.write_all(&spaces)?;
-            write!(out, "logger={}", target.unwrap())?;
-            if let Some(span_name) = span {
-                write!(out, " span={}", span_name)?;
-            }
-            if let Some(tid) = thread_id {
-                write!(out, " threadId={}", tid)?;
-            }
-        }
+        // logger/target and details: inline when compact, else on an aligned continuation line
+        let mut tail = Tail::default();
+        write_kv_str(&mut tail, ctx, "logger", target)?;
+        write_kv_str(&mut tail, ctx, "span", span)?;
+        write_kv_str(&mut tail, ctx, "threadId", thread_id)?;
+        write_kv_src(&mut tail, ctx, source_location(obj).as_deref())?;
         if let Some(fobj) = fields {
             for (k, val) in fobj {
                 if k == "message" {
                     continue;
                 }
-                if ctx.compact {
-                    write!(out, " {}=", k)?;
-                } else {
-                    write!(out, " {}=", k)?;
-                }
-                write_json_atom(&mut *out, val)?;
+                write_kv_json(&mut tail, ctx, k, val)?;
             }
         }
-        if let Some(spans) = obj.get("spans").and_then(Value::as_array) {
-            if !spans.is_empty() {
-                write!(out, " spans=")?;
-                write!(out, "{}", spans.len())?;
-            }
+        if let Some(spans) = obj.get("spans").and_then(Value::as_array)
+            && !spans.is_empty()
+        {
+            write_kv_str(&mut tail, ctx, "spans", Some(&spans.len().to_string()))?;
         }
-        out.write_all(b"\n")?;
+        tail.write(out, ctx, indent_cols)?;
         Ok(true)
     }
+
+    fn normalize(&self, v: &Value) -> Option<Record> {
+        let obj = v.as_object()?;
+        let level = obj.get("level").and_then(Value::as_str)?;
+        let target = obj.get("target").and_then(Value::as_str)?;
+        let fields = obj.get("fields").and_then(Value::as_object)?;
+        let message = fields.get("message").and_then(Value::as_str)?;
+
+        let mut rec = Record {
+            timestamp: obj
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            level: Level::parse(level),
+            message: Some(message.to_string()),
+            logger: Some(target.to_string()),
+            fields: Vec::new(),
+        };
+        rec.push("span", obj.get("span").and_then(|s| s.get("name")).cloned());
+        rec.push("threadId", obj.get("threadId").cloned());
+        rec.push("src", source_location(obj).map(Value::String));
+        for (k, val) in fields {
+            if k != "message" {
+                rec.push(k.as_str(), Some(val.clone()));
+            }
+        }
+        if let Some(spans) = obj.get("spans").and_then(Value::as_array)
+            && !spans.is_empty()
+        {
+            rec.push("spans", Some(spans.len().into()));
+        }
+        Some(rec)
+    }
+
+    fn head<'a>(&self, o: &Fields<'a>) -> Option<Head<'a>> {
+        let level = o.str("level")?;
+        o.str("target")?;
+        o.object("fields")?.str("message")?;
+        Some(Head {
+            timestamp: o.str("timestamp"),
+            level: Level::parse(&level),
+        })
+    }
+}
+
+/// `file:line` from tracing-subscriber's `filename`/`line_number` (`with_file`, `with_line_number`)
+/// or a slog-style `source` field.
+fn source_location(obj: &Map<String, Value>) -> Option<String> {
+    if let Some(file) = obj.get("filename").and_then(Value::as_str) {
+        return Some(match obj.get("line_number").and_then(Value::as_u64) {
+            Some(line) => format!("{file}:{line}"),
+            None => file.to_string(),
+        });
+    }
+    match obj.get("source")? {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(src) => {
+            let file = src.get("file").and_then(Value::as_str)?;
+            Some(match src.get("line").and_then(Value::as_u64) {
+                Some(line) => format!("{file}:{line}"),
+                None => file.to_string(),
+            })
+        }
+        _ => None,
+    }
 }
@@ -0,0 +1,46 @@
+//! Capping the rendered lines per second for `--max-rate`.
+
+use std::time::{Duration, Instant};
+
+/// Admits up to `max` lines per one-second window and counts the rest.
+pub struct RateLimit {
+    max: u32,
+    window_start: Instant,
+    shown: u32,
+    suppressed: u64,
+}
+
+impl RateLimit {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            window_start: Instant::now(),
+            shown: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Whether to render the next line, and the number of lines suppressed in the previous
+    /// window if one just ended.
+    pub fn admit(&mut self) -> (bool, Option<u64>) {
+        let now = Instant::now();
+        let mut ended = None;
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            ended = self.take_suppressed();
+            self.window_start = now;
+            self.shown = 0;
+        }
+        if self.shown < self.max {
+            self.shown += 1;
+            (true, ended)
+        } else {
+            self.suppressed += 1;
+            (false, ended)
+        }
+    }
+
+    /// Lines suppressed since the last report, if any.
+    pub fn take_suppressed(&mut self) -> Option<u64> {
+        Some(std::mem::take(&mut self.suppressed)).filter(|&n| n > 0)
+    }
+}
@@ -0,0 +1,209 @@
+//! End-of-run reports (`--report`) that aggregate the records passing the filters instead of
+//! printing them.
+
+pub mod agg;
+pub mod count;
+pub mod hist;
+pub mod latency;
+pub mod levels;
+pub mod slow;
+pub mod status;
+pub mod timeline;
+pub mod top;
+pub mod uniq;
+
+use clap::ValueEnum;
+use jiff::tz::TimeZone;
+use jiff::{Timestamp, Zoned};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::output::{self, OutputFormat};
+
+/// What `--report` shows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportKind {
+    /// A bar chart of the levels per time bucket.
+    Levels,
+    /// Percentiles of the request and upstream times, overall and per path prefix.
+    Latency,
+    /// Requests per status class and code with the bytes sent and the average latency.
+    Status,
+    /// Database statements taking at least `--min`, grouped by fingerprint, with their total and
+    /// mean time.
+    SlowQueries,
+}
+
+pub trait Report {
+    /// Account for one record that passed the filters.
+    fn record(&mut self, line: &Line<'_>);
+
+    /// Write the report at the end of the run.
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Roughly how many bytes of state the report holds, for `--max-memory`; 0 for reports
+    /// whose state is bounded anyway.
+    fn memory(&self) -> usize {
+        0
+    }
+
+    /// Give up about half of that state, for less detail, once over `--max-memory`.
+    fn shrink(&mut self) {}
+}
+
+impl ReportKind {
+    /// The report; `min` is the threshold of [`ReportKind::SlowQueries`].
+    pub fn report(self, min: Duration) -> Box<dyn Report> {
+        match self {
+            Self::Levels => Box::new(levels::Levels::default()),
+            Self::Latency => Box::new(latency::Latency::default()),
+            Self::Status => Box::new(status::Status::default()),
+            Self::SlowQueries => Box::new(slow::SlowQueries::new(min)),
+        }
+    }
+}
+
+/// The value of a `--by` field: `path`, `status`, `client`, `ua`, and `target` work across
+/// protocols; anything else is looked up as a (dotted) field.
+pub fn group_key(line: &Line<'_>, by: &str) -> Option<String> {
+    let rec = line.record()?;
+    let v = match by {
+        "path" => return rec.path(),
+        "status" => return rec.status().map(|s| s.to_string()),
+        "ua" => return rec.user_agent(),
+        "target" => return rec.logger.clone(),
+        "client" => rec
+            .get("client")
+            .or_else(|| rec.get("http.remoteIp"))
+            .or_else(|| rec.get("ClientIP")),
+        _ => rec.lookup(line.value(), by),
+    }?;
+    Some(match v {
+        Value::String(s) => s,
+        v => v.to_string(),
+    })
+}
+
+/// The bucket width in seconds that splits `span` seconds of records into at most about
+/// `max_buckets` buckets.
+pub fn bucket_width(span: i64, max_buckets: i64) -> i64 {
+    const WIDTHS: [i64; 19] = [
+        1, 2, 5, 10, 15, 30, 60, 120, 300, 600, 900, 1800, 3600, 7200, 10800, 21600, 43200, 86400,
+        604_800,
+    ];
+    WIDTHS
+        .into_iter()
+        .find(|w| span / w < max_buckets)
+        .unwrap_or(WIDTHS[WIDTHS.len() - 1])
+}
+
+/// A bucket width such as `10s`, `5m`, `1h`, or `1d`.
+pub fn width_label(width: i64) -> String {
+    match width {
+        w if w % 86400 == 0 => format!("{}d", w / 86400),
+        w if w % 3600 == 0 => format!("{}h", w / 3600),
+        w if w % 60 == 0 => format!("{}m", w / 60),
+        w => format!("{w}s"),
+    }
+}
+
+/// Label for the bucket starting at `secs` (Unix seconds) in the `--tz`/`--local` zone (UTC
+/// otherwise), as precise as the bucket `width` and total `span` need.
+pub fn bucket_label(ctx: &RenderCtx, secs: i64, width: i64, span: i64) -> String {
+    let zone = ctx.time.zone.clone().unwrap_or(TimeZone::UTC);
+    let Ok(ts) = Timestamp::from_second(secs) else {
+        return secs.to_string();
+    };
+    let format = match (span >= 86400, width >= 60) {
+        (true, _) => "%Y-%m-%d %H:%M",
+        (false, true) => "%H:%M",
+        (false, false) => "%H:%M:%S",
+    };
+    Zoned::new(ts, zone).strftime(format).to_string()
+}
+
+/// Write `rows` under `header` as CSV or TSV for those outputs, and otherwise as aligned
+/// columns with a dimmed header: text in the first `text_columns`, right-aligned numbers after.
+pub fn write_table(
+    out: &mut dyn Write,
+    ctx: &RenderCtx,
+    header: &[&str],
+    rows: &[Vec<String>],
+    text_columns: usize,
+) -> io::Result<()> {
+    if matches!(ctx.output, OutputFormat::Csv | OutputFormat::Tsv) {
+        output::write_cells(out, ctx.output, header)?;
+        for row in rows {
+            let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+            output::write_cells(out, ctx.output, &cells)?;
+        }
+        return Ok(());
+    }
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .map(|c| c.chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &mut dyn Iterator<Item = &str>| {
+        let mut line = String::new();
+        for (i, (cell, width)) in cells.zip(&widths).enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            if i < text_columns {
+                line += &format!("{cell:<width$}");
+            } else {
+                line += &format!("{cell:>width$}");
+            }
+        }
+        line.trim_end().to_string()
+    };
+    let pal = ctx.pal;
+    writeln!(
+        out,
+        "{}",
+        pal.paint(pal.faint, line(&mut header.iter().copied()))
+    )?;
+    for row in rows {
+        writeln!(out, "{}", line(&mut row.iter().map(String::as_str)))?;
+    }
+    Ok(())
+}
+
+/// Width of the bar charts, in terminal cells.
+pub const BAR_WIDTH: usize = 40;
+
+/// A stacked bar of `segments` (count and color), scaled so that `max` fills [`BAR_WIDTH`]
+/// cells, padded to that width.
+pub fn write_bar(
+    out: &mut dyn Write,
+    ctx: &RenderCtx,
+    segments: &[(u64, &'static str)],
+    max: u64,
+) -> io::Result<()> {
+    let max = max.max(1) as f64;
+    let mut cum = 0;
+    let mut drawn = 0;
+    for &(n, color) in segments {
+        cum += n;
+        let end = (cum as f64 / max * BAR_WIDTH as f64).round() as usize;
+        let mut cells = end.saturating_sub(drawn);
+        // Keep small counts visible
+        if n > 0 && cells == 0 && drawn < BAR_WIDTH {
+            cells = 1;
+        }
+        if cells > 0 {
+            write!(out, "{}", ctx.pal.paint(color, "█".repeat(cells)))?;
+        }
+        drawn += cells;
+    }
+    write!(out, "{}", " ".repeat(BAR_WIDTH.saturating_sub(drawn)))
+}
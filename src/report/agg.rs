@@ -0,0 +1,266 @@
+//! `--agg`: aggregates per time bucket (`--every`) and field value (`--by`), for turning logs
+//! into metrics after the fact.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::memory;
+use crate::report::{Report, bucket_label, group_key, write_table};
+use crate::time;
+
+/// One `--agg` function: `count`, or `sum`, `avg`, `min`, or `max` of a numeric field.
+#[derive(Debug, Clone)]
+pub struct AggFn {
+    op: Op,
+    /// The field for everything but `count`.
+    field: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Op {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggFn {
+    /// Parse `count`, `avg(req_time)`, `max(latencies.request)`, …
+    /// Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s == "count" {
+            return Ok(Self {
+                op: Op::Count,
+                field: None,
+            });
+        }
+        let invalid =
+            || format!("`{s}`: expected count, sum(FIELD), avg(FIELD), min(FIELD), or max(FIELD)");
+        let (name, field) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(invalid)?;
+        let op = match name.trim() {
+            "sum" => Op::Sum,
+            "avg" | "mean" => Op::Avg,
+            "min" => Op::Min,
+            "max" => Op::Max,
+            _ => return Err(invalid()),
+        };
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            op,
+            field: Some(field.to_string()),
+        })
+    }
+
+    fn header(&self) -> String {
+        let name = match self.op {
+            Op::Count => return "count".into(),
+            Op::Sum => "sum",
+            Op::Avg => "avg",
+            Op::Min => "min",
+            Op::Max => "max",
+        };
+        format!("{name}({})", self.field.as_deref().unwrap_or_default())
+    }
+}
+
+/// Running values of one function in one row.
+#[derive(Clone, Default)]
+struct Acc {
+    n: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Acc {
+    fn add(&mut self, x: f64) {
+        self.n += 1;
+        self.sum += x;
+        self.min = Some(self.min.map_or(x, |m| m.min(x)));
+        self.max = Some(self.max.map_or(x, |m| m.max(x)));
+    }
+
+    fn show(&self, f: &AggFn, rows: u64) -> String {
+        let x = match f.op {
+            Op::Count => return rows.to_string(),
+            Op::Sum if self.n > 0 => self.sum,
+            Op::Avg if self.n > 0 => self.sum / self.n as f64,
+            Op::Min => self.min.unwrap_or(f64::NAN),
+            Op::Max => self.max.unwrap_or(f64::NAN),
+            _ => f64::NAN,
+        };
+        if x.is_nan() {
+            String::new()
+        } else if x.fract() == 0.0 && x.abs() < 1e15 {
+            format!("{x:.0}")
+        } else {
+            format!("{x:.4}")
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        }
+    }
+}
+
+pub struct Agg {
+    fns: Vec<AggFn>,
+    /// Bucket width in seconds; `None` aggregates the whole run.
+    every: Option<i64>,
+    by: Option<String>,
+    /// Record count and accumulators per bucket start and `by` value.
+    rows: BTreeMap<(i64, String), (u64, Vec<Acc>)>,
+    /// Records skipped for lack of a timestamp.
+    untimed: u64,
+    /// Records in the rows given up to stay within `--max-memory`.
+    dropped: u64,
+}
+
+impl Agg {
+    pub fn new(fns: Vec<AggFn>, every: Option<Duration>, by: Option<String>) -> Self {
+        Self {
+            fns,
+            every: every.map(|d| (d.as_secs() as i64).max(1)),
+            by,
+            rows: BTreeMap::new(),
+            untimed: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl Report for Agg {
+    fn record(&mut self, line: &Line<'_>) {
+        let Some(rec) = line.record() else {
+            return;
+        };
+        let bucket = match self.every {
+            Some(every) => {
+                let Some(ts) = rec.timestamp.as_deref().and_then(time::parse_timestamp) else {
+                    self.untimed += 1;
+                    return;
+                };
+                ts.as_second().div_euclid(every) * every
+            }
+            None => 0,
+        };
+        let group = match &self.by {
+            Some(by) => group_key(line, by).unwrap_or_default(),
+            None => String::new(),
+        };
+        let (count, accs) = self
+            .rows
+            .entry((bucket, group))
+            .or_insert_with(|| (0, vec![Acc::default(); self.fns.len()]));
+        *count += 1;
+        for (f, acc) in self.fns.iter().zip(accs) {
+            let Some(field) = &f.field else {
+                continue;
+            };
+            let x = match rec.lookup(line.value(), field) {
+                Some(Value::Number(n)) => n.as_f64(),
+                Some(Value::String(s)) => s.trim().parse().ok(),
+                _ => None,
+            };
+            if let Some(x) = x {
+                acc.add(x);
+            }
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let fn_headers: Vec<String> = self.fns.iter().map(AggFn::header).collect();
+        let mut header: Vec<&str> = Vec::new();
+        if self.every.is_some() {
+            header.push("time");
+        }
+        if let Some(by) = &self.by {
+            header.push(by);
+        }
+        let text_columns = header.len();
+        header.extend(fn_headers.iter().map(String::as_str));
+
+        let first = self.rows.keys().next().map_or(0, |(t, _)| *t);
+        let last = self.rows.keys().next_back().map_or(0, |(t, _)| *t);
+        let tabular = ctx.output.is_machine_readable();
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|((bucket, group), (count, accs))| {
+                let mut row = Vec::with_capacity(header.len());
+                if let Some(every) = self.every {
+                    row.push(match jiff::Timestamp::from_second(*bucket) {
+                        Ok(ts) if tabular => ts.to_string(),
+                        _ => bucket_label(ctx, *bucket, every, last - first),
+                    });
+                }
+                if self.by.is_some() {
+                    row.push(group.clone());
+                }
+                row.extend(
+                    self.fns
+                        .iter()
+                        .zip(accs)
+                        .map(|(f, acc)| acc.show(f, *count)),
+                );
+                row
+            })
+            .collect();
+        write_table(out, ctx, &header, &rows, text_columns)?;
+        if self.untimed > 0 && !tabular {
+            let pal = ctx.pal;
+            let text = format!("{} records without a timestamp", self.untimed);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        if self.dropped > 0 && !tabular {
+            let pal = ctx.pal;
+            let text = format!(
+                "{} records in rows dropped to stay within --max-memory",
+                self.dropped
+            );
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> usize {
+        let accs = self.fns.len() * size_of::<Acc>();
+        self.rows
+            .keys()
+            .map(|(_, group)| group.len() + accs + memory::ENTRY)
+            .sum()
+    }
+
+    /// Drop the older half of the time buckets, or without `--every` the half of the groups with
+    /// the fewest records.
+    fn shrink(&mut self) {
+        let drop = self.rows.len().div_ceil(2);
+        let keys: Vec<(i64, String)> = if self.every.is_some() {
+            self.rows.keys().take(drop).cloned().collect()
+        } else {
+            let mut by_count: Vec<_> = self.rows.iter().map(|(k, (n, _))| (*n, k)).collect();
+            by_count.sort_unstable();
+            by_count
+                .into_iter()
+                .take(drop)
+                .map(|(_, k)| k.clone())
+                .collect()
+        };
+        for key in keys {
+            if let Some((n, _)) = self.rows.remove(&key) {
+                self.dropped += n;
+            }
+        }
+    }
+}
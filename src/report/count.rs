@@ -0,0 +1,91 @@
+//! `--count`: the number of records passing the filters, like `grep -c`, optionally per value
+//! of a `--by` field.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::memory;
+use crate::report::{Report, group_key};
+
+pub struct Count {
+    by: Option<String>,
+    total: u64,
+    /// Exact counts per value of `by`.
+    groups: HashMap<String, u64>,
+    /// Records of the rarer values given up to stay within `--max-memory`.
+    other: u64,
+    /// Records without `by`.
+    missing: u64,
+}
+
+impl Count {
+    pub fn new(by: Option<String>) -> Self {
+        Self {
+            by,
+            total: 0,
+            groups: HashMap::new(),
+            other: 0,
+            missing: 0,
+        }
+    }
+}
+
+impl Report for Count {
+    fn record(&mut self, line: &Line<'_>) {
+        self.total += 1;
+        let Some(by) = &self.by else {
+            return;
+        };
+        match group_key(line, by) {
+            Some(value) => *self.groups.entry(value).or_default() += 1,
+            None => self.missing += 1,
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        if self.by.is_none() {
+            return writeln!(out, "{}", self.total);
+        }
+        // Like `uniq -c | sort -rn`
+        let mut groups: Vec<_> = self.groups.iter().collect();
+        groups.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (value, n) in groups {
+            writeln!(out, "{n:>8} {value}")?;
+        }
+        let pal = ctx.pal;
+        if self.other > 0 {
+            writeln!(out, "{:>8} {}", self.other, pal.paint(pal.faint, "(other)"))?;
+        }
+        if self.missing > 0 {
+            writeln!(
+                out,
+                "{:>8} {}",
+                self.missing,
+                pal.paint(pal.faint, "(none)")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> usize {
+        self.groups.keys().map(|k| k.len() + memory::ENTRY).sum()
+    }
+
+    fn shrink(&mut self) {
+        let mut counts: Vec<u64> = self.groups.values().copied().collect();
+        counts.sort_unstable();
+        let Some(&cutoff) = counts.get(counts.len() / 2) else {
+            return;
+        };
+        // Ties with the median go too, or a run of equal counts would never shrink
+        self.groups.retain(|_, n| {
+            let keep = *n > cutoff;
+            if !keep {
+                self.other += *n;
+            }
+            keep
+        });
+    }
+}
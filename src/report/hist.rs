@@ -0,0 +1,179 @@
+//! `--hist FIELD`: a histogram of a numeric field as a bar chart, over `--buckets` boundaries or
+//! automatic 1-2-5 log-scale ones.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::report::{Report, write_bar, write_table};
+
+/// Mantissas of the automatic bucket boundaries in each decade.
+const STEPS: [f64; 3] = [1.0, 2.0, 5.0];
+
+pub struct Hist {
+    field: String,
+    /// Upper bounds of the buckets (inclusive, like Prometheus' `le`), ascending; empty for
+    /// automatic buckets.
+    bounds: Vec<f64>,
+    /// Counts per bucket of `bounds`, the last one above all of them.
+    counts: Vec<u64>,
+    /// Counts per automatic bucket: index `i` covers `[boundary(i), boundary(i + 1))`.
+    auto: BTreeMap<i32, u64>,
+    /// Values at or below zero, which have no place on a log scale.
+    non_positive: u64,
+    total: u64,
+    min: f64,
+    max: f64,
+    /// Records without a numeric value in the field.
+    missing: u64,
+}
+
+impl Hist {
+    pub fn new(field: String, mut bounds: Vec<f64>) -> Self {
+        bounds.retain(|b| b.is_finite());
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+        Self {
+            field,
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            auto: BTreeMap::new(),
+            non_positive: 0,
+            total: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            missing: 0,
+        }
+    }
+
+    /// Labels and counts of the buckets, from the lowest; automatic ones with the empty buckets
+    /// in between, so the shape shows.
+    fn rows(&self) -> Vec<(String, u64)> {
+        if !self.bounds.is_empty() {
+            let last = self.bounds.len();
+            return self
+                .counts
+                .iter()
+                .enumerate()
+                .map(|(i, &n)| {
+                    let label = match i {
+                        0 => format!("≤ {}", self.bounds[0]),
+                        _ if i == last => format!("> {}", self.bounds[last - 1]),
+                        _ => format!("{} – {}", self.bounds[i - 1], self.bounds[i]),
+                    };
+                    (label, n)
+                })
+                .collect();
+        }
+        let mut rows = Vec::new();
+        if self.non_positive > 0 {
+            rows.push(("≤ 0".to_string(), self.non_positive));
+        }
+        if let (Some((&first, _)), Some((&last, _))) =
+            (self.auto.first_key_value(), self.auto.last_key_value())
+        {
+            for i in first..=last {
+                let label = format!("{} – {}", boundary(i), boundary(i + 1));
+                rows.push((label, self.auto.get(&i).copied().unwrap_or(0)));
+            }
+        }
+        rows
+    }
+}
+
+impl Report for Hist {
+    fn record(&mut self, line: &Line<'_>) {
+        let x = line
+            .record()
+            .and_then(|rec| match rec.lookup(line.value(), &self.field)? {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            })
+            .filter(|x: &f64| x.is_finite());
+        let Some(x) = x else {
+            self.missing += 1;
+            return;
+        };
+        self.total += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        if !self.bounds.is_empty() {
+            let bucket = self.bounds.partition_point(|&b| b < x);
+            self.counts[bucket] += 1;
+        } else if x <= 0.0 {
+            self.non_positive += 1;
+        } else {
+            *self.auto.entry(bucket_of(x)).or_default() += 1;
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let rows = self.rows();
+        if ctx.output.is_machine_readable() {
+            let rows: Vec<Vec<String>> = rows
+                .into_iter()
+                .map(|(label, n)| vec![label, n.to_string()])
+                .collect();
+            return write_table(out, ctx, &[&self.field, "count"], &rows, 1);
+        }
+        if self.total == 0 {
+            let text = format!("no numeric values of {}", self.field);
+            return writeln!(out, "{}", pal.paint(pal.faint, text));
+        }
+        let width = rows
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(self.field.len());
+        let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0);
+        writeln!(
+            out,
+            "{}",
+            pal.paint(pal.faint, format!("{:<width$} {:>9}", self.field, "count"))
+        )?;
+        for (label, n) in &rows {
+            write!(out, "{label:<width$} {n:>9} ")?;
+            write_bar(out, ctx, &[(*n, pal.info)], max)?;
+            writeln!(out)?;
+        }
+        let summary = format!("{} values, min {}, max {}", self.total, self.min, self.max);
+        writeln!(out, "{}", pal.paint(pal.faint, summary))?;
+        if self.missing > 0 {
+            let text = format!("{} records without {}", self.missing, self.field);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+}
+
+/// The automatic bucket of a positive `x`.
+fn bucket_of(x: f64) -> i32 {
+    let decade = x.log10().floor() as i32;
+    let step = STEPS
+        .iter()
+        .rposition(|&m| boundary_in(decade, m) <= x)
+        .unwrap_or(0);
+    let i = decade * STEPS.len() as i32 + step as i32;
+    // `log10` may land just below an exact power of ten
+    if boundary(i + 1) <= x { i + 1 } else { i }
+}
+
+/// The lower boundary of automatic bucket `i`: …, 0.5, 1, 2, 5, 10, 20, ….
+fn boundary(i: i32) -> f64 {
+    let steps = STEPS.len() as i32;
+    boundary_in(i.div_euclid(steps), STEPS[i.rem_euclid(steps) as usize])
+}
+
+fn boundary_in(decade: i32, mantissa: f64) -> f64 {
+    // Dividing keeps 0.05 exact where multiplying by 0.01 wouldn't
+    if decade < 0 {
+        mantissa / 10f64.powi(-decade)
+    } else {
+        mantissa * 10f64.powi(decade)
+    }
+}
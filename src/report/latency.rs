@@ -0,0 +1,329 @@
+//! `--report latency`: p50/p90/p95/p99/max of the request and upstream times, overall and per
+//! path prefix.
+
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::memory;
+use crate::protocols::Record;
+use crate::report::Report;
+use crate::time::humanize_duration;
+
+/// Path segments that make up a prefix: `/api/orders/7` is counted under `/api/orders`.
+const PREFIX_SEGMENTS: usize = 2;
+
+/// Rows shown per table besides the overall one.
+const MAX_ROWS: usize = 20;
+
+const QUANTILES: [(f64, &str); 4] = [(0.5, "p50"), (0.9, "p90"), (0.95, "p95"), (0.99, "p99")];
+
+#[derive(Default)]
+pub struct Latency {
+    request: Times,
+    upstream: Times,
+}
+
+/// Sketches of one duration, overall and per path prefix.
+#[derive(Default)]
+struct Times {
+    all: Sketch,
+    by_prefix: HashMap<String, Sketch>,
+    /// Records under the prefixes with the fewest, given up to stay within `--max-memory`.
+    dropped: u64,
+}
+
+impl Times {
+    fn add(&mut self, prefix: Option<&str>, secs: f64) {
+        self.all.add(secs);
+        if let Some(prefix) = prefix {
+            self.by_prefix
+                .entry(prefix.to_string())
+                .or_default()
+                .add(secs);
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, title: &str, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let mut rows: Vec<_> = self.by_prefix.iter().collect();
+        rows.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        let hidden = rows.len().saturating_sub(MAX_ROWS);
+        rows.truncate(MAX_ROWS);
+        let width = rows
+            .iter()
+            .map(|(p, _)| p.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(title.len());
+
+        write!(
+            out,
+            "{}",
+            pal.paint(pal.faint, format!("{title:<width$} {:>8}", "count"))
+        )?;
+        for (_, name) in QUANTILES {
+            write!(out, "{}", pal.paint(pal.faint, format!(" {name:>7}")))?;
+        }
+        writeln!(out, "{}", pal.paint(pal.faint, format!(" {:>7}", "max")))?;
+        write_row(out, "(all)", &self.all, width)?;
+        for (prefix, sketch) in rows {
+            write_row(out, prefix, sketch, width)?;
+        }
+        if hidden > 0 {
+            let text = format!("… {hidden} more prefixes");
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        if self.dropped > 0 {
+            let text = format!(
+                "{} requests under prefixes dropped to stay within --max-memory",
+                self.dropped
+            );
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> usize {
+        self.all.memory()
+            + self
+                .by_prefix
+                .iter()
+                .map(|(prefix, sketch)| prefix.len() + sketch.memory() + memory::ENTRY)
+                .sum::<usize>()
+    }
+
+    /// Drop the rarer half of the prefixes beyond those shown, then make the sketches coarser.
+    fn shrink(&mut self) {
+        if self.by_prefix.len() > MAX_ROWS {
+            let mut counts: Vec<u64> = self.by_prefix.values().map(|s| s.count).collect();
+            counts.sort_unstable_by(|a, b| b.cmp(a));
+            let cutoff = counts[MAX_ROWS.max(counts.len() / 2) - 1];
+            let before = self.by_prefix.len();
+            self.by_prefix.retain(|_, sketch| {
+                let keep = sketch.count >= cutoff;
+                if !keep {
+                    self.dropped += sketch.count;
+                }
+                keep
+            });
+            if self.by_prefix.len() < before {
+                return;
+            }
+        }
+        self.all.coarsen();
+        self.by_prefix.values_mut().for_each(Sketch::coarsen);
+    }
+}
+
+fn write_row(out: &mut dyn Write, name: &str, sketch: &Sketch, width: usize) -> io::Result<()> {
+    write!(out, "{name:<width$} {:>8}", sketch.count)?;
+    for (q, _) in QUANTILES {
+        write!(out, " {:>7}", show(sketch.quantile(q)))?;
+    }
+    writeln!(out, " {:>7}", show(sketch.max))
+}
+
+fn show(secs: f64) -> String {
+    Duration::try_from_secs_f64(secs).map_or_else(|_| "-".into(), humanize_duration)
+}
+
+impl Report for Latency {
+    fn record(&mut self, line: &Line<'_>) {
+        let Some(rec) = line.record() else {
+            return;
+        };
+        let prefix = rec.path().map(|p| path_prefix(&p));
+        // nginx `req_time`, or whatever duration field other request logs have
+        let request = seconds(rec, "rt").or_else(|| rec.duration().map(|d| d.as_secs_f64()));
+        if let Some(secs) = request {
+            self.request.add(prefix.as_deref(), secs);
+        }
+        if let Some(secs) = seconds(rec, "up") {
+            self.upstream.add(prefix.as_deref(), secs);
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        if self.request.all.count == 0 {
+            let pal = ctx.pal;
+            return writeln!(out, "{}", pal.paint(pal.faint, "no request times"));
+        }
+        self.request.write(ctx, "request time", out)?;
+        if self.upstream.all.count > 0 {
+            writeln!(out)?;
+            self.upstream.write(ctx, "upstream time", out)?;
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> usize {
+        self.request.memory() + self.upstream.memory()
+    }
+
+    fn shrink(&mut self) {
+        self.request.shrink();
+        self.upstream.shrink();
+    }
+}
+
+/// A number of seconds, or nginx's `0.012, 0.034` for retried upstreams, summed.
+fn seconds(rec: &Record, key: &str) -> Option<f64> {
+    match rec.get(key)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s
+            .split([',', ':'])
+            .map(|part| part.trim().parse::<f64>().ok())
+            .sum(),
+        _ => None,
+    }
+}
+
+/// The first [`PREFIX_SEGMENTS`] segments of `path`.
+fn path_prefix(path: &str) -> String {
+    let end = path
+        .match_indices('/')
+        .nth(PREFIX_SEGMENTS)
+        .map_or(path.len(), |(i, _)| i);
+    path[..end].to_string()
+}
+
+/// Streaming quantile sketch with logarithmic buckets: every quantile is within
+/// [`Sketch::ACCURACY`] of the true value, in memory bounded by the range of values
+/// rather than their number.
+#[derive(Default)]
+struct Sketch {
+    buckets: BTreeMap<i32, u64>,
+    /// How often pairs of buckets were merged under `--max-memory`, each time doubling the
+    /// width of the buckets on the log scale and about the error.
+    coarsened: u32,
+    /// Values too small for a bucket (under a microsecond).
+    zeros: u64,
+    count: u64,
+    max: f64,
+}
+
+impl Sketch {
+    const ACCURACY: f64 = 0.01;
+    const MIN: f64 = 1e-6;
+
+    fn gamma(&self) -> f64 {
+        ((1.0 + Self::ACCURACY) / (1.0 - Self::ACCURACY)).powi(1 << self.coarsened)
+    }
+
+    fn memory(&self) -> usize {
+        self.buckets.len() * memory::ENTRY
+    }
+
+    /// Merge each pair of buckets into one of twice the width, (γ^(2j-2), γ^(2j)].
+    fn coarsen(&mut self) {
+        if self.buckets.len() < 2 {
+            return;
+        }
+        let mut merged = BTreeMap::new();
+        for (index, n) in std::mem::take(&mut self.buckets) {
+            *merged.entry((index + 1).div_euclid(2)).or_default() += n;
+        }
+        self.buckets = merged;
+        self.coarsened += 1;
+    }
+
+    fn add(&mut self, v: f64) {
+        if !v.is_finite() || v < 0.0 {
+            return;
+        }
+        self.count += 1;
+        self.max = self.max.max(v);
+        if v < Self::MIN {
+            self.zeros += 1;
+            return;
+        }
+        let index = (v.ln() / self.gamma().ln()).ceil() as i32;
+        *self.buckets.entry(index).or_default() += 1;
+    }
+
+    fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return f64::NAN;
+        }
+        let rank = (q * (self.count - 1) as f64).round() as u64;
+        if rank < self.zeros {
+            return 0.0;
+        }
+        let mut seen = self.zeros;
+        let gamma = self.gamma();
+        for (&index, &n) in &self.buckets {
+            seen += n;
+            if seen > rank {
+                // Midpoint of the bucket (gamma^(i-1), gamma^i], relative to its bounds
+                return (2.0 * gamma.powi(index) / (gamma + 1.0)).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sketch_quantiles_within_accuracy() {
+        let mut sketch = Sketch::default();
+        // 1ms to 10s
+        for i in 1..=10_000 {
+            sketch.add(f64::from(i) * 1e-3);
+        }
+        for (q, _) in QUANTILES {
+            let exact = (q * 9_999.0).round() * 1e-3 + 1e-3;
+            let estimate = sketch.quantile(q);
+            assert!(
+                (estimate - exact).abs() <= exact * Sketch::ACCURACY,
+                "{q}: {estimate} vs {exact}"
+            );
+        }
+        assert_eq!(sketch.quantile(1.0), 10.0);
+    }
+
+    #[test]
+    fn sketch_edge_cases() {
+        let mut sketch = Sketch::default();
+        assert!(sketch.quantile(0.5).is_nan());
+        for v in [0.0, 0.0, 0.0, -1.0, f64::NAN, f64::INFINITY, 2.0] {
+            sketch.add(v);
+        }
+        assert_eq!(sketch.count, 4);
+        assert_eq!(sketch.quantile(0.5), 0.0);
+        assert!((sketch.quantile(1.0) - 2.0).abs() <= 2.0 * Sketch::ACCURACY);
+    }
+
+    #[test]
+    fn coarsened_sketch_doubles_the_error() {
+        let mut sketch = Sketch::default();
+        for i in 1..=1_000 {
+            sketch.add(f64::from(i));
+        }
+        let buckets = sketch.buckets.len();
+        sketch.coarsen();
+        assert!(sketch.buckets.len() < buckets);
+        assert_eq!(sketch.buckets.values().sum::<u64>(), 1_000);
+        let median = sketch.quantile(0.5);
+        assert!(
+            (median - 500.0).abs() <= 500.0 * 2.0 * Sketch::ACCURACY,
+            "{median}"
+        );
+        // New values land in the wider buckets
+        sketch.add(1_000.0);
+        assert_eq!(sketch.count, 1_001);
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(path_prefix("/api/orders/7"), "/api/orders");
+        assert_eq!(path_prefix("/api"), "/api");
+        assert_eq!(path_prefix("/"), "/");
+    }
+}
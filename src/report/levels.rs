@@ -0,0 +1,110 @@
+//! `--report levels`: how many records of each level arrived per time bucket.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::protocols::Level;
+use crate::report::{Report, bucket_label, bucket_width, width_label, write_bar};
+use crate::time;
+
+/// Time buckets short enough to show the shape of a run in at most about this many rows.
+const MAX_ROWS: i64 = 40;
+
+/// Levels from most to least severe, then records without one; the order of the stacked bars.
+const COLUMNS: [Option<Level>; 7] = [
+    Some(Level::Fatal),
+    Some(Level::Error),
+    Some(Level::Warn),
+    Some(Level::Info),
+    Some(Level::Debug),
+    Some(Level::Trace),
+    None,
+];
+
+#[derive(Default)]
+pub struct Levels {
+    /// Counts per Unix second, by [`COLUMNS`] index.
+    seconds: BTreeMap<i64, [u64; COLUMNS.len()]>,
+    /// Records without a parseable timestamp.
+    untimed: u64,
+}
+
+impl Report for Levels {
+    fn record(&mut self, line: &Line<'_>) {
+        if !line.is_json() {
+            return;
+        }
+        let Some(ts) = line.timestamp().and_then(time::parse_timestamp) else {
+            self.untimed += 1;
+            return;
+        };
+        let column = COLUMNS.iter().position(|&l| l == line.level()).unwrap_or(0);
+        self.seconds.entry(ts.as_second()).or_default()[column] += 1;
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let (Some((&first, _)), Some((&last, _))) = (
+            self.seconds.first_key_value(),
+            self.seconds.last_key_value(),
+        ) else {
+            return writeln!(out, "{}", pal.paint(pal.faint, "no timestamped records"));
+        };
+        let span = last - first;
+        let width = bucket_width(span, MAX_ROWS);
+        let mut buckets = BTreeMap::<i64, [u64; COLUMNS.len()]>::new();
+        // Empty buckets too, so gaps show
+        for start in (first.div_euclid(width)..=last.div_euclid(width)).map(|b| b * width) {
+            buckets.insert(start, [0; COLUMNS.len()]);
+        }
+        for (&sec, counts) in &self.seconds {
+            let bucket = buckets.entry(sec.div_euclid(width) * width).or_default();
+            for (sum, n) in bucket.iter_mut().zip(counts) {
+                *sum += n;
+            }
+        }
+        let max = buckets
+            .values()
+            .map(|c| c.iter().sum::<u64>())
+            .max()
+            .unwrap_or(0);
+
+        writeln!(
+            out,
+            "{}",
+            pal.paint(pal.faint, format!("levels per {}", width_label(width)))
+        )?;
+        for (&start, counts) in &buckets {
+            let segments: Vec<_> = COLUMNS
+                .iter()
+                .zip(counts)
+                .map(|(&level, &n)| (n, pal.for_level(level)))
+                .collect();
+            write!(
+                out,
+                "{}  ",
+                pal.paint(pal.faint, bucket_label(ctx, start, width, span))
+            )?;
+            write_bar(out, ctx, &segments, max)?;
+            write!(out, " {:>6}", counts.iter().sum::<u64>())?;
+            for (&level, &n) in COLUMNS.iter().zip(counts) {
+                if n > 0 {
+                    let name = level.map_or("none", Level::as_str);
+                    write!(
+                        out,
+                        "  {}",
+                        pal.paint(pal.for_level(level), format!("{name} {n}"))
+                    )?;
+                }
+            }
+            writeln!(out)?;
+        }
+        if self.untimed > 0 {
+            let text = format!("{} records without a timestamp", self.untimed);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+}
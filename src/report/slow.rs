@@ -0,0 +1,276 @@
+//! `--report slow-queries`: database statements that took at least `--min`, grouped by their
+//! fingerprint (literals stripped), by total time.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::memory;
+use crate::output;
+use crate::protocols::Record;
+use crate::report::Report;
+use crate::time::humanize_duration;
+
+/// Fields holding the statement: SQL text, or a MongoDB command document.
+const STATEMENT_FIELDS: [&str; 6] = [
+    "statement",
+    "query",
+    "sql",
+    "db.statement",
+    "command",
+    "attr.command",
+];
+
+/// Fingerprints shown, at most.
+const MAX_ROWS: usize = 20;
+
+pub struct SlowQueries {
+    min: Duration,
+    by_fingerprint: HashMap<String, Calls>,
+    /// Calls of the statements with the least total time, given up to stay within
+    /// `--max-memory`.
+    dropped: u64,
+}
+
+#[derive(Default)]
+struct Calls {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl SlowQueries {
+    pub fn new(min: Duration) -> Self {
+        Self {
+            min,
+            by_fingerprint: HashMap::new(),
+            dropped: 0,
+        }
+    }
+}
+
+impl Report for SlowQueries {
+    fn record(&mut self, line: &Line<'_>) {
+        let Some((fingerprint, took)) = line.record().and_then(|rec| statement(rec, line.value()))
+        else {
+            return;
+        };
+        if took < self.min {
+            return;
+        }
+        let calls = self.by_fingerprint.entry(fingerprint).or_default();
+        calls.count += 1;
+        calls.total += took;
+        calls.max = calls.max.max(took);
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let mut rows: Vec<_> = self.by_fingerprint.iter().collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        if ctx.output.is_machine_readable() {
+            output::write_cells(
+                out,
+                ctx.output,
+                &["total (s)", "calls", "mean (s)", "max (s)", "statement"],
+            )?;
+            for (statement, calls) in rows {
+                let mean = calls.total.div_f64(calls.count as f64);
+                let secs = |d: Duration| format!("{:.6}", d.as_secs_f64());
+                output::write_cells(
+                    out,
+                    ctx.output,
+                    &[
+                        &secs(calls.total),
+                        &calls.count.to_string(),
+                        &secs(mean),
+                        &secs(calls.max),
+                        statement,
+                    ],
+                )?;
+            }
+            return Ok(());
+        }
+        if rows.is_empty() {
+            let text = format!(
+                "no statements taking {} or more",
+                humanize_duration(self.min)
+            );
+            return writeln!(out, "{}", pal.paint(pal.faint, text));
+        }
+        let hidden = rows.len().saturating_sub(MAX_ROWS);
+        rows.truncate(MAX_ROWS);
+        writeln!(
+            out,
+            "{}",
+            pal.paint(
+                pal.faint,
+                format!(
+                    "{:>8} {:>7} {:>8} {:>8}  statement",
+                    "total", "calls", "mean", "max"
+                )
+            )
+        )?;
+        for (statement, calls) in rows {
+            let mean = calls.total.div_f64(calls.count as f64);
+            writeln!(
+                out,
+                "{:>8} {:>7} {:>8} {:>8}  {statement}",
+                humanize_duration(calls.total),
+                calls.count,
+                humanize_duration(mean),
+                humanize_duration(calls.max),
+            )?;
+        }
+        if hidden > 0 {
+            let text = format!("… {hidden} more statements");
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        if self.dropped > 0 {
+            let text = format!(
+                "{} calls of statements dropped to stay within --max-memory",
+                self.dropped
+            );
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> usize {
+        self.by_fingerprint
+            .keys()
+            .map(|k| k.len() + size_of::<Calls>() + memory::ENTRY)
+            .sum()
+    }
+
+    fn shrink(&mut self) {
+        let mut totals: Vec<Duration> = self.by_fingerprint.values().map(|c| c.total).collect();
+        totals.sort_unstable();
+        let Some(&cutoff) = totals.get(totals.len() / 2) else {
+            return;
+        };
+        self.by_fingerprint.retain(|_, calls| {
+            let keep = calls.total > cutoff;
+            if !keep {
+                self.dropped += calls.count;
+            }
+            keep
+        });
+    }
+}
+
+/// The fingerprint of the record's statement and how long it took; Postgres' `jsonlog` has both
+/// only in the message: `duration: 812.3 ms  statement: select …`.
+fn statement(rec: &Record, raw: Option<&Value>) -> Option<(String, Duration)> {
+    let found = STATEMENT_FIELDS
+        .into_iter()
+        .find_map(|name| match rec.lookup(raw, name)? {
+            Value::String(s) => Some(fingerprint(&s)),
+            Value::Object(command) => Some(command_shape(&command).to_string()),
+            _ => None,
+        });
+    if let Some(fingerprint) = found {
+        return Some((fingerprint, rec.duration()?));
+    }
+    let (ms, rest) = rec
+        .message
+        .as_deref()?
+        .strip_prefix("duration: ")?
+        .split_once(" ms")?;
+    // `statement: …`, or `execute <name>: …` for prepared statements
+    let (_, sql) = rest.trim_start().split_once(": ")?;
+    let took = Duration::try_from_secs_f64(ms.trim().parse::<f64>().ok()? / 1000.0).ok()?;
+    Some((fingerprint(sql), took))
+}
+
+/// SQL with its string and number literals replaced by `?`, lists of them by `?+`, and
+/// whitespace collapsed, so that statements differing only in their values group together.
+fn fingerprint(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    // Digits within identifiers (`t1`, `$1`) aren't literals
+    let mut in_word = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\'' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                        }
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+                push_placeholder(&mut out);
+                in_word = false;
+            }
+            c if c.is_ascii_digit() && !in_word => {
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '.')
+                {
+                    chars.next();
+                }
+                push_placeholder(&mut out);
+            }
+            c if c.is_whitespace() => {
+                if !out.is_empty() && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+                in_word = false;
+            }
+            c => {
+                out.push(c);
+                in_word = c.is_alphanumeric() || c == '_' || c == '$';
+            }
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Append `?`, merging it into a preceding `?, ` list.
+fn push_placeholder(out: &mut String) {
+    if let Some(head) = out.trim_end().strip_suffix(',') {
+        let head = head.trim_end();
+        if let Some(head) = head.strip_suffix("?+").or_else(|| head.strip_suffix('?')) {
+            out.truncate(head.len());
+            out.push_str("?+");
+            return;
+        }
+    }
+    out.push('?');
+}
+
+/// A MongoDB command such as `{"find":"users","filter":{"age":{"$gt":30}}}` with the values
+/// stripped, keeping the collection it starts with.
+fn command_shape(command: &serde_json::Map<String, Value>) -> Value {
+    let mut entries = command.iter();
+    let first = entries.next().map(|(k, v)| match v {
+        Value::String(_) => (k.clone(), v.clone()),
+        _ => (k.clone(), shape(v)),
+    });
+    Value::Object(
+        first
+            .into_iter()
+            .chain(entries.map(|(k, v)| (k.clone(), shape(v))))
+            .collect(),
+    )
+}
+
+/// `v` with every value replaced by `"?"` and arrays by their first element.
+fn shape(v: &Value) -> Value {
+    match v {
+        Value::Object(obj) => {
+            Value::Object(obj.iter().map(|(k, v)| (k.clone(), shape(v))).collect())
+        }
+        Value::Array(items) => Value::Array(items.first().map(shape).into_iter().collect()),
+        _ => Value::String("?".into()),
+    }
+}
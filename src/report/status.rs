@@ -0,0 +1,121 @@
+//! `--report status`: requests per status class and code, with the bytes sent and the average
+//! latency.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::report::{Report, write_table};
+use crate::size::humanize_bytes;
+use crate::time::humanize_duration;
+
+#[derive(Default)]
+pub struct Status {
+    codes: BTreeMap<u64, Row>,
+    /// Records without a status code.
+    missing: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Row {
+    count: u64,
+    bytes: u64,
+    /// Sum and number of the request durations, for the average.
+    secs: f64,
+    timed: u64,
+}
+
+impl Row {
+    fn merge(&mut self, other: &Row) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+        self.secs += other.secs;
+        self.timed += other.timed;
+    }
+
+    fn cells(&self, label: String, total: u64, tabular: bool) -> Vec<String> {
+        let share = self.count as f64 * 100.0 / total.max(1) as f64;
+        let avg = (self.timed > 0).then(|| self.secs / self.timed as f64);
+        if tabular {
+            return vec![
+                label,
+                self.count.to_string(),
+                format!("{share:.1}"),
+                self.bytes.to_string(),
+                avg.map_or_else(String::new, |secs| format!("{secs:.6}")),
+            ];
+        }
+        vec![
+            label,
+            self.count.to_string(),
+            format!("{share:.1}%"),
+            humanize_bytes(self.bytes),
+            avg.and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .map_or_else(String::new, humanize_duration),
+        ]
+    }
+}
+
+impl Report for Status {
+    fn record(&mut self, line: &Line<'_>) {
+        let Some(rec) = line.record() else {
+            return;
+        };
+        let Some(code) = rec.status() else {
+            self.missing += 1;
+            return;
+        };
+        let row = self.codes.entry(code).or_default();
+        row.count += 1;
+        row.bytes += rec.bytes().unwrap_or(0);
+        if let Some(d) = rec.duration() {
+            row.secs += d.as_secs_f64();
+            row.timed += 1;
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        if self.codes.is_empty() {
+            return writeln!(out, "{}", pal.paint(pal.faint, "no records with a status"));
+        }
+        let tabular = ctx.output.is_machine_readable();
+        let total = self.codes.values().map(|r| r.count).sum();
+        let mut classes = BTreeMap::<u64, Row>::new();
+        for (code, row) in &self.codes {
+            classes.entry(code / 100).or_default().merge(row);
+        }
+        // Each class followed by its codes
+        let mut rows = Vec::new();
+        for (class, summed) in &classes {
+            rows.push(summed.cells(format!("{class}xx"), total, tabular));
+            for (code, row) in self.codes.range(class * 100..(class + 1) * 100) {
+                let label = if tabular {
+                    code.to_string()
+                } else {
+                    format!("  {code}")
+                };
+                rows.push(row.cells(label, total, tabular));
+            }
+        }
+        let latency = if tabular {
+            "avg latency (s)"
+        } else {
+            "avg latency"
+        };
+        write_table(
+            out,
+            ctx,
+            &["status", "count", "share", "bytes", latency],
+            &rows,
+            1,
+        )?;
+        if self.missing > 0 && !tabular {
+            let text = format!("{} records without a status", self.missing);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+}
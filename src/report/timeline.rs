@@ -0,0 +1,88 @@
+//! `--timeline`: a sparkline of the volume per time bucket, buckets with errors in red.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::protocols::Level;
+use crate::report::{Report, bucket_label, bucket_width, width_label};
+use crate::time;
+
+/// Cells in the sparkline, at most.
+const MAX_CELLS: i64 = 60;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Default)]
+pub struct Timeline {
+    /// Records and errors per Unix second.
+    seconds: BTreeMap<i64, (u64, u64)>,
+    /// Records without a parseable timestamp.
+    untimed: u64,
+}
+
+impl Report for Timeline {
+    fn record(&mut self, line: &Line<'_>) {
+        if !line.is_json() {
+            return;
+        }
+        let Some(ts) = line.timestamp().and_then(time::parse_timestamp) else {
+            self.untimed += 1;
+            return;
+        };
+        let error = line.level().is_some_and(|l| l >= Level::Error);
+        let (n, errors) = self.seconds.entry(ts.as_second()).or_default();
+        *n += 1;
+        *errors += u64::from(error);
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let (Some((&first, _)), Some((&last, _))) = (
+            self.seconds.first_key_value(),
+            self.seconds.last_key_value(),
+        ) else {
+            return writeln!(out, "{}", pal.paint(pal.faint, "no timestamped records"));
+        };
+        let span = last - first;
+        let width = bucket_width(span, MAX_CELLS);
+        let start = first.div_euclid(width);
+        let cells = (last.div_euclid(width) - start + 1) as usize;
+        let mut buckets = vec![(0u64, 0u64); cells];
+        for (&sec, &(n, errors)) in &self.seconds {
+            let bucket = &mut buckets[(sec.div_euclid(width) - start) as usize];
+            bucket.0 += n;
+            bucket.1 += errors;
+        }
+        let max = buckets.iter().map(|b| b.0).max().unwrap_or(0).max(1);
+        let error_buckets = buckets.iter().filter(|b| b.1 > 0).count();
+
+        let title = format!(
+            "volume per {}, max {max}; errors in {error_buckets} of {cells} buckets",
+            width_label(width)
+        );
+        writeln!(out, "{}", pal.paint(pal.faint, title))?;
+        for &(n, errors) in &buckets {
+            if n == 0 {
+                write!(out, " ")?;
+                continue;
+            }
+            let bar = BARS[((n * BARS.len() as u64).div_ceil(max) - 1) as usize];
+            let color = if errors > 0 { pal.error } else { pal.info };
+            write!(out, "{}", pal.paint(color, bar))?;
+        }
+        writeln!(out)?;
+        // The first and last bucket under the ends of the line
+        let from = bucket_label(ctx, start * width, width, span);
+        let to = bucket_label(ctx, (start + cells as i64 - 1) * width, width, span);
+        let gap = cells.saturating_sub(from.len() + to.len()).max(1);
+        let axis = format!("{from}{}{to}", " ".repeat(gap));
+        writeln!(out, "{}", pal.paint(pal.faint, axis))?;
+        if self.untimed > 0 {
+            let text = format!("{} records without a timestamp", self.untimed);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+}
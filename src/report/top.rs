@@ -0,0 +1,104 @@
+//! `--top N --by FIELD`: the most frequent values of a field, in bounded memory.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::filter::Line;
+use crate::report::{Report, group_key, write_bar};
+
+/// Space-saving counters (Metwally et al.): at most `capacity` values are tracked; a new value
+/// replaces the least frequent one and inherits its count as possible overcount. Values more
+/// frequent than `total / capacity` are guaranteed to be tracked.
+pub struct Top {
+    n: usize,
+    by: String,
+    capacity: usize,
+    /// Count and possible overcount per tracked value.
+    counts: HashMap<String, (u64, u64)>,
+    /// The tracked values ordered by count, to find the one to replace.
+    by_count: BTreeSet<(u64, String)>,
+    /// Records with the field.
+    total: u64,
+    /// Records without it.
+    missing: u64,
+}
+
+impl Top {
+    pub fn new(n: usize, by: String) -> Self {
+        Self {
+            n,
+            by,
+            capacity: (n * 10).max(1000),
+            counts: HashMap::new(),
+            by_count: BTreeSet::new(),
+            total: 0,
+            missing: 0,
+        }
+    }
+
+    fn add(&mut self, value: String) {
+        self.total += 1;
+        if let Some((count, _)) = self.counts.get_mut(&value) {
+            self.by_count.remove(&(*count, value.clone()));
+            *count += 1;
+            self.by_count.insert((*count, value));
+            return;
+        }
+        let (count, error) = if self.counts.len() < self.capacity {
+            (1, 0)
+        } else {
+            let (min, evicted) = self.by_count.pop_first().expect("full");
+            self.counts.remove(&evicted);
+            (min + 1, min)
+        };
+        self.by_count.insert((count, value.clone()));
+        self.counts.insert(value, (count, error));
+    }
+}
+
+impl Report for Top {
+    fn record(&mut self, line: &Line<'_>) {
+        match group_key(line, &self.by) {
+            Some(value) => self.add(value),
+            None => self.missing += 1,
+        }
+    }
+
+    fn write(&self, ctx: &RenderCtx, out: &mut dyn Write) -> io::Result<()> {
+        let pal = ctx.pal;
+        let mut top: Vec<_> = self.by_count.iter().collect();
+        top.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        top.truncate(self.n);
+        let width = top
+            .iter()
+            .map(|(_, v)| v.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(self.by.len());
+        let max = top.first().map_or(0, |(count, _)| *count);
+        writeln!(
+            out,
+            "{}",
+            pal.paint(
+                pal.faint,
+                format!("{:<width$} {:>9} {:>6}", self.by, "count", "share")
+            )
+        )?;
+        for (count, value) in top {
+            let (_, error) = self.counts[value];
+            // Counts of values that replaced others are upper bounds
+            let approx = if error > 0 { "≈" } else { "" };
+            let count_text = format!("{approx}{count}");
+            let share = *count as f64 * 100.0 / self.total.max(1) as f64;
+            write!(out, "{value:<width$} {count_text:>9} {share:>5.1}% ")?;
+            write_bar(out, ctx, &[(*count, pal.info)], max)?;
+            writeln!(out)?;
+        }
+        if self.missing > 0 {
+            let text = format!("{} records without {}", self.missing, self.by);
+            writeln!(out, "{}", pal.paint(pal.faint, text))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Byte counts for `--humanize` and `--max-memory`.
+
+/// `512 B`, `1.4 KiB`, `23 MiB`: one decimal below 10, binary prefixes.
+pub fn humanize_bytes(n: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut size = n as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{size:.1} {}", UNITS[unit])
+    } else {
+        format!("{size:.0} {}", UNITS[unit])
+    }
+}
+
+/// Whether a numeric field holds a byte count, judged by its name
+/// (`bytes_sent`, `body_bytes`, `content_length`, `response_size`, …).
+pub fn is_size_field(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    let key = key.rsplit('.').next().unwrap_or(&key);
+    key.contains("bytes") || key.ends_with("size") || key.ends_with("_length")
+}
+
+/// Parse a size such as `512M`, `1.5GiB`, or `2000000` (bytes); the units are binary. Intended for
+/// use as a clap `value_parser`.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("`{s}`: expected a size such as 512M or 2G"))?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let shift = match unit.trim_end_matches('b').trim_end_matches('i') {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return Err(format!("`{s}`: unknown unit, expected K, M, G, or T")),
+    };
+    Ok((value * (1u64 << shift) as f64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("2000000"), Ok(2_000_000));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("64kb"), Ok(64 << 10));
+        assert_eq!(parse_size("2 G"), Ok(2 << 30));
+        assert_eq!(parse_size("1T"), Ok(1 << 40));
+        for invalid in ["", "M", "12X", "1.2.3M", "-1M"] {
+            assert!(parse_size(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn humanized_sizes() {
+        assert_eq!(humanize_bytes(512), "512 B");
+        assert_eq!(humanize_bytes(1434), "1.4 KiB");
+        assert_eq!(humanize_bytes(23 << 20), "23 MiB");
+        assert_eq!(humanize_bytes(u64::MAX), "16 EiB");
+    }
+
+    #[test]
+    fn size_fields() {
+        for key in [
+            "bytes_sent",
+            "body_bytes",
+            "http.content_length",
+            "response_size",
+        ] {
+            assert!(is_size_field(key), "{key}");
+        }
+        for key in ["status", "size_class", "length"] {
+            assert!(!is_size_field(key), "{key}");
+        }
+    }
+}
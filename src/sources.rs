@@ -0,0 +1,237 @@
+//! Remote log sources that feed the line pipeline in addition to local files and stdin.
+//!
+//! Every source hands complete log lines (one JSON document or raw text line each)
+//! to a [`LineSink`], so they all share the same sniffing and rendering path.
+
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "aws")]
+pub mod cloudwatch;
+pub mod es;
+pub mod gcp;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod loki;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "redis")]
+pub mod redis_streams;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sse;
+
+use serde_json::Value;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::encoding::{self, Encoding};
+use crate::to_io_err;
+
+/// Callback receiving one log line at a time, without the trailing newline, plus an
+/// optional source annotation (e.g. a Kafka partition/offset) rendered dimmed in front of it.
+pub type LineSink<'a> = dyn FnMut(&[u8], Option<&str>) -> io::Result<()> + 'a;
+
+/// Returns `true` if the input argument names an S3 object or key prefix.
+pub fn is_s3_url(path: &str) -> bool {
+    path.starts_with("s3://")
+}
+
+/// Returns `true` if the input argument is a named pipe (always `false` off Unix).
+pub fn is_fifo(path: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Position `file` at the start of its last `n` non-empty lines by scanning backwards from
+/// the end, so `--tail` doesn't read all of a large file.
+///
+/// Returns the encoding sniffed from the start of the file, which the lines are scanned in: a
+/// UTF-16 file is searched for whole newline code units, and read from there with
+/// [`encoding::decode_as`].
+pub fn seek_to_last_lines(file: &mut (impl Read + Seek), n: usize) -> io::Result<Encoding> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut head = Vec::with_capacity(4);
+    file.take(4).read_to_end(&mut head)?;
+    let (encoding, bom) = encoding::sniff(&head);
+    let bom = bom as u64;
+    let width = match encoding {
+        Encoding::Utf8 => 1,
+        Encoding::Utf16 { .. } => 2,
+    };
+    let unit = |b: &[u8]| match encoding {
+        Encoding::Utf8 => u16::from(b[0]),
+        Encoding::Utf16 { big_endian: false } => u16::from_le_bytes([b[0], b[1]]),
+        Encoding::Utf16 { big_endian: true } => u16::from_be_bytes([b[0], b[1]]),
+    };
+    let mut end = file.seek(SeekFrom::End(0))?;
+    if n == 0 {
+        return Ok(encoding);
+    }
+    // A UTF-16 file cut short ends in half a code unit
+    end -= (end - bom) % width;
+    let mut lines = 0;
+    // Whether the line after the newline being looked for has any content
+    let mut content = false;
+    let mut chunk = vec![0; CHUNK as usize];
+    while end > bom {
+        let start = end.saturating_sub(CHUNK).max(bom);
+        let chunk = &mut chunk[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        for (i, u) in chunk
+            .chunks_exact(width as usize)
+            .map(unit)
+            .enumerate()
+            .rev()
+        {
+            match u {
+                0x0A if content => {
+                    lines += 1;
+                    content = false;
+                    if lines == n {
+                        file.seek(SeekFrom::Start(start + (i as u64 + 1) * width))?;
+                        return Ok(encoding);
+                    }
+                }
+                0x0A | 0x0D => {}
+                _ => content = true,
+            }
+        }
+        end = start;
+    }
+    file.seek(SeekFrom::Start(bom))?;
+    Ok(encoding)
+}
+
+/// Error for inputs that need a cargo feature this binary was built without.
+#[allow(dead_code)]
+pub fn feature_disabled(input: &str, feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{input}: jlo was built without the `{feature}` feature"),
+    )
+}
+
+/// Runtime and shared AWS configuration (region, credentials) for the AWS-backed sources.
+#[cfg(any(feature = "aws", feature = "s3"))]
+pub(crate) fn aws_runtime() -> io::Result<(tokio::runtime::Runtime, aws_config::SdkConfig)> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let config = rt.block_on(aws_config::load_from_env());
+    Ok((rt, config))
+}
+
+/// HTTP agent for the API-backed sources. Non-2xx responses are returned as
+/// regular responses so that [`read_json`] can include the server's error body.
+pub(crate) fn http_agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into()
+}
+
+/// Read a JSON response body, turning non-2xx statuses into errors carrying the body text.
+pub(crate) fn read_json(
+    resp: Result<ureq::http::Response<ureq::Body>, ureq::Error>,
+) -> io::Result<Value> {
+    let mut resp = resp.map_err(to_io_err)?;
+    let status = resp.status();
+    let body = resp.body_mut().with_config().limit(u64::MAX);
+    if !status.is_success() {
+        let text = body.read_to_string().unwrap_or_default();
+        return Err(io::Error::other(format!("HTTP {status}: {}", text.trim())));
+    }
+    body.read_json().map_err(to_io_err)
+}
+
+/// Value for a basic `Authorization` header from `user:password` credentials.
+pub(crate) fn basic_auth(credentials: &str) -> String {
+    use base64::Engine;
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(credentials)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// The text from where `--tail n` starts reading.
+    fn tail(bytes: &[u8], n: usize) -> (Encoding, String) {
+        let mut file = Cursor::new(bytes);
+        let encoding = seek_to_last_lines(&mut file, n).unwrap();
+        let mut out = String::new();
+        encoding::decode_as(file, encoding)
+            .read_to_string(&mut out)
+            .unwrap();
+        (encoding, out)
+    }
+
+    fn utf16(s: &str, big_endian: bool) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn last_lines() {
+        let text = b"a\nb\n\n\nc\r\nd\n";
+        assert_eq!(tail(text, 1).1, "d\n");
+        assert_eq!(tail(text, 2).1, "c\r\nd\n");
+        // Empty lines don't count
+        assert_eq!(tail(text, 3).1, "b\n\n\nc\r\nd\n");
+        assert_eq!(tail(text, 10).1, "a\nb\n\n\nc\r\nd\n");
+        assert_eq!(tail(text, 0).1, "");
+        assert_eq!(tail(b"a\nb", 1).1, "b");
+        assert_eq!(tail(b"", 1).1, "");
+    }
+
+    #[test]
+    fn last_lines_across_chunks() {
+        let long = "x".repeat(100 * 1024);
+        let text = format!("first\n{long}\nlast\n");
+        assert_eq!(tail(text.as_bytes(), 2).1, format!("{long}\nlast\n"));
+        assert_eq!(tail(text.as_bytes(), 3).1, text);
+    }
+
+    #[test]
+    fn last_lines_of_utf16() {
+        // `\u{a0a}` is `0A 0A` in either byte order, and `\u{10a}` holds a `0A` byte: neither
+        // is a newline
+        let text = "{\"a\":\"\u{a0a}\"}\n{\"b\":\"\u{10a}\"}\r\n{\"c\":3}\n";
+        for big_endian in [false, true] {
+            let encoding = Encoding::Utf16 { big_endian };
+            let bytes = utf16(text, big_endian);
+            assert_eq!(
+                tail(&bytes, 2),
+                (encoding, "{\"b\":\"\u{10a}\"}\r\n{\"c\":3}\n".into())
+            );
+            assert_eq!(tail(&bytes, 3), (encoding, text.into()));
+
+            // Lines not starting with `{"` are read as UTF-16 too, past the byte order mark
+            let bytes = utf16("\u{feff}plain\ntext\n", big_endian);
+            assert_eq!(tail(&bytes, 1), (encoding, "text\n".into()));
+            assert_eq!(tail(&bytes, 5), (encoding, "plain\ntext\n".into()));
+            // Half a code unit at the end
+            let mut cut = bytes.clone();
+            cut.push(0);
+            assert_eq!(tail(&cut, 1).1, "text\n\u{FFFD}");
+        }
+    }
+}
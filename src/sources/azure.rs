@@ -0,0 +1,105 @@
+use clap::Args;
+use serde_json::{Map, Value, json};
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+use super::{LineSink, http_agent, read_json};
+use crate::time::parse_duration;
+
+/// Run a Kusto query against an Azure Log Analytics workspace.
+#[derive(Args, Debug)]
+pub struct AzureArgs {
+    /// Workspace (customer) ID
+    #[arg(long, env = "AZURE_LOG_ANALYTICS_WORKSPACE")]
+    workspace: String,
+
+    /// Kusto query, e.g. `AppTraces | where SeverityLevel >= 2 | order by TimeGenerated asc`
+    #[arg(long)]
+    kusto: String,
+
+    /// Time range the query covers
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    since: Duration,
+
+    /// Bearer token; defaults to `az account get-access-token`
+    #[arg(long, env = "AZURE_LOG_ANALYTICS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+/// Feed each result row to `sink` as a JSON object keyed by column name.
+///
+/// Null and empty columns are dropped, since Log Analytics tables are wide and sparse.
+pub fn run(args: &AzureArgs, sink: &mut LineSink) -> io::Result<()> {
+    let token = match &args.token {
+        Some(t) => t.clone(),
+        None => az_access_token()?,
+    };
+    let url = format!(
+        "https://api.loganalytics.io/v1/workspaces/{}/query",
+        args.workspace
+    );
+    let resp = read_json(
+        http_agent()
+            .post(&url)
+            .header("Authorization", format!("Bearer {token}"))
+            .send_json(json!({
+                "query": args.kusto,
+                "timespan": format!("PT{}S", args.since.as_secs()),
+            })),
+    )?;
+
+    for table in resp
+        .get("tables")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let columns: Vec<&str> = table
+            .get("columns")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|c| c.get("name").and_then(Value::as_str).unwrap_or_default())
+            .collect();
+        for row in table
+            .get("rows")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_array)
+        {
+            let record: Map<String, Value> = columns
+                .iter()
+                .zip(row)
+                .filter(|(_, v)| !v.is_null() && v.as_str() != Some(""))
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect();
+            sink(&serde_json::to_vec(&record)?, None)?;
+        }
+    }
+    Ok(())
+}
+
+fn az_access_token() -> io::Result<String> {
+    let output = Command::new("az")
+        .args([
+            "account",
+            "get-access-token",
+            "--resource",
+            "https://api.loganalytics.io",
+            "--query",
+            "accessToken",
+            "--output",
+            "tsv",
+        ])
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("running az for an access token: {e}")))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "az account get-access-token failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,129 @@
+use aws_sdk_cloudwatchlogs::Client;
+use aws_sdk_cloudwatchlogs::types::StartLiveTailResponseStream;
+use clap::Args;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{LineSink, aws_runtime};
+use crate::time::parse_duration;
+use crate::to_io_err;
+
+/// Read or live-tail a CloudWatch Logs group.
+#[derive(Args, Debug)]
+pub struct CloudWatchArgs {
+    /// Log group name or ARN
+    #[arg(long)]
+    group: String,
+
+    /// Only read log streams whose name starts with this prefix
+    #[arg(long)]
+    stream_prefix: Option<String>,
+
+    /// CloudWatch filter pattern applied server-side
+    #[arg(long)]
+    filter_pattern: Option<String>,
+
+    /// How far back to read (ignored with --follow)
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    since: Duration,
+
+    /// Stream new events through a live tail session instead of reading history
+    #[arg(short, long)]
+    pub(crate) follow: bool,
+}
+
+/// Feed the `message` payload of every log event to `sink`.
+pub fn run(args: &CloudWatchArgs, sink: &mut LineSink<'_>) -> io::Result<()> {
+    let (rt, config) = aws_runtime()?;
+    let client = Client::new(&config);
+    if args.follow {
+        rt.block_on(live_tail(&client, args, sink))
+    } else {
+        rt.block_on(filter_events(&client, args, sink))
+    }
+}
+
+async fn filter_events(
+    client: &Client,
+    args: &CloudWatchArgs,
+    sink: &mut LineSink<'_>,
+) -> io::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(to_io_err)?;
+    let start = now.saturating_sub(args.since).as_millis() as i64;
+
+    let mut pages = client
+        .filter_log_events()
+        .log_group_identifier(&args.group)
+        .start_time(start)
+        .set_log_stream_name_prefix(args.stream_prefix.clone())
+        .set_filter_pattern(args.filter_pattern.clone())
+        .into_paginator()
+        .send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(to_io_err)?;
+        for event in page.events() {
+            if let Some(message) = event.message() {
+                sink(message.trim_end().as_bytes(), None)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn live_tail(
+    client: &Client,
+    args: &CloudWatchArgs,
+    sink: &mut LineSink<'_>,
+) -> io::Result<()> {
+    // Live tail only accepts ARNs, so resolve plain group names first
+    let group_arn = if args.group.starts_with("arn:") {
+        args.group.clone()
+    } else {
+        resolve_group_arn(client, &args.group).await?
+    };
+
+    let mut req = client
+        .start_live_tail()
+        .log_group_identifiers(group_arn)
+        .set_log_event_filter_pattern(args.filter_pattern.clone());
+    if let Some(prefix) = &args.stream_prefix {
+        req = req.log_stream_name_prefixes(prefix);
+    }
+    let mut output = req.send().await.map_err(to_io_err)?;
+
+    while let Some(event) = output.response_stream.recv().await.map_err(to_io_err)? {
+        if let StartLiveTailResponseStream::SessionUpdate(update) = event {
+            for log_event in update.session_results() {
+                if let Some(message) = log_event.message() {
+                    sink(message.trim_end().as_bytes(), None)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn resolve_group_arn(client: &Client, name: &str) -> io::Result<String> {
+    let mut pages = client
+        .describe_log_groups()
+        .log_group_name_prefix(name)
+        .into_paginator()
+        .send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(to_io_err)?;
+        if let Some(arn) = page
+            .log_groups()
+            .iter()
+            .find(|g| g.log_group_name() == Some(name))
+            .and_then(|g| g.log_group_arn())
+        {
+            return Ok(arn.to_string());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("log group not found: {name}"),
+    ))
+}
@@ -0,0 +1,183 @@
+use clap::Args;
+use serde_json::{Value, json};
+use std::io;
+
+use super::{LineSink, basic_auth, http_agent, read_json};
+
+/// Search an Elasticsearch index and render the matching documents.
+#[derive(Args, Debug)]
+pub struct EsArgs {
+    /// Base URL of the cluster, e.g. `http://localhost:9200`
+    #[arg(long, env = "ELASTICSEARCH_URL")]
+    url: String,
+
+    /// Index, alias, data stream, or pattern to search (e.g. `logs-*`)
+    #[arg(long)]
+    index: String,
+
+    /// Query as JSON query DSL (`{"match": ...}`) or a Lucene query string
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Field the documents are ordered by
+    #[arg(long, default_value = "@timestamp")]
+    sort_field: String,
+
+    /// Documents fetched per request
+    #[arg(long, default_value_t = 1000)]
+    page_size: usize,
+
+    /// Stop after this many documents
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Page through a point-in-time snapshot instead of the live index
+    #[arg(long)]
+    pit: bool,
+
+    /// Basic auth credentials as `user:password`
+    #[arg(long, env = "ELASTICSEARCH_USER", hide_env_values = true)]
+    user: Option<String>,
+
+    /// API key, sent as `Authorization: ApiKey <key>`
+    #[arg(long, env = "ELASTICSEARCH_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+}
+
+/// Run the search, feeding each hit's `_source` to `sink` in timestamp order.
+pub fn run(args: &EsArgs, sink: &mut LineSink) -> io::Result<()> {
+    let es = Client::new(args);
+    let query = match args.query.as_deref().map(str::trim) {
+        None | Some("") => json!({ "match_all": {} }),
+        Some(q) if q.starts_with('{') => serde_json::from_str(q)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("--query: {e}")))?,
+        Some(q) => json!({ "query_string": { "query": q } }),
+    };
+
+    let mut pit = if args.pit {
+        let resp = es.send(
+            "POST",
+            &format!("{}/_pit?keep_alive=1m", es.index_url()),
+            None,
+        )?;
+        resp.get("id").and_then(Value::as_str).map(str::to_string)
+    } else {
+        None
+    };
+
+    let result = search_after_loop(&es, args, &query, &mut pit, sink);
+
+    if let Some(id) = pit {
+        // Best effort: the PIT expires on its own after the keep-alive anyway
+        let _ = es.send(
+            "DELETE",
+            &format!("{}/_pit", es.base),
+            Some(json!({ "id": id })),
+        );
+    }
+    result
+}
+
+fn search_after_loop(
+    es: &Client,
+    args: &EsArgs,
+    query: &Value,
+    pit: &mut Option<String>,
+    sink: &mut LineSink,
+) -> io::Result<()> {
+    let mut search_after: Option<Value> = None;
+    let mut seen = 0usize;
+    loop {
+        let size = match args.limit {
+            Some(limit) if seen >= limit => break,
+            Some(limit) => args.page_size.min(limit - seen),
+            None => args.page_size,
+        };
+
+        // Tiebreaker keeps paging stable for documents sharing a timestamp
+        let tiebreaker = if pit.is_some() { "_shard_doc" } else { "_doc" };
+        let mut body = json!({
+            "size": size,
+            "query": query,
+            "sort": [
+                { &args.sort_field: { "order": "asc", "unmapped_type": "date" } },
+                { tiebreaker: "asc" },
+            ],
+            "track_total_hits": false,
+        });
+        if let Some(sa) = &search_after {
+            body["search_after"] = sa.clone();
+        }
+        let url = if let Some(id) = pit.as_deref() {
+            body["pit"] = json!({ "id": id, "keep_alive": "1m" });
+            format!("{}/_search", es.base)
+        } else {
+            format!("{}/_search", es.index_url())
+        };
+
+        let resp = es.send("POST", &url, Some(body))?;
+        if let Some(id) = resp.get("pit_id").and_then(Value::as_str) {
+            *pit = Some(id.to_string());
+        }
+        let hits = resp
+            .pointer("/hits/hits")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        for hit in hits {
+            if let Some(source) = hit.get("_source") {
+                let line = serde_json::to_vec(source)?;
+                sink(&line, None)?;
+                seen += 1;
+            }
+        }
+
+        match hits.last().and_then(|h| h.get("sort")) {
+            Some(sort) if hits.len() == size => search_after = Some(sort.clone()),
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+struct Client<'a> {
+    agent: ureq::Agent,
+    base: &'a str,
+    index: &'a str,
+    auth: Option<String>,
+}
+
+impl<'a> Client<'a> {
+    fn new(args: &'a EsArgs) -> Self {
+        let auth = match (&args.api_key, &args.user) {
+            (Some(key), _) => Some(format!("ApiKey {key}")),
+            (None, Some(user)) => Some(basic_auth(user)),
+            (None, None) => None,
+        };
+        Self {
+            agent: http_agent(),
+            base: args.url.trim_end_matches('/'),
+            index: &args.index,
+            auth,
+        }
+    }
+
+    fn index_url(&self) -> String {
+        format!("{}/{}", self.base, self.index)
+    }
+
+    fn send(&self, method: &str, url: &str, body: Option<Value>) -> io::Result<Value> {
+        let req = ureq::http::Request::builder()
+            .method(method)
+            .uri(url)
+            .header("Content-Type", "application/json");
+        let req = match &self.auth {
+            Some(auth) => req.header("Authorization", auth),
+            None => req,
+        };
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let req = req.body(body).map_err(crate::to_io_err)?;
+        read_json(self.agent.run(req))
+    }
+}
@@ -0,0 +1,141 @@
+use clap::Args;
+use jiff::Timestamp;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+
+use super::{LineSink, http_agent, read_json};
+use crate::time::parse_duration;
+use crate::to_io_err;
+
+const ENTRIES_LIST_URL: &str = "https://logging.googleapis.com/v2/entries:list";
+
+/// Read entries from Google Cloud Logging.
+#[derive(Args, Debug)]
+pub struct GcpArgs {
+    /// Project to read logs from
+    #[arg(long, env = "CLOUDSDK_CORE_PROJECT")]
+    project: String,
+
+    /// Logging query language filter, e.g. `resource.type="k8s_container"`
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// How far back to read
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    since: Duration,
+
+    /// Stop after this many entries
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Keep polling for new entries
+    #[arg(long)]
+    pub(crate) tail: bool,
+
+    /// Delay between polls with --tail
+    #[arg(long, default_value = "2s", value_parser = parse_duration)]
+    poll_interval: Duration,
+
+    /// OAuth access token; defaults to `gcloud auth print-access-token`
+    #[arg(long, env = "GOOGLE_OAUTH_ACCESS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+/// Feed each `LogEntry` (oldest first) to `sink` as a JSON line.
+pub fn run(args: &GcpArgs, sink: &mut LineSink) -> io::Result<()> {
+    let token = match &args.token {
+        Some(t) => t.clone(),
+        None => gcloud_access_token()?,
+    };
+    let agent = http_agent();
+    let start = Timestamp::now()
+        .checked_sub(args.since)
+        .map_err(to_io_err)?;
+
+    let mut lower_bound = start.to_string();
+    // insertIds already emitted at `lower_bound`, since polling resumes inclusively
+    let mut seen_at_bound: HashSet<String> = HashSet::new();
+    let mut emitted = 0usize;
+    loop {
+        let filter = match &args.filter {
+            Some(f) => format!("({f}) AND timestamp >= \"{lower_bound}\""),
+            None => format!("timestamp >= \"{lower_bound}\""),
+        };
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut body = json!({
+                "resourceNames": [format!("projects/{}", args.project)],
+                "filter": filter,
+                "orderBy": "timestamp asc",
+                "pageSize": 1000,
+            });
+            if let Some(t) = &page_token {
+                body["pageToken"] = Value::String(t.clone());
+            }
+            let resp = read_json(
+                agent
+                    .post(ENTRIES_LIST_URL)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .send_json(&body),
+            )?;
+
+            let entries = resp.get("entries").and_then(Value::as_array);
+            for entry in entries.into_iter().flatten() {
+                let ts = entry.get("timestamp").and_then(Value::as_str);
+                let id = entry.get("insertId").and_then(Value::as_str);
+                if ts == Some(lower_bound.as_str())
+                    && id.is_some_and(|id| seen_at_bound.contains(id))
+                {
+                    continue;
+                }
+                if let Some(ts) = ts
+                    && ts != lower_bound
+                {
+                    lower_bound = ts.to_string();
+                    seen_at_bound.clear();
+                }
+                if let Some(id) = id {
+                    seen_at_bound.insert(id.to_string());
+                }
+
+                sink(&serde_json::to_vec(entry)?, None)?;
+                emitted += 1;
+                if args.limit.is_some_and(|limit| emitted >= limit) {
+                    return Ok(());
+                }
+            }
+
+            page_token = resp
+                .get("nextPageToken")
+                .and_then(Value::as_str)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string);
+            if page_token.is_none() {
+                break;
+            }
+        }
+        if !args.tail {
+            return Ok(());
+        }
+        std::thread::sleep(args.poll_interval);
+    }
+}
+
+fn gcloud_access_token() -> io::Result<String> {
+    let output = Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .output()
+        .map_err(|e| {
+            io::Error::new(e.kind(), format!("running gcloud for an access token: {e}"))
+        })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "gcloud auth print-access-token failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,97 @@
+use clap::Args;
+use rdkafka::Message;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use serde_json::Value;
+use std::io;
+use std::time::Duration;
+
+use super::LineSink;
+use crate::to_io_err;
+
+/// Consume a Kafka topic and render each message as a log record.
+#[derive(Args, Debug)]
+pub struct KafkaArgs {
+    /// Comma-separated bootstrap brokers, e.g. `localhost:9092`
+    #[arg(long, env = "KAFKA_BROKERS")]
+    brokers: String,
+
+    /// Topic to consume
+    #[arg(long)]
+    topic: String,
+
+    /// Start at the earliest available offset instead of only new messages
+    #[arg(long)]
+    from_beginning: bool,
+
+    /// Consumer group ID; defaults to a throwaway group that commits nothing
+    #[arg(long)]
+    group: Option<String>,
+
+    /// Prefix each record with a dimmed `partition@offset` token
+    #[arg(long)]
+    show_offsets: bool,
+
+    /// Extra librdkafka settings as `key=value` (e.g. `security.protocol=ssl`)
+    #[arg(short = 'X', long = "set", value_name = "KEY=VALUE")]
+    settings: Vec<String>,
+}
+
+/// Poll the topic until interrupted. Messages holding several NDJSON lines are split.
+pub fn run(args: &KafkaArgs, sink: &mut LineSink) -> io::Result<()> {
+    let group = args
+        .group
+        .clone()
+        .unwrap_or_else(|| format!("jlo-{}", std::process::id()));
+    let mut config = ClientConfig::new();
+    config
+        .set("bootstrap.servers", &args.brokers)
+        .set("group.id", group)
+        .set("enable.auto.commit", args.group.is_some().to_string())
+        .set(
+            "auto.offset.reset",
+            if args.from_beginning {
+                "earliest"
+            } else {
+                "latest"
+            },
+        );
+    for setting in &args.settings {
+        let (key, value) = setting.split_once('=').ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected KEY=VALUE, got `{setting}`"),
+            )
+        })?;
+        config.set(key, value);
+    }
+
+    let consumer: BaseConsumer = config.create().map_err(to_io_err)?;
+    consumer.subscribe(&[&args.topic]).map_err(to_io_err)?;
+
+    loop {
+        let Some(msg) = consumer.poll(Duration::from_millis(500)) else {
+            continue;
+        };
+        let msg = msg.map_err(to_io_err)?;
+        let Some(payload) = msg.payload() else {
+            continue;
+        };
+        let note = args
+            .show_offsets
+            .then(|| format!("{}@{}", msg.partition(), msg.offset()));
+        // A message holding one pretty-printed document is a record, not lines of one
+        if payload.contains(&b'\n')
+            && let Ok(v) = serde_json::from_slice::<Value>(payload)
+        {
+            sink(&serde_json::to_vec(&v)?, note.as_deref())?;
+            continue;
+        }
+        for line in payload.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if !line.is_empty() {
+                sink(line, note.as_deref())?;
+            }
+        }
+    }
+}
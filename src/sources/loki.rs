@@ -0,0 +1,202 @@
+use clap::Args;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::Value;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tungstenite::Message;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::HeaderValue;
+
+use super::{LineSink, basic_auth, http_agent, read_json};
+use crate::time::parse_duration;
+use crate::to_io_err;
+
+/// Run a LogQL query against Loki and render the returned log lines.
+#[derive(Args, Debug)]
+pub struct LokiArgs {
+    /// Base URL of the Loki server, e.g. `http://localhost:3100`
+    #[arg(long, env = "LOKI_ADDR")]
+    url: String,
+
+    /// LogQL log query, e.g. `{app="api"} |= "error"`
+    query: String,
+
+    /// How far back to query (e.g. `15m`, `1h`, `2d`)
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    since: Duration,
+
+    /// Stop after this many lines (ignored with --tail)
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Keep streaming new lines through the tail websocket
+    #[arg(long)]
+    pub(crate) tail: bool,
+
+    /// Tenant ID sent as `X-Scope-OrgID` for multi-tenant setups
+    #[arg(long, env = "LOKI_ORG_ID")]
+    org_id: Option<String>,
+
+    /// Basic auth credentials as `user:password`
+    #[arg(long, env = "LOKI_USER", hide_env_values = true)]
+    user: Option<String>,
+}
+
+/// Lines requested per `query_range` call.
+const PAGE_SIZE: usize = 1000;
+
+/// Execute the query and feed each line (oldest first) to `sink`.
+pub fn run(args: &LokiArgs, sink: &mut LineSink) -> io::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(to_io_err)?
+        .as_nanos();
+    let start = now.saturating_sub(args.since.as_nanos());
+    if args.tail {
+        tail(args, start, sink)
+    } else {
+        query_range(args, start, now, sink)
+    }
+}
+
+fn query_range(args: &LokiArgs, mut start: u128, end: u128, sink: &mut LineSink) -> io::Result<()> {
+    let agent = http_agent();
+    let url = format!("{}/loki/api/v1/query_range", args.url.trim_end_matches('/'));
+    let mut seen = 0usize;
+    // The lines at `start` passed on already, which the next page returns again
+    let mut boundary: Vec<String> = Vec::new();
+    loop {
+        let page = match args.limit {
+            Some(limit) if seen >= limit => break,
+            Some(limit) => PAGE_SIZE.min(limit - seen),
+            None => PAGE_SIZE,
+        } + boundary.len();
+        let mut req = agent
+            .get(&url)
+            .query("query", &args.query)
+            .query("start", start.to_string())
+            .query("end", end.to_string())
+            .query("limit", page.to_string())
+            .query("direction", "forward");
+        if let Some(org) = &args.org_id {
+            req = req.header("X-Scope-OrgID", org);
+        }
+        if let Some(user) = &args.user {
+            req = req.header("Authorization", basic_auth(user));
+        }
+        let resp = read_json(req.call())?;
+
+        if let Some(kind) = resp.pointer("/data/resultType").and_then(Value::as_str)
+            && kind != "streams"
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected a log query, but Loki returned a `{kind}` result"),
+            ));
+        }
+        let entries = collect_entries(resp.pointer("/data/result"));
+        let mut emitted = 0;
+        for (ts, line) in &entries {
+            if *ts == start
+                && let Some(i) = boundary.iter().position(|l| l == line)
+            {
+                boundary.swap_remove(i);
+                continue;
+            }
+            sink(line.as_bytes(), None)?;
+            emitted += 1;
+        }
+        seen += emitted;
+
+        match entries.last() {
+            // Timestamps are inclusive: resume at the last entry's, as more entries may share it
+            Some(&(ts, _)) if entries.len() == page && emitted > 0 => {
+                boundary = entries
+                    .into_iter()
+                    .filter(|&(t, _)| t == ts)
+                    .map(|(_, line)| line)
+                    .collect();
+                start = ts;
+            }
+            // A page of nothing but lines seen already shouldn't come back again
+            Some(&(ts, _)) if entries.len() == page => {
+                boundary.clear();
+                start = ts + 1;
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+fn tail(args: &LokiArgs, start: u128, sink: &mut LineSink) -> io::Result<()> {
+    let base = args.url.trim_end_matches('/');
+    let base = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        base.to_string()
+    };
+    let url = format!(
+        "{base}/loki/api/v1/tail?query={}&start={start}",
+        utf8_percent_encode(&args.query, NON_ALPHANUMERIC)
+    );
+
+    let mut req = url.into_client_request().map_err(to_io_err)?;
+    let headers = req.headers_mut();
+    if let Some(org) = &args.org_id {
+        headers.insert(
+            "X-Scope-OrgID",
+            HeaderValue::from_str(org).map_err(to_io_err)?,
+        );
+    }
+    if let Some(user) = &args.user {
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&basic_auth(user)).map_err(to_io_err)?,
+        );
+    }
+
+    let (mut socket, _) = tungstenite::connect(req).map_err(to_io_err)?;
+    loop {
+        let msg = match socket.read() {
+            Ok(msg) => msg,
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(to_io_err(e)),
+        };
+        let Message::Text(text) = msg else {
+            if msg.is_close() {
+                return Ok(());
+            }
+            continue;
+        };
+        let resp: Value = serde_json::from_str(text.as_str())?;
+        for (_, line) in collect_entries(resp.get("streams")) {
+            sink(line.as_bytes(), None)?;
+        }
+        if let Some(dropped) = resp.get("dropped_entries").and_then(Value::as_array)
+            && !dropped.is_empty()
+        {
+            eprintln!("jlo: Loki dropped {} entries while tailing", dropped.len());
+        }
+    }
+}
+
+/// Flatten Loki streams into `(timestamp_ns, line)` pairs ordered by time.
+fn collect_entries(streams: Option<&Value>) -> Vec<(u128, String)> {
+    let mut entries: Vec<(u128, String)> = streams
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("values").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|entry| {
+            let ts = entry.get(0)?.as_str()?.parse().ok()?;
+            let line = entry.get(1)?.as_str()?;
+            Some((ts, line.to_string()))
+        })
+        .collect();
+    entries.sort_by_key(|(ts, _)| *ts);
+    entries
+}
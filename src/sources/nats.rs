@@ -0,0 +1,46 @@
+use clap::Args;
+use futures_util::StreamExt;
+use std::io;
+
+use super::LineSink;
+use crate::to_io_err;
+
+/// Subscribe to a NATS subject and render each message as a log record.
+#[derive(Args, Debug)]
+pub struct NatsArgs {
+    /// Server URL, e.g. `nats://localhost:4222`
+    #[arg(long, env = "NATS_URL", default_value = "nats://localhost:4222")]
+    url: String,
+
+    /// Subject to subscribe to; wildcards such as `logs.>` are allowed
+    #[arg(long)]
+    subject: String,
+
+    /// Prefix each record with the dimmed subject it was published on
+    #[arg(long)]
+    show_subject: bool,
+}
+
+/// Receive messages until the connection closes or jlo is interrupted.
+pub fn run(args: &NatsArgs, sink: &mut LineSink) -> io::Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async {
+        let client = async_nats::connect(&args.url).await.map_err(to_io_err)?;
+        let mut sub = client
+            .subscribe(args.subject.clone())
+            .await
+            .map_err(to_io_err)?;
+        while let Some(msg) = sub.next().await {
+            let note = args.show_subject.then_some(msg.subject.as_str());
+            for line in msg.payload.split(|&b| b == b'\n') {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                if !line.is_empty() {
+                    sink(line, note)?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,69 @@
+use clap::Args;
+use redis::Commands;
+use redis::streams::{StreamReadOptions, StreamReadReply};
+use serde_json::{Map, Value};
+use std::io;
+
+use super::LineSink;
+use crate::to_io_err;
+
+/// Read entries from a Redis Stream and render each one as a log record.
+#[derive(Args, Debug)]
+pub struct RedisArgs {
+    /// Connection URL, e.g. `redis://localhost:6379/0`
+    #[arg(long, env = "REDIS_URL", default_value = "redis://localhost:6379")]
+    url: String,
+
+    /// Stream key to read
+    #[arg(long)]
+    stream: String,
+
+    /// Entry field holding the log line. By default a single-field entry is
+    /// used as-is and multi-field entries are rendered as a JSON object.
+    #[arg(long)]
+    field: Option<String>,
+
+    /// Start with the oldest entry instead of only new ones
+    #[arg(long)]
+    from_beginning: bool,
+}
+
+/// Block on `XREAD` until interrupted, following the stream as entries arrive.
+pub fn run(args: &RedisArgs, sink: &mut LineSink) -> io::Result<()> {
+    let client = redis::Client::open(args.url.as_str()).map_err(to_io_err)?;
+    let mut con = client.get_connection().map_err(to_io_err)?;
+    let opts = StreamReadOptions::default().block(5_000).count(500);
+
+    let mut last_id = if args.from_beginning { "0" } else { "$" }.to_string();
+    loop {
+        let reply: Option<StreamReadReply> = con
+            .xread_options(&[&args.stream], &[&last_id], &opts)
+            .map_err(to_io_err)?;
+        for key in reply.into_iter().flat_map(|r| r.keys) {
+            for entry in key.ids {
+                let line = match &args.field {
+                    Some(field) => entry.get::<String>(field),
+                    None if entry.map.len() == 1 => entry.map.values().next().and_then(as_string),
+                    None => {
+                        // Sort fields so repeated entries render identically
+                        let mut fields: Vec<_> = entry.map.iter().collect();
+                        fields.sort_by_key(|(k, _)| k.as_str());
+                        let obj: Map<String, Value> = fields
+                            .into_iter()
+                            .filter_map(|(k, v)| Some((k.clone(), Value::String(as_string(v)?))))
+                            .collect();
+                        Some(Value::Object(obj).to_string())
+                    }
+                };
+                if let Some(line) = line {
+                    sink(line.as_bytes(), None)?;
+                }
+                last_id = entry.id;
+            }
+        }
+    }
+}
+
+fn as_string(v: &redis::Value) -> Option<String> {
+    redis::from_redis_value_ref(v).ok()
+}
@@ -0,0 +1,119 @@
+use aws_sdk_s3::Client;
+use aws_sdk_s3::primitives::ByteStream;
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader, Read};
+use tokio::runtime::Runtime;
+
+use super::aws_runtime;
+use crate::to_io_err;
+
+/// A parsed `s3://bucket/key` URL. The key may be empty or a prefix.
+struct S3Url<'a> {
+    bucket: &'a str,
+    key: &'a str,
+}
+
+impl<'a> S3Url<'a> {
+    fn parse(url: &'a str) -> io::Result<Self> {
+        let rest = url.strip_prefix("s3://").ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("not an S3 URL: {url}"))
+        })?;
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("missing bucket in S3 URL: {url}"),
+            ));
+        }
+        Ok(Self { bucket, key })
+    }
+}
+
+/// Stream every object addressed by `url` through `f`, one object at a time.
+///
+/// If an object with exactly the given key exists, only that object is read;
+/// otherwise the key is treated as a prefix and all matching objects are read
+/// in lexicographic key order. Gzip-compressed objects are decompressed on the fly.
+pub fn for_each_object<F>(url: &str, mut f: F) -> io::Result<()>
+where
+    F: FnMut(&mut dyn BufRead) -> io::Result<()>,
+{
+    let url = S3Url::parse(url)?;
+    let (rt, config) = aws_runtime()?;
+    let client = Client::new(&config);
+
+    let keys = rt.block_on(list_keys(&client, &url))?;
+    if keys.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no S3 objects found at s3://{}/{}", url.bucket, url.key),
+        ));
+    }
+
+    for key in keys {
+        let obj = rt
+            .block_on(client.get_object().bucket(url.bucket).key(&key).send())
+            .map_err(to_io_err)?;
+        let gzip_encoded = obj.content_encoding() == Some("gzip");
+        let body = BodyReader {
+            rt: &rt,
+            body: obj.body,
+            chunk: Default::default(),
+        };
+        let mut reader = BufReader::with_capacity(64 * 1024, body);
+        // Sniff the gzip magic so that objects without metadata are handled too
+        let is_gzip =
+            gzip_encoded || key.ends_with(".gz") || reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+        if is_gzip {
+            f(&mut BufReader::new(MultiGzDecoder::new(reader)))?;
+        } else {
+            f(&mut reader)?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the object keys for a URL: the exact key if present, else all keys under the prefix.
+async fn list_keys(client: &Client, url: &S3Url<'_>) -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut pages = client
+        .list_objects_v2()
+        .bucket(url.bucket)
+        .prefix(url.key)
+        .into_paginator()
+        .send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(to_io_err)?;
+        for obj in page.contents() {
+            if let Some(key) = obj.key() {
+                if key == url.key {
+                    return Ok(vec![key.to_string()]);
+                }
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+/// Adapts an async S3 body stream to blocking `Read` by driving it on the runtime chunk by chunk.
+struct BodyReader<'rt> {
+    rt: &'rt Runtime,
+    body: ByteStream,
+    chunk: bytes::Bytes,
+}
+
+impl Read for BodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.rt.block_on(self.body.try_next()).map_err(to_io_err)? {
+                Some(next) => self.chunk = next,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk.split_to(n));
+        Ok(n)
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::{self, BufRead, BufReader};
+use std::time::Duration;
+
+use super::{LineSink, http_agent};
+use crate::to_io_err;
+
+/// Reconnect delay until the server sends its own `retry:` hint.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+/// Consume a `text/event-stream` endpoint, feeding every `data:` payload to `sink`.
+///
+/// Dropped connections are re-established after the server's `retry:` delay,
+/// resuming from the last seen event via the `Last-Event-ID` header.
+pub fn run(url: &str, sink: &mut LineSink) -> io::Result<()> {
+    let agent = http_agent();
+    let mut last_event_id: Option<String> = None;
+    let mut retry = DEFAULT_RETRY;
+
+    loop {
+        let mut req = agent
+            .get(url)
+            .header("Accept", "text/event-stream")
+            .header("Cache-Control", "no-cache");
+        if let Some(id) = &last_event_id {
+            req = req.header("Last-Event-ID", id);
+        }
+
+        match req.call() {
+            Ok(resp) if resp.status() == 204 => return Ok(()),
+            Ok(resp) if !resp.status().is_success() => {
+                return Err(io::Error::other(format!("{url}: HTTP {}", resp.status())));
+            }
+            Ok(resp) => {
+                let reader = BufReader::new(resp.into_body().into_reader());
+                if let Err(e) = read_events(reader, &mut last_event_id, &mut retry, sink) {
+                    // Errors from the sink (e.g. a closed stdout) end the stream for good
+                    if e.kind() != io::ErrorKind::ConnectionAborted {
+                        return Err(e);
+                    }
+                    eprintln!("jlo: event stream interrupted: {e}");
+                }
+            }
+            Err(e) => eprintln!("jlo: connecting to {url}: {}", to_io_err(e)),
+        }
+        std::thread::sleep(retry);
+    }
+}
+
+/// Parse events until the connection ends. Read failures are reported as
+/// `ConnectionAborted` so the caller can tell them apart from sink errors.
+fn read_events<R: BufRead>(
+    mut reader: R,
+    last_event_id: &mut Option<String>,
+    retry: &mut Duration,
+    sink: &mut LineSink,
+) -> io::Result<()> {
+    let mut data: Vec<u8> = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e))?;
+        if n == 0 {
+            return Ok(());
+        }
+        while matches!(line.last(), Some(b'\n' | b'\r')) {
+            line.pop();
+        }
+
+        // A blank line dispatches the buffered event
+        if line.is_empty() {
+            for payload in data.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+                sink(payload, None)?;
+            }
+            data.clear();
+            continue;
+        }
+
+        let (field, value) = match line.iter().position(|&b| b == b':') {
+            Some(0) => continue, // comment / keep-alive
+            Some(i) => {
+                let value = &line[i + 1..];
+                (&line[..i], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (&line[..], &b""[..]),
+        };
+        match field {
+            b"data" => {
+                if !data.is_empty() {
+                    data.push(b'\n');
+                }
+                data.extend_from_slice(value);
+            }
+            b"id" if !value.contains(&0) => {
+                *last_event_id = Some(String::from_utf8_lossy(value).into_owned());
+            }
+            b"retry" => {
+                if let Some(ms) = std::str::from_utf8(value).ok().and_then(|v| v.parse().ok()) {
+                    *retry = Duration::from_millis(ms);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Routing of lines into one file per level (or other field value) for `--split-by`.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::filter::Line;
+use crate::output::OutputFormat;
+use crate::pipeline::Renderer;
+
+/// Writes each line to `<dir>/<value>.<ext>`, where `value` is the line's value of `key`.
+///
+/// Lines without the field (including non-JSON lines) go to `unknown.<ext>`.
+pub struct Split {
+    key: String,
+    dir: PathBuf,
+    /// Write the original input lines instead of rendering them.
+    raw: bool,
+    renderer: Renderer,
+    extension: &'static str,
+    files: HashMap<String, BufWriter<File>>,
+}
+
+impl Split {
+    /// `renderer` should be colorless; see [`crate::pipeline::Pipeline::plain_renderer`].
+    pub fn new(
+        key: String,
+        dir: PathBuf,
+        raw: bool,
+        renderer: Renderer,
+        format: OutputFormat,
+    ) -> io::Result<Self> {
+        let extension = match format {
+            _ if raw => "ndjson",
+            OutputFormat::Json => "ndjson",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Html | OutputFormat::HtmlFragment => "html",
+            OutputFormat::Sqlite => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--split-by needs --split-raw with --output sqlite",
+                ));
+            }
+            OutputFormat::Pretty | OutputFormat::Logfmt => "log",
+        };
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            key,
+            dir,
+            raw,
+            renderer,
+            extension,
+            files: HashMap::new(),
+        })
+    }
+
+    pub fn write(&mut self, line: &Line<'_>) -> io::Result<()> {
+        let value = line
+            .record()
+            .and_then(|rec| rec.lookup(line.value(), &self.key))
+            .map(|value| match value {
+                Value::String(s) => s,
+                other => other.to_string(),
+            });
+        let name = file_stem(value.as_deref().unwrap_or("unknown"));
+
+        let out = match self.files.get_mut(&name) {
+            Some(out) => out,
+            None => {
+                let path = self.dir.join(format!("{name}.{}", self.extension));
+                let mut out = BufWriter::new(File::create(&path)?);
+                if !self.raw {
+                    self.renderer.write_header(&mut out)?;
+                }
+                self.files.entry(name).or_insert(out)
+            }
+        };
+        if self.raw {
+            out.write_all(line.buf)?;
+            out.write_all(b"\n")
+        } else {
+            self.renderer.render(line, None, out)
+        }
+    }
+
+    /// Complete and flush every file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        for out in self.files.values_mut() {
+            if !self.raw {
+                self.renderer.write_footer(out)?;
+            }
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Field values become file names: keep them to a safe character set.
+fn file_stem(value: &str) -> String {
+    let stem: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match stem.trim_start_matches('.') {
+        "" => "_".to_string(),
+        s => s.to_string(),
+    }
+}
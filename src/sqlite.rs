@@ -0,0 +1,95 @@
+//! `--output sqlite`: normalized records inserted into a SQLite database.
+
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+use std::io;
+use std::path::Path;
+
+use crate::filter::Line;
+use crate::protocols::Record;
+use crate::to_io_err;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS logs (
+        id       INTEGER PRIMARY KEY,
+        ts       TEXT,
+        level    TEXT,
+        message  TEXT,
+        logger   TEXT,
+        protocol TEXT,
+        fields   TEXT -- JSON object of the remaining fields
+    );
+    CREATE INDEX IF NOT EXISTS logs_ts ON logs (ts);
+    CREATE INDEX IF NOT EXISTS logs_level ON logs (level);
+";
+
+const INSERT: &str = "INSERT INTO logs (ts, level, message, logger, protocol, fields) \
+                      VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
+
+/// Rows are committed in batches of this size (and at the end of the run).
+const BATCH: usize = 1000;
+
+pub struct SqliteSink {
+    conn: Connection,
+    pending: usize,
+}
+
+impl SqliteSink {
+    /// Open (or create) the database and its `logs` table; existing rows are kept.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(to_io_err)?;
+        conn.execute_batch(SCHEMA).map_err(to_io_err)?;
+        conn.execute_batch("BEGIN").map_err(to_io_err)?;
+        Ok(Self { conn, pending: 0 })
+    }
+
+    /// Insert one input line; non-JSON lines are stored as the message only.
+    pub fn insert(&mut self, line: &Line<'_>) -> io::Result<()> {
+        // The protocol that actually produced the record
+        let unparsed;
+        let (protocol, rec) = match (line.protocol(), line.record()) {
+            (Some(proto), Some(rec)) => (Some(proto.name()), rec),
+            _ => {
+                unparsed = Record {
+                    message: Some(String::from_utf8_lossy(line.buf).into_owned()),
+                    ..Default::default()
+                };
+                (None, &unparsed)
+            }
+        };
+        let fields = (!rec.fields.is_empty()).then(|| {
+            let map: Map<String, Value> = rec.fields.iter().cloned().collect();
+            Value::Object(map).to_string()
+        });
+        self.conn
+            .prepare_cached(INSERT)
+            .and_then(|mut stmt| {
+                stmt.execute((
+                    rec.timestamp.as_deref(),
+                    rec.level.map(|l| l.as_str().to_ascii_lowercase()),
+                    rec.message.as_deref(),
+                    rec.logger.as_deref(),
+                    protocol,
+                    fields,
+                ))
+            })
+            .map_err(to_io_err)?;
+
+        self.pending += 1;
+        if self.pending >= BATCH {
+            self.conn
+                .execute_batch("COMMIT; BEGIN")
+                .map_err(to_io_err)?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.conn
+            .execute_batch("COMMIT; BEGIN")
+            .map_err(to_io_err)?;
+        self.pending = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! `--stats`: a summary of what a run read, skipped, and showed, printed to stderr at the end;
+//! without it, a note on lines that weren't JSON or fell back to the generic renderer.
+
+use jiff::Timestamp;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::filter::Line;
+use crate::protocols::generic::Generic;
+use crate::protocols::{JsonProtocol, Level};
+use crate::size::humanize_bytes;
+use crate::time::{self, humanize_duration};
+
+/// How the lines of a run were read: as not JSON, or by which protocol.
+#[derive(Clone, Default)]
+pub struct Tally {
+    /// Input lines, however many records each holds.
+    lines: u64,
+    /// Records: a line each, or each of several documents on a line.
+    records: u64,
+    /// Lines that aren't JSON.
+    invalid: u64,
+    /// JSON records per protocol rendering them, in order of first appearance; `generic` also
+    /// counts the records no other protocol could make sense of.
+    protocols: Vec<(&'static str, u64)>,
+}
+
+impl Tally {
+    /// Count one input line, before its records.
+    pub fn line(&mut self) {
+        self.lines += 1;
+    }
+
+    /// Count one record, by the protocol rendering it if it is JSON.
+    pub fn count(&mut self, line: &Line<'_>) {
+        self.records += 1;
+        match line.protocol() {
+            Some(p) => self.add(p.name(), 1),
+            None => self.invalid += 1,
+        }
+    }
+
+    /// Add the counts of another part of the input, e.g. a `--jobs` batch.
+    pub fn merge(&mut self, other: &Tally) {
+        self.lines += other.lines;
+        self.records += other.records;
+        self.invalid += other.invalid;
+        for &(name, n) in &other.protocols {
+            self.add(name, n);
+        }
+    }
+
+    fn add(&mut self, name: &'static str, n: u64) {
+        match self.protocols.iter_mut().find(|(p, _)| *p == name) {
+            Some((_, count)) => *count += n,
+            None => self.protocols.push((name, n)),
+        }
+    }
+
+    fn generic(&self) -> u64 {
+        self.protocols
+            .iter()
+            .find(|(name, _)| *name == Generic.name())
+            .map_or(0, |(_, n)| *n)
+    }
+
+    /// Write a note when lines weren't JSON and so were skipped, with how the others were
+    /// rendered, e.g.
+    ///
+    /// ```text
+    /// jlo: of 10 lines, 5 weren't JSON and 3 fell back to generic JSON; nginx 2 (more with --stats)
+    /// ```
+    pub fn write_note(&self, out: &mut dyn Write) -> io::Result<()> {
+        if self.invalid == 0 {
+            return Ok(());
+        }
+        let generic = self.generic();
+        let mut skipped = Vec::new();
+        if self.invalid > 0 {
+            let were = if self.invalid == 1 {
+                "wasn't"
+            } else {
+                "weren't"
+            };
+            skipped.push(format!("{} {were} JSON", self.invalid));
+        }
+        if generic > 0 {
+            skipped.push(format!("{generic} fell back to generic JSON"));
+        }
+        let matched: Vec<_> = self
+            .protocols
+            .iter()
+            .filter(|(name, _)| *name != Generic.name())
+            .map(|(name, n)| format!("{name} {n}"))
+            .collect();
+        let matched = if matched.is_empty() {
+            String::new()
+        } else {
+            format!("; {}", matched.join(", "))
+        };
+        writeln!(
+            out,
+            "jlo: of {}, {}{matched} (more with --stats)",
+            self.read(),
+            skipped.join(" and "),
+        )
+    }
+
+    /// How many lines were read, and records if some lines held several, e.g. `6 lines holding 7
+    /// records`.
+    fn read(&self) -> String {
+        let lines = if self.lines == 1 { "line" } else { "lines" };
+        if self.records > self.lines {
+            format!("{} {lines} holding {} records", self.lines, self.records)
+        } else {
+            format!("{} {lines}", self.lines)
+        }
+    }
+}
+
+/// Counters collected over the whole run.
+pub struct Stats {
+    started: Instant,
+    bytes: u64,
+    tally: Tally,
+    /// Lines that passed the filters.
+    matched: u64,
+    levels: BTreeMap<Level, u64>,
+    /// JSON lines without a recognizable level.
+    no_level: u64,
+    earliest: Option<Timestamp>,
+    latest: Option<Timestamp>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes: 0,
+            tally: Tally::default(),
+            matched: 0,
+            levels: BTreeMap::new(),
+            no_level: 0,
+            earliest: None,
+            latest: None,
+        }
+    }
+
+    /// Count one input line of `len` bytes, before its records.
+    pub fn line(&mut self, len: usize) {
+        self.bytes += len as u64 + 1;
+        self.tally.line();
+    }
+
+    /// Count one record; `matched` is whether it passed the filters.
+    pub fn record(&mut self, line: &Line<'_>, matched: bool) {
+        self.matched += u64::from(matched);
+        self.tally.count(line);
+        if !line.is_json() {
+            return;
+        }
+        match line.level() {
+            Some(level) => *self.levels.entry(level).or_default() += 1,
+            None => self.no_level += 1,
+        }
+        if let Some(ts) = line.timestamp().and_then(time::parse_timestamp) {
+            self.earliest = self.earliest.min(Some(ts)).or(Some(ts));
+            self.latest = self.latest.max(Some(ts));
+        }
+    }
+
+    /// Write the summary, e.g.
+    ///
+    /// ```text
+    /// jlo: 10 lines (1.2 KiB) in 0.01s, 1000 lines/s
+    ///   json 9, not json 1, matched 4, filtered out 6
+    ///   protocols: nginx 2, tracing 2, generic 5
+    ///   levels: ERROR 2, WARN 1, INFO 3, none 3
+    ///   time span: 2024-05-03T10:11:12Z .. 2024-05-03T10:11:15Z (3.0s)
+    /// ```
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        let Tally {
+            lines,
+            records,
+            invalid,
+            ref protocols,
+        } = self.tally;
+        let elapsed = self.started.elapsed();
+        let rate = lines as f64 / elapsed.as_secs_f64().max(1e-6);
+        writeln!(
+            out,
+            "jlo: {} ({}) in {}, {rate:.0} lines/s",
+            self.tally.read(),
+            humanize_bytes(self.bytes),
+            humanize_duration(elapsed),
+        )?;
+        writeln!(
+            out,
+            "  json {}, not json {}, matched {}, filtered out {}",
+            records - invalid,
+            invalid,
+            self.matched,
+            records - self.matched,
+        )?;
+        if !protocols.is_empty() {
+            let counts: Vec<_> = protocols
+                .iter()
+                .map(|(name, n)| format!("{name} {n}"))
+                .collect();
+            writeln!(out, "  protocols: {}", counts.join(", "))?;
+        }
+        let mut levels: Vec<_> = self
+            .levels
+            .iter()
+            .rev()
+            .map(|(level, n)| format!("{} {n}", level.as_str()))
+            .collect();
+        if self.no_level > 0 {
+            levels.push(format!("none {}", self.no_level));
+        }
+        if !levels.is_empty() {
+            writeln!(out, "  levels: {}", levels.join(", "))?;
+        }
+        if let (Some(earliest), Some(latest)) = (self.earliest, self.latest) {
+            let span = earliest.duration_until(latest).unsigned_abs();
+            writeln!(
+                out,
+                "  time span: {earliest} .. {latest} ({})",
+                humanize_duration(span)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Lock the `--stats` counters, also after a panic elsewhere poisoned them.
+pub fn lock(stats: &Mutex<Stats>) -> MutexGuard<'_, Stats> {
+    stats.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,283 @@
+//! The key=value tail after a record's message, shared by the protocol renderers.
+
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::RenderCtx;
+
+/// Order of the key=value tail (`--key-order`).
+#[derive(Clone, Debug, Default)]
+pub enum KeyOrder {
+    /// As the renderer emits them: its well-known fields first, then the rest in source order.
+    #[default]
+    Input,
+    Alpha,
+    /// These keys first, in this order, then the rest as emitted.
+    Priority(Arc<[String]>),
+}
+
+impl KeyOrder {
+    /// Parse `alpha`, `input`, or `priority:<key>,<key>,…`. Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "input" => Ok(Self::Input),
+            "alpha" => Ok(Self::Alpha),
+            _ => {
+                let keys = s
+                    .strip_prefix("priority:")
+                    .ok_or_else(|| format!("`{s}`: expected alpha, input, or priority:<keys>"))?;
+                let keys: Arc<[String]> = keys
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if keys.is_empty() {
+                    return Err("priority: needs at least one key".into());
+                }
+                Ok(Self::Priority(keys))
+            }
+        }
+    }
+
+    /// Sort `items` in place; the sort is stable, so ties keep their emitted order.
+    pub fn sort_by_key<T>(&self, items: &mut [T], key: impl Fn(&T) -> &str) {
+        match self {
+            Self::Input => {}
+            Self::Alpha => items.sort_by(|a, b| key(a).cmp(key(b))),
+            Self::Priority(first) => items.sort_by_key(|item| {
+                first
+                    .iter()
+                    .position(|k| k == key(item))
+                    .unwrap_or(first.len())
+            }),
+        }
+    }
+}
+
+/// Fields left out of the output (`--hide`).
+#[derive(Clone, Debug, Default)]
+pub struct KeyFilter {
+    /// Glob patterns (`*`, `?`); `a.*` also hides `a` itself.
+    pub hide: Arc<[String]>,
+}
+
+impl KeyFilter {
+    pub fn shows(&self, key: &str) -> bool {
+        !self.hide.iter().any(|pattern| {
+            glob_match(pattern, key)
+                || pattern
+                    .strip_suffix(".*")
+                    .is_some_and(|parent| glob_match(parent, key))
+        })
+    }
+
+    /// Drop hidden fields from `v`, matching nested keys by their dotted path; objects left
+    /// empty by hiding are dropped too.
+    pub fn apply(&self, v: &mut Value) {
+        self.apply_at(v, "");
+    }
+
+    fn apply_at(&self, v: &mut Value, prefix: &str) {
+        let Value::Object(obj) = v else {
+            return;
+        };
+        obj.retain(|k, child| {
+            let path = format!("{prefix}{k}");
+            if !self.shows(&path) {
+                return false;
+            }
+            let was_empty = matches!(child, Value::Object(o) if o.is_empty());
+            self.apply_at(child, &format!("{path}."));
+            was_empty || !matches!(child, Value::Object(o) if o.is_empty())
+        });
+    }
+}
+
+/// Shell-style wildcard match: `*` is any run of characters, `?` any single one.
+///
+/// Works on the bytes, as it runs for every key of every record; `?` and backtracking step
+/// over whole UTF-8 characters.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t) = (pattern.as_bytes(), text.as_bytes());
+    let char_len = |i: usize| text[i..].chars().next().map_or(1, char::len_utf8);
+    let (mut pi, mut ti) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while ti < t.len() {
+        match p.get(pi) {
+            Some(b'*') => {
+                backtrack = Some((pi + 1, ti));
+                pi += 1;
+            }
+            Some(b'?') => {
+                pi += 1;
+                ti += char_len(ti);
+            }
+            Some(&c) if c == t[ti] => {
+                pi += 1;
+                ti += 1;
+            }
+            _ => match backtrack {
+                Some((star_pi, star_ti)) => {
+                    pi = star_pi;
+                    ti = star_ti + char_len(star_ti);
+                    backtrack = Some((star_pi, ti));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == b'*')
+}
+
+/// Keys with their rendered values, collected so they can be filtered, reordered, and laid out
+/// before writing.
+#[derive(Default)]
+pub struct Tail {
+    entries: Vec<(String, Vec<u8>)>,
+    /// Keys to show dimmed along with their values, e.g. `src`.
+    dimmed: Vec<String>,
+}
+
+impl Tail {
+    /// Start a new entry for `key`; write its (painted) value into the returned buffer.
+    pub fn push(&mut self, key: &str) -> &mut Vec<u8> {
+        self.entries.push((key.to_string(), Vec::new()));
+        &mut self.entries.last_mut().expect("just pushed").1
+    }
+
+    /// Like [`Tail::push`] for secondary information: the key is dimmed too.
+    pub fn push_dimmed(&mut self, key: &str) -> &mut Vec<u8> {
+        self.dimmed.push(key.to_string());
+        self.push(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the entries and end the record.
+    ///
+    /// Pretty output puts them on a continuation line indented by `indent` columns, or with
+    /// `--details` one aligned `key: value` per line; compact output on the same line.
+    pub fn write(mut self, out: &mut dyn Write, ctx: &RenderCtx, indent: usize) -> io::Result<()> {
+        let dimmed = std::mem::take(&mut self.dimmed);
+        let entries = self.shown(ctx);
+        let is_dimmed = |key: &str| dimmed.iter().any(|k| k == key);
+        if entries.is_empty() || ctx.compact {
+            return write_inline(out, ctx, &entries, true, is_dimmed);
+        }
+        if !ctx.details {
+            out.write_all(b"\n")?;
+            // Each entry brings its own leading space
+            out.write_all(&vec![b' '; indent.saturating_sub(1)])?;
+            return write_inline(out, ctx, &entries, true, is_dimmed);
+        }
+        let width = entries
+            .iter()
+            .map(|(k, _)| k.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, value) in &entries {
+            out.write_all(b"\n")?;
+            out.write_all(&vec![b' '; indent])?;
+            let pad = width - key.chars().count();
+            let color = if is_dimmed(key) {
+                ctx.pal.faint
+            } else {
+                ctx.pal.key
+            };
+            write!(out, "{}:{:pad$} ", ctx.pal.paint(color, key), "")?;
+            out.write_all(value)?;
+        }
+        out.write_all(b"\n")
+    }
+
+    /// Write the entries right after a head that already ends in a space, and end the record.
+    pub fn write_inline(mut self, out: &mut dyn Write, ctx: &RenderCtx) -> io::Result<()> {
+        let dimmed = std::mem::take(&mut self.dimmed);
+        let is_dimmed = |key: &str| dimmed.iter().any(|k| k == key);
+        write_inline(out, ctx, &self.shown(ctx), false, is_dimmed)
+    }
+
+    /// The entries left after `--hide`, in `--key-order`.
+    fn shown(mut self, ctx: &RenderCtx) -> Vec<(String, Vec<u8>)> {
+        self.entries.retain(|(k, _)| ctx.filter.shows(k));
+        ctx.key_order.sort_by_key(&mut self.entries, |(k, _)| k);
+        self.entries
+    }
+}
+
+/// ` key=value` pairs on one line (without the first space unless `lead`), then a line break.
+fn write_inline(
+    out: &mut dyn Write,
+    ctx: &RenderCtx,
+    entries: &[(String, Vec<u8>)],
+    lead: bool,
+    is_dimmed: impl Fn(&str) -> bool,
+) -> io::Result<()> {
+    for (i, (key, value)) in entries.iter().enumerate() {
+        if lead || i > 0 {
+            out.write_all(b" ")?;
+        }
+        if is_dimmed(key) {
+            write!(
+                out,
+                "{}",
+                ctx.pal.paint(ctx.pal.faint, format_args!("{key}="))
+            )?;
+        } else {
+            write!(out, "{}=", ctx.pal.paint(ctx.pal.key, key))?;
+        }
+        out.write_all(value)?;
+    }
+    out.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("a.*", "a.b"));
+        assert!(glob_match("*_id", "trace_id"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(!glob_match("a.?", "a."));
+        assert!(!glob_match("user", "username"));
+        // `?` is one character, not one byte
+        assert!(glob_match("gr?ß", "grüß"));
+        assert!(glob_match("?", "🎉"));
+        assert!(!glob_match("??", "🎉"));
+        assert!(glob_match("*ß", "grüß"));
+        assert!(glob_match("*é?", "éé🎉"));
+    }
+
+    #[test]
+    fn hidden_keys() {
+        let filter = KeyFilter {
+            hide: vec!["http.*".to_string(), "*_ms".to_string()].into(),
+        };
+        assert!(!filter.shows("http"));
+        assert!(!filter.shows("http.status"));
+        assert!(!filter.shows("elapsed_ms"));
+        assert!(filter.shows("https"));
+        let mut v = serde_json::json!({"http": {"status": 200}, "a": {"b_ms": 1}, "c": {}, "d": 1});
+        filter.apply(&mut v);
+        assert_eq!(v, serde_json::json!({"c": {}, "d": 1}));
+    }
+
+    #[test]
+    fn priority_order() {
+        let order = KeyOrder::parse("priority: b , a,").unwrap();
+        let mut keys = ["c", "a", "d", "b"];
+        order.sort_by_key(&mut keys, |k| k);
+        assert_eq!(keys, ["b", "a", "c", "d"]);
+        assert!(KeyOrder::parse("priority:,").is_err());
+        assert!(KeyOrder::parse("random").is_err());
+    }
+}
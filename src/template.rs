@@ -0,0 +1,279 @@
+//! User-defined line layouts for `--format`.
+//!
+//! A template is literal text with placeholders:
+//!
+//! - `{name}` inserts a field; `ts`, `level`, `msg`, and `logger` address the normalized
+//!   record, anything else is looked up in the normalized fields and then in the original
+//!   JSON object (`.` descends into nested objects)
+//! - `{name|-}` falls back to `-` when the field is missing
+//! - `{name:>5}`, `{name:<8}`, `{name:^9}` pad to a width (left-aligned if no alignment given)
+//! - `{#red}` … `{#}` colors the text in between; `{#level}` uses the record's level color
+//! - `{{` and `}}` are literal braces
+
+use serde_json::Value;
+use std::io::{self, Write};
+
+use crate::RenderCtx;
+use crate::protocols::Record;
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+    Field {
+        name: String,
+        default: Option<String>,
+        pad: Option<(Align, usize)>,
+    },
+    Style(Style),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Style {
+    Ansi(&'static str),
+    Level,
+    Reset,
+}
+
+impl Template {
+    /// Parse a template string. Intended for use as a clap `value_parser`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut lit = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    lit.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    lit.push('}');
+                }
+                '}' => return Err("unmatched `}` (use `}}` for a literal brace)".into()),
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => return Err(format!("unterminated placeholder `{{{spec}`")),
+                        }
+                    }
+                    if !lit.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut lit)));
+                    }
+                    parts.push(Part::parse(&spec)?);
+                }
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() {
+            parts.push(Part::Literal(lit));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Write one line for `rec`, looking up non-canonical fields in `raw` as a fallback.
+    pub fn render(
+        &self,
+        rec: &Record,
+        raw: Option<&Value>,
+        ctx: &RenderCtx,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let pal = ctx.pal;
+        let mut styled = false;
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.write_all(s.as_bytes())?,
+                Part::Field { name, default, pad } => {
+                    let text = match lookup(rec, raw, name) {
+                        Some(Value::String(s)) => s,
+                        Some(v) => v.to_string(),
+                        None => default.clone().unwrap_or_default(),
+                    };
+                    let text = match name.as_str() {
+                        "ts" | "timestamp" => ctx.time.show(&text).into_owned(),
+                        _ => text,
+                    };
+                    write_padded(&mut *out, &text, *pad)?;
+                }
+                Part::Style(_) if !pal.enabled => {}
+                Part::Style(style) => {
+                    let code = match style {
+                        Style::Ansi(code) => code,
+                        Style::Level => pal.for_level(rec.level),
+                        Style::Reset => pal.reset,
+                    };
+                    out.write_all(code.as_bytes())?;
+                    styled = !matches!(style, Style::Reset);
+                }
+            }
+        }
+        if styled {
+            out.write_all(pal.reset.as_bytes())?;
+        }
+        out.write_all(b"\n")
+    }
+}
+
+impl Part {
+    /// Parse the inside of a `{...}` placeholder.
+    fn parse(spec: &str) -> Result<Self, String> {
+        if let Some(style) = spec.strip_prefix('#') {
+            return Ok(Self::Style(match style {
+                "" | "/" | "reset" => Style::Reset,
+                "level" => Style::Level,
+                name => Style::Ansi(ansi(name).ok_or_else(|| format!("unknown color `{name}`"))?),
+            }));
+        }
+
+        let (field, pad) = match spec.split_once(':') {
+            Some((field, pad)) => (field, Some(parse_pad(pad)?)),
+            None => (spec, None),
+        };
+        let (name, default) = match field.split_once('|') {
+            Some((name, default)) => (name, Some(default.to_string())),
+            None => (field, None),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("empty field name in `{{{spec}}}`"));
+        }
+        Ok(Self::Field {
+            name: name.to_string(),
+            default,
+            pad,
+        })
+    }
+}
+
+/// Parse `>5`, `<8`, `^9`, or a bare width.
+fn parse_pad(s: &str) -> Result<(Align, usize), String> {
+    let (align, width) = match s.chars().next() {
+        Some('<') => (Align::Left, &s[1..]),
+        Some('>') => (Align::Right, &s[1..]),
+        Some('^') => (Align::Center, &s[1..]),
+        _ => (Align::Left, s),
+    };
+    let width = width
+        .parse()
+        .map_err(|_| format!("invalid width `{s}` (expected e.g. `>5`)"))?;
+    Ok((align, width))
+}
+
+fn write_padded(out: &mut dyn Write, s: &str, pad: Option<(Align, usize)>) -> io::Result<()> {
+    let Some((align, width)) = pad else {
+        return out.write_all(s.as_bytes());
+    };
+    match align {
+        Align::Left => write!(out, "{s:<width$}"),
+        Align::Right => write!(out, "{s:>width$}"),
+        Align::Center => write!(out, "{s:^width$}"),
+    }
+}
+
+/// Canonical record slots, then normalized fields, then the original JSON.
+///
+/// The level is spelled in upper case, as in the pretty output.
+fn lookup(rec: &Record, raw: Option<&Value>, name: &str) -> Option<Value> {
+    if name == "level" {
+        return rec.level.map(|l| Value::String(l.as_str().into()));
+    }
+    rec.lookup(raw, name)
+}
+
+fn ansi(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "bold" => "\x1b[1m",
+        "dim" | "faint" => "\x1b[2m",
+        "italic" => "\x1b[3m",
+        "underline" => "\x1b[4m",
+        "red" => "\x1b[31m",
+        "green" => "\x1b[32m",
+        "yellow" => "\x1b[33m",
+        "blue" => "\x1b[34m",
+        "magenta" => "\x1b[35m",
+        "cyan" => "\x1b[36m",
+        "white" => "\x1b[37m",
+        "gray" | "grey" => "\x1b[90m",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        let t = Template::parse("{ts} {#level}{level:>5}{#} {msg|-} {{x}}").unwrap();
+        let parts: Vec<String> = t
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(s) => format!("lit {s:?}"),
+                Part::Field { name, default, pad } => {
+                    format!(
+                        "field {name} {default:?} {:?}",
+                        pad.map(|(a, w)| (a as u8, w))
+                    )
+                }
+                Part::Style(Style::Ansi(code)) => format!("ansi {code:?}"),
+                Part::Style(Style::Level) => "level color".into(),
+                Part::Style(Style::Reset) => "reset".into(),
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                "field ts None None",
+                r#"lit " ""#,
+                "level color",
+                "field level None Some((1, 5))",
+                "reset",
+                r#"lit " ""#,
+                r#"field msg Some("-") None"#,
+                r#"lit " {x}""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert!(matches!(parse_pad(">5"), Ok((Align::Right, 5))));
+        assert!(matches!(parse_pad("<8"), Ok((Align::Left, 8))));
+        assert!(matches!(parse_pad("^9"), Ok((Align::Center, 9))));
+        assert!(matches!(parse_pad("12"), Ok((Align::Left, 12))));
+        assert!(parse_pad(">").is_err());
+        assert!(parse_pad("wide").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        for (template, error) in [
+            ("{msg", "unterminated placeholder `{msg`"),
+            ("msg}", "unmatched `}`"),
+            ("{}", "empty field name in `{}`"),
+            ("{ |-}", "empty field name in `{ |-}`"),
+            ("{#purple}", "unknown color `purple`"),
+            ("{msg:>x}", "invalid width `>x`"),
+        ] {
+            let e = Template::parse(template).unwrap_err();
+            assert!(e.starts_with(error), "{template}: {e}");
+        }
+    }
+}
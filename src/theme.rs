@@ -0,0 +1,68 @@
+//! Built-in color themes for the pretty output.
+
+use clap::ValueEnum;
+
+/// Selectable color theme (`--theme`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+    /// The basic 8-color ANSI set, rendered by the terminal's own color scheme
+    #[default]
+    Ansi,
+    /// 256-color theme for dark backgrounds
+    Dark,
+    /// 256-color theme for light backgrounds
+    Light,
+    /// Truecolor Solarized accents, readable on both Solarized variants
+    Solarized,
+}
+
+/// Escape sequences for the roles the renderers color.
+#[derive(Copy, Clone, Debug)]
+pub struct Colors {
+    pub info: &'static str,
+    pub warn: &'static str,
+    pub error: &'static str,
+    pub status3xx: &'static str,
+    pub faint: &'static str,
+    /// Background for whole error lines with `--highlight-errors`.
+    pub error_bg: &'static str,
+}
+
+impl Theme {
+    pub fn colors(self) -> Colors {
+        match self {
+            Self::Ansi => Colors {
+                info: "\x1b[32m",      // green
+                warn: "\x1b[33m",      // yellow
+                error: "\x1b[31m",     // red
+                status3xx: "\x1b[36m", // cyan
+                faint: "\x1b[2m",
+                error_bg: "\x1b[48;5;52m",
+            },
+            Self::Dark => Colors {
+                info: "\x1b[38;5;114m",
+                warn: "\x1b[38;5;221m",
+                error: "\x1b[38;5;203m",
+                status3xx: "\x1b[38;5;80m",
+                faint: "\x1b[38;5;245m",
+                error_bg: "\x1b[48;5;52m",
+            },
+            Self::Light => Colors {
+                info: "\x1b[38;5;28m",
+                warn: "\x1b[38;5;130m",
+                error: "\x1b[38;5;160m",
+                status3xx: "\x1b[38;5;31m",
+                faint: "\x1b[38;5;244m",
+                error_bg: "\x1b[48;5;224m",
+            },
+            Self::Solarized => Colors {
+                info: "\x1b[38;2;133;153;0m",       // green  #859900
+                warn: "\x1b[38;2;181;137;0m",       // yellow #b58900
+                error: "\x1b[38;2;220;50;47m",      // red    #dc322f
+                status3xx: "\x1b[38;2;42;161;152m", // cyan #2aa198
+                faint: "\x1b[38;2;147;161;161m",    // base1  #93a1a1
+                error_bg: "\x1b[48;2;68;36;36m",
+            },
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Parsing of human-friendly durations used by command-line flags, and of log timestamps.
+
+use jiff::Timestamp;
+use jiff::tz::TimeZone;
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Parse durations such as `500ms`, `15m`, `1h30m`, `1.5s`, or `2d`.
+///
+/// A bare number is taken as seconds. Intended for use as a clap `value_parser`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".into());
+    }
+    if let Ok(secs) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).map_err(|e| format!("{s}: {e}"));
+    }
+
+    let mut total = 0f64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(|| format!("{s}: missing unit after number"))?;
+        if num_len == 0 {
+            return Err(format!("{s}: expected a number"));
+        }
+        let value: f64 = rest[..num_len]
+            .parse()
+            .map_err(|_| format!("{s}: invalid number"))?;
+        rest = &rest[num_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" | "sec" => 1.0,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            unit => return Err(format!("{s}: unknown unit `{unit}`")),
+        };
+        total += value * scale;
+        rest = &rest[unit_len..];
+    }
+    Duration::try_from_secs_f64(total).map_err(|e| format!("{s}: {e}"))
+}
+
+/// How timestamps are shown in the pretty output.
+#[derive(Clone, Default)]
+pub struct TimeDisplay {
+    /// Reprint parseable timestamps in this zone (`--local`, `--tz`); `None` keeps them verbatim.
+    pub zone: Option<TimeZone>,
+    /// Show offsets from this instant instead (`--timestamp=relative`); the first parseable
+    /// timestamp sets it unless it was set up front. Shared by the copies of the settings, e.g.
+    /// the `--jobs` workers'.
+    pub relative: Option<Arc<OnceLock<Timestamp>>>,
+}
+
+impl TimeDisplay {
+    /// The text to show for a timestamp field value.
+    pub fn show<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        if let Some(anchor) = &self.relative {
+            return match parse_timestamp(raw) {
+                Some(ts) => Cow::Owned(format_offset(
+                    ts.as_millisecond() - anchor.get_or_init(|| ts).as_millisecond(),
+                )),
+                None => Cow::Borrowed(raw),
+            };
+        }
+        let Some(zone) = &self.zone else {
+            return Cow::Borrowed(raw);
+        };
+        match parse_timestamp(raw) {
+            Some(ts) => Cow::Owned(
+                ts.to_zoned(zone.clone())
+                    .strftime("%Y-%m-%dT%H:%M:%S%.f%:z")
+                    .to_string(),
+            ),
+            None => Cow::Borrowed(raw),
+        }
+    }
+}
+
+/// `850µs`, `53ms`, `1.2s`, `3m04s`, or `2h05m`.
+pub fn humanize_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        _ if d < Duration::from_millis(1) => format!("{}µs", d.as_micros()),
+        0 => format!("{}ms", d.as_millis()),
+        1..60 => format!("{:.1}s", d.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Seconds per unit of a numeric duration field, judged by its name: `elapsed_ms` is in
+/// milliseconds, `req_time` or `duration` in seconds, Kong's `latencies.*` in milliseconds;
+/// `None` if it isn't a duration.
+pub fn duration_unit(key: &str) -> Option<f64> {
+    let key = key.to_ascii_lowercase();
+    // Kong's `latencies.request`, `latencies.proxy`, … are in milliseconds
+    if key.starts_with("latencies.") || key.contains(".latencies.") {
+        return Some(1e-3);
+    }
+    let key = key.rsplit('.').next().unwrap_or(&key);
+    for (suffix, unit) in [
+        ("_ns", 1e-9),
+        ("_us", 1e-6),
+        ("_ms", 1e-3),
+        // Also MongoDB's `durationMillis`
+        ("millis", 1e-3),
+    ] {
+        if key.strip_suffix(suffix).is_some_and(|k| !k.is_empty()) {
+            return Some(unit);
+        }
+    }
+    let seconds = matches!(
+        key,
+        "duration" | "elapsed" | "latency" | "took" | "rt" | "up"
+    ) || ["_time", "_duration", "_secs", "_seconds", "_latency"]
+        .iter()
+        .any(|suffix| key.ends_with(suffix));
+    seconds.then_some(1.0)
+}
+
+/// `+00:03.214`, `+1:02:03.000`, or `-00:00.250`.
+fn format_offset(millis: i64) -> String {
+    let sign = if millis < 0 { '-' } else { '+' };
+    let millis = millis.unsigned_abs();
+    let (secs, ms) = (millis / 1000, millis % 1000);
+    let (mins, secs) = (secs / 60, secs % 60);
+    match (mins / 60, mins % 60) {
+        (0, mins) => format!("{sign}{mins:02}:{secs:02}.{ms:03}"),
+        (hours, mins) => format!("{sign}{hours}:{mins:02}:{secs:02}.{ms:03}"),
+    }
+}
+
+/// Parse a zone name for `--tz`: an IANA name such as `Europe/Berlin`, or `UTC`.
+pub fn parse_time_zone(name: &str) -> Result<TimeZone, String> {
+    TimeZone::get(name).map_err(|e| format!("{name}: {e}"))
+}
+
+/// Parse a `--since`/`--until` bound: an RFC 3339 timestamp, a local date and time
+/// (`2024-05-03 10:00`), a local time of day today (`10:30`, `10:30:15`), or a duration
+/// ago (`15m`, `2h`). Intended for use as a clap `value_parser`.
+pub fn parse_time_bound(s: &str) -> Result<Timestamp, String> {
+    let s = s.trim();
+    let local = |dt: jiff::civil::DateTime| {
+        dt.to_zoned(TimeZone::system())
+            .map(|z| z.timestamp())
+            .map_err(|e| format!("{s}: {e}"))
+    };
+    if let Ok(time) = s.parse::<jiff::civil::Time>() {
+        return local(jiff::Zoned::now().date().to_datetime(time));
+    }
+    if let Ok(dt) = s.parse::<jiff::civil::DateTime>() {
+        return local(dt);
+    }
+    if let Some(ts) = parse_timestamp(s) {
+        return Ok(ts);
+    }
+    let ago = parse_duration(s).map_err(|_| {
+        format!("{s}: expected a timestamp, a time of day like 10:30, or a duration like 15m")
+    })?;
+    let ago = jiff::SignedDuration::try_from(ago).map_err(|e| format!("{s}: {e}"))?;
+    Timestamp::now()
+        .checked_sub(ago)
+        .map_err(|e| format!("{s}: {e}"))
+}
+
+/// Parse RFC 3339 timestamps, Unix epochs (seconds, milliseconds, microseconds, or
+/// nanoseconds, told apart by magnitude), and nginx `$time_local` (`03/May/2024:10:11:12 +0000`).
+pub fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    let s = s.trim();
+    if let Ok(ts) = s.parse::<Timestamp>() {
+        return Some(ts);
+    }
+    if let Ok(epoch) = s.parse::<i64>() {
+        let scale = match epoch.unsigned_abs() {
+            e if e < 100_000_000_000 => 1_000_000_000,
+            e if e < 100_000_000_000_000 => 1_000_000,
+            e if e < 100_000_000_000_000_000 => 1_000,
+            _ => 1,
+        };
+        return Timestamp::from_nanosecond(i128::from(epoch) * scale).ok();
+    }
+    if let Ok(epoch) = s.parse::<f64>()
+        && epoch.is_finite()
+    {
+        // Fractional epochs are seconds or milliseconds, e.g. nginx `$msec`
+        let secs = if epoch.abs() < 1e11 {
+            epoch
+        } else {
+            epoch / 1e3
+        };
+        return Timestamp::from_nanosecond((secs * 1e9) as i128).ok();
+    }
+    jiff::fmt::strtime::parse("%d/%b/%Y:%H:%M:%S %z", s)
+        .and_then(|tm| tm.to_timestamp())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        assert_eq!(parse_duration("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 3min "), Ok(Duration::from_secs(180)));
+        for invalid in ["", "5x", "m5", "1h30", "-5s", "1..5s"] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn timestamps() {
+        let ts = |s: &str| parse_timestamp(s).map(|ts| ts.to_string());
+        let expected = Some("2024-05-03T10:11:12Z".to_string());
+        assert_eq!(ts("2024-05-03T10:11:12Z"), expected);
+        assert_eq!(ts("2024-05-03T12:11:12+02:00"), expected);
+        assert_eq!(ts("03/May/2024:12:11:12 +0200"), expected);
+        // Epochs in seconds, milliseconds, microseconds, and nanoseconds
+        assert_eq!(ts("1714731072"), expected);
+        assert_eq!(ts("1714731072000"), expected);
+        assert_eq!(ts("1714731072000000"), expected);
+        assert_eq!(ts("1714731072000000000"), expected);
+        assert_eq!(
+            ts("1714731072.5"),
+            Some("2024-05-03T10:11:12.5Z".to_string())
+        );
+        assert_eq!(
+            ts("1714731072500.0"),
+            Some("2024-05-03T10:11:12.5Z".to_string())
+        );
+        assert_eq!(ts("yesterday"), None);
+        assert_eq!(ts("NaN"), None);
+    }
+
+    #[test]
+    fn time_bounds() {
+        assert_eq!(
+            parse_time_bound("2024-05-03T10:11:12Z").map(|ts| ts.to_string()),
+            Ok("2024-05-03T10:11:12Z".to_string())
+        );
+        let ago = parse_time_bound("15m").unwrap();
+        let expected = Timestamp::now() - jiff::SignedDuration::from_mins(15);
+        assert!(ago.duration_until(expected).abs() < jiff::SignedDuration::from_secs(5));
+        assert!(parse_time_bound("soon").is_err());
+    }
+
+    #[test]
+    fn humanized_durations() {
+        assert_eq!(humanize_duration(Duration::from_micros(250)), "250µs");
+        assert_eq!(humanize_duration(Duration::from_millis(42)), "42ms");
+        assert_eq!(humanize_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(humanize_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(humanize_duration(Duration::from_secs(3720)), "1h02m");
+    }
+}
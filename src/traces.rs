@@ -0,0 +1,267 @@
+//! `--group-by-trace`: records sharing a trace or request ID, held back and written as one block
+//! once the trace goes quiet.
+
+use jiff::Timestamp;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::filter::Line;
+use crate::links;
+use crate::memory;
+use crate::protocols::{Detection, Record};
+use crate::time;
+
+/// Traces held back at once, at most; the oldest is written early to make room.
+const MAX_OPEN: usize = 10_000;
+
+/// Fields naming the service that wrote a record, in order of preference; the logger otherwise.
+const SERVICE_FIELDS: [&str; 6] = [
+    "service",
+    "service.name",
+    "service_name",
+    "serviceName",
+    "resource.labels.service_name",
+    "app",
+];
+
+/// The earliest and latest timestamp of some records.
+type Span = (Timestamp, Timestamp);
+
+pub struct TraceGroups {
+    /// How long a trace has to go without records to count as finished.
+    idle: Duration,
+    open: HashMap<String, Trace>,
+    /// Roughly how many bytes the held records take.
+    held: usize,
+    /// Traces started so far, to write them in the order they began.
+    started: u64,
+    /// The latest timestamp in the input: the clock for logs read faster than they were written.
+    latest: Option<Timestamp>,
+    /// When quiet traces were last looked for, by the wall clock and the input's.
+    scanned: (Instant, Option<Timestamp>),
+}
+
+/// A record held back until its trace is written.
+pub struct Held {
+    pub buf: Vec<u8>,
+    pub v: Value,
+    pub note: Option<String>,
+    /// How the record's protocol was picked when it was read.
+    pub detection: Detection,
+}
+
+impl Held {
+    /// The line and its parsed value, which takes about three times as much as its text.
+    fn memory(&self) -> usize {
+        self.buf.len() * 4 + self.note.as_ref().map_or(0, String::len) + memory::ENTRY
+    }
+}
+
+struct Trace {
+    started: u64,
+    records: Vec<Held>,
+    /// Timestamps of the earliest and latest record.
+    span: Option<Span>,
+    /// When the last record arrived.
+    arrived: Instant,
+    /// Records and their time span per service, in order of appearance.
+    services: Vec<(String, u64, Option<Span>)>,
+}
+
+/// A finished trace: the summary line and the records under it.
+pub struct Group {
+    pub header: String,
+    pub records: Vec<Held>,
+}
+
+impl TraceGroups {
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            open: HashMap::new(),
+            held: 0,
+            started: 0,
+            latest: None,
+            scanned: (Instant::now(), None),
+        }
+    }
+
+    /// Hold back a record with a trace or request ID; returns `false` for others, which are
+    /// written right away.
+    pub fn push(&mut self, line: &Line<'_>, note: Option<&str>) -> bool {
+        let (Some(v), Some(rec)) = (line.value(), line.record()) else {
+            return false;
+        };
+        let ts = line.timestamp().and_then(time::parse_timestamp);
+        if let Some(ts) = ts {
+            self.latest = Some(self.latest.map_or(ts, |latest| latest.max(ts)));
+        }
+        let Some(id) = links::find_correlation_id(v) else {
+            return false;
+        };
+        let trace = self.open.entry(id.to_string()).or_insert_with(|| {
+            self.started += 1;
+            Trace {
+                started: self.started,
+                records: Vec::new(),
+                span: None,
+                arrived: Instant::now(),
+                services: Vec::new(),
+            }
+        });
+        trace.arrived = Instant::now();
+        if let Some(ts) = ts {
+            trace.span = Some(widen(trace.span, ts));
+        }
+        if let Some(service) = service(rec, v) {
+            match trace.services.iter_mut().find(|(s, _, _)| *s == service) {
+                Some((_, n, span)) => {
+                    *n += 1;
+                    *span = ts.map(|ts| widen(*span, ts)).or(*span);
+                }
+                None => trace.services.push((service, 1, ts.map(|ts| (ts, ts)))),
+            }
+        }
+        let held = Held {
+            buf: line.buf.to_vec(),
+            v: v.clone(),
+            note: note.map(str::to_string),
+            detection: line.detection,
+        };
+        self.held += held.memory();
+        trace.records.push(held);
+        true
+    }
+
+    /// Roughly how many bytes the open traces take, for `--max-memory`.
+    pub fn memory(&self) -> usize {
+        self.held
+            + self
+                .open
+                .keys()
+                .map(|id| id.len() + memory::ENTRY)
+                .sum::<usize>()
+    }
+
+    /// The older half of the open traces, written early to stay within `--max-memory`.
+    pub fn evict(&mut self) -> Vec<Group> {
+        let mut ids: Vec<(u64, String)> = self
+            .open
+            .iter()
+            .map(|(id, t)| (t.started, id.clone()))
+            .collect();
+        ids.sort();
+        ids.truncate(ids.len().div_ceil(2));
+        ids.into_iter().map(|(_, id)| self.close(&id)).collect()
+    }
+
+    /// The traces without records for the idle time, by the wall clock or the input's
+    /// timestamps, and any beyond [`MAX_OPEN`]; in the order they began.
+    pub fn quiet(&mut self) -> Vec<Group> {
+        let now = Instant::now();
+        let step = self.idle / 10;
+        // Looking at every open trace for every record would be quadratic
+        let input_moved = match (self.latest, self.scanned.1) {
+            (Some(latest), Some(scanned)) => since(latest, scanned) >= step,
+            (latest, scanned) => latest.is_some() && scanned.is_none(),
+        };
+        if now - self.scanned.0 < step && !input_moved && self.open.len() <= MAX_OPEN {
+            return Vec::new();
+        }
+        self.scanned = (now, self.latest);
+
+        let (idle, latest) = (self.idle, self.latest);
+        let mut ids: Vec<(u64, String)> = self
+            .open
+            .iter()
+            .filter(|(_, t)| {
+                now - t.arrived >= idle
+                    || matches!((latest, t.span), (Some(latest), Some((_, last))) if since(latest, last) >= idle)
+            })
+            .map(|(id, t)| (t.started, id.clone()))
+            .collect();
+        let over = (self.open.len() - ids.len()).saturating_sub(MAX_OPEN);
+        if over > 0 {
+            let mut rest: Vec<_> = self
+                .open
+                .iter()
+                .filter(|(id, _)| !ids.iter().any(|(_, quiet)| quiet == *id))
+                .map(|(id, t)| (t.started, id.clone()))
+                .collect();
+            rest.sort();
+            ids.extend(rest.into_iter().take(over));
+        }
+        ids.sort();
+        ids.into_iter().map(|(_, id)| self.close(&id)).collect()
+    }
+
+    /// Every trace still open, at the end of the input.
+    pub fn finish(&mut self) -> Vec<Group> {
+        let mut ids: Vec<(u64, String)> = self
+            .open
+            .iter()
+            .map(|(id, t)| (t.started, id.clone()))
+            .collect();
+        ids.sort();
+        ids.into_iter().map(|(_, id)| self.close(&id)).collect()
+    }
+
+    /// Remove a trace, summarized as `trace <id> · 7 records in 1.2s · api 4 in 820ms, db 3 in 310ms`.
+    fn close(&mut self, id: &str) -> Group {
+        let trace = self.open.remove(id).expect("open trace");
+        self.held -= trace.records.iter().map(Held::memory).sum::<usize>();
+        // A single record takes no time
+        let took = |n: u64, span: Option<Span>| {
+            span.filter(|_| n > 1)
+                .map(|(first, last)| time::humanize_duration(since(last, first)))
+        };
+        let n = trace.records.len();
+        let mut header = format!("trace {id} · {n} {}", plural(n as u64, "record"));
+        if let Some(took) = took(n as u64, trace.span) {
+            header += &format!(" in {took}");
+        }
+        let services: Vec<String> = trace
+            .services
+            .iter()
+            .map(|(service, n, span)| match took(*n, *span) {
+                Some(took) => format!("{service} {n} in {took}"),
+                None => format!("{service} {n}"),
+            })
+            .collect();
+        if !services.is_empty() {
+            header += &format!(" · {}", services.join(", "));
+        }
+        Group {
+            header,
+            records: trace.records,
+        }
+    }
+}
+
+fn service(rec: &Record, v: &Value) -> Option<String> {
+    SERVICE_FIELDS
+        .into_iter()
+        .find_map(|name| match rec.lookup(Some(v), name)? {
+            Value::String(s) => Some(s),
+            _ => None,
+        })
+        .or_else(|| rec.logger.clone())
+}
+
+fn widen(span: Option<Span>, ts: Timestamp) -> Span {
+    span.map_or((ts, ts), |(first, last)| (first.min(ts), last.max(ts)))
+}
+
+/// How much later `a` is than `b`; zero if it isn't.
+fn since(a: Timestamp, b: Timestamp) -> Duration {
+    Duration::from_millis((a.as_millisecond() - b.as_millisecond()).max(0) as u64)
+}
+
+fn plural(n: u64, noun: &str) -> String {
+    if n == 1 {
+        noun.to_string()
+    } else {
+        format!("{noun}s")
+    }
+}